@@ -0,0 +1,248 @@
+use crate::{renderer::TextureId, sprite::SpriteRegion};
+use image::{ImageError, RgbaImage};
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+pub const ATLAS_MAX_WIDTH: u32 = 1024;
+
+// Space left between packed images
+pub const ATLAS_PADDING: u32 = 1;
+
+const ATLAS_IMAGE_EXTENSION: &str = "png";
+
+#[derive(Debug)]
+pub enum AtlasError {
+    Io(PathBuf, io::Error),
+    Image(PathBuf, ImageError),
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    TooWide(String),
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AtlasError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            AtlasError::Image(path, e) => write!(f, "{}: {}", path.display(), e),
+            AtlasError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            AtlasError::TooWide(name) => write!(
+                f,
+                "Sprite `{}` is wider than the atlas ({} pixels)",
+                name, ATLAS_MAX_WIDTH
+            ),
+        }
+    }
+}
+
+/// Named sprite regions within a texture, so sprites can be looked up by name (e.g. `"brick/red/cracked"`)
+/// instead of by hand-written coordinates.
+pub struct TextureAtlas {
+    tex_id: TextureId,
+    regions: HashMap<String, SpriteRegion>,
+}
+
+impl TextureAtlas {
+    pub fn new(tex_id: TextureId) -> Self {
+        TextureAtlas {
+            tex_id,
+            regions: HashMap::new(),
+        }
+    }
+
+    /// Load the regions of an existing texture from a manifest file, with one sprite per line:
+    /// `name x y w h`. Empty lines and lines starting with `//` are skipped.
+    pub fn load_manifest(tex_id: TextureId, path: &Path) -> Result<Self, AtlasError> {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => return Err(AtlasError::Io(path.to_owned(), e)),
+        };
+
+        let mut atlas = TextureAtlas::new(tex_id);
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let parse_error = |message: &str| AtlasError::Parse {
+                path: path.to_owned(),
+                line: i + 1,
+                message: message.to_owned(),
+            };
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 5 {
+                return Err(parse_error("Expected `name x y w h`"));
+            }
+
+            let numbers = fields[1..]
+                .iter()
+                .map(|field| field.parse::<u32>())
+                .collect::<Result<Vec<u32>, _>>()
+                .map_err(|_| parse_error("Expected whole numbers for `x y w h`"))?;
+
+            let name = fields[0];
+            if atlas.regions.contains_key(name) {
+                return Err(parse_error(&format!("Sprite `{}` is defined twice", name)));
+            }
+
+            atlas.insert(
+                name,
+                SpriteRegion {
+                    x: numbers[0],
+                    y: numbers[1],
+                    w: numbers[2],
+                    h: numbers[3],
+                },
+            );
+        }
+
+        Ok(atlas)
+    }
+
+    /// Pack images into a single texture, returning the atlas and the packed image to create the texture from.
+    /// Images are placed on shelves, tallest first, so the result only depends on the images given.
+    pub fn pack(
+        tex_id: TextureId,
+        mut images: Vec<(String, RgbaImage)>,
+    ) -> Result<(Self, RgbaImage), AtlasError> {
+        images.sort_by(|(a_name, a), (b_name, b)| {
+            b.height().cmp(&a.height()).then_with(|| a_name.cmp(b_name))
+        });
+
+        let mut atlas = TextureAtlas::new(tex_id);
+        let mut x: u32 = 0;
+        let mut y: u32 = 0;
+        let mut shelf_height: u32 = 0;
+        let mut width: u32 = 0;
+        for (name, image) in images.iter() {
+            if image.width() > ATLAS_MAX_WIDTH {
+                return Err(AtlasError::TooWide(name.clone()));
+            }
+
+            // Start a new shelf once this one is full
+            if x + image.width() > ATLAS_MAX_WIDTH {
+                x = 0;
+                y += shelf_height + ATLAS_PADDING;
+                shelf_height = 0;
+            }
+
+            atlas.insert(
+                name,
+                SpriteRegion {
+                    x,
+                    y,
+                    w: image.width(),
+                    h: image.height(),
+                },
+            );
+
+            width = width.max(x + image.width());
+            shelf_height = shelf_height.max(image.height());
+            x += image.width() + ATLAS_PADDING;
+        }
+
+        let mut packed = RgbaImage::new(width.max(1), (y + shelf_height).max(1));
+        for (name, image) in images.iter() {
+            let region = atlas.regions[name];
+            for (image_x, image_y, pixel) in image.enumerate_pixels() {
+                packed.put_pixel(region.x + image_x, region.y + image_y, *pixel);
+            }
+        }
+
+        Ok((atlas, packed))
+    }
+
+    /// Pack every PNG in a directory (and its subdirectories) into a single texture. Sprites are named by their
+    /// path within the directory, without the extension, so `brick/red/cracked.png` becomes `brick/red/cracked`.
+    pub fn pack_directory(tex_id: TextureId, dir: &Path) -> Result<(Self, RgbaImage), AtlasError> {
+        let mut images = Vec::new();
+        collect_images(dir, "", &mut images)?;
+        TextureAtlas::pack(tex_id, images)
+    }
+
+    pub fn tex_id(&self) -> TextureId {
+        self.tex_id
+    }
+
+    pub fn insert(&mut self, name: &str, region: SpriteRegion) {
+        self.regions.insert(name.to_owned(), region);
+    }
+
+    pub fn region(&self, name: &str) -> Option<SpriteRegion> {
+        self.regions.get(name).copied()
+    }
+
+    /// Get a sprite's region, or an empty region (so nothing is drawn) if the atlas doesn't have it.
+    pub fn sprite(&self, name: &str) -> SpriteRegion {
+        self.region(name).unwrap_or_else(|| {
+            eprintln!(
+                "Sprite `{}` isn't in the atlas for texture {}!",
+                name, self.tex_id
+            );
+            SpriteRegion {
+                x: 0,
+                y: 0,
+                w: 0,
+                h: 0,
+            }
+        })
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.regions.keys().map(|name| name.as_str())
+    }
+}
+
+fn collect_images(
+    dir: &Path,
+    prefix: &str,
+    images: &mut Vec<(String, RgbaImage)>,
+) -> Result<(), AtlasError> {
+    let entries = fs::read_dir(dir).map_err(|e| AtlasError::Io(dir.to_owned(), e))?;
+    for entry in entries {
+        let path = entry.map_err(|e| AtlasError::Io(dir.to_owned(), e))?.path();
+
+        // Directories keep their whole name, images lose their extension
+        let stem = if path.is_dir() {
+            path.file_name()
+        } else {
+            path.file_stem()
+        };
+        let stem = match stem.and_then(|stem| stem.to_str()) {
+            Some(stem) => stem,
+            None => continue,
+        };
+
+        // Names always use `/`, whatever the platform
+        let name = if prefix.is_empty() {
+            stem.to_owned()
+        } else {
+            format!("{}/{}", prefix, stem)
+        };
+
+        if path.is_dir() {
+            collect_images(&path, &name, images)?;
+        } else if path
+            .extension()
+            .map_or(false, |ext| ext == ATLAS_IMAGE_EXTENSION)
+        {
+            let image = image::open(&path)
+                .map_err(|e| AtlasError::Image(path.clone(), e))?
+                .to_rgba();
+            images.push((name, image));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use crate::input::{GamepadAxis, GamepadButton, GamepadDevice};
+use gilrs::{Axis, Button, EventType, Gilrs};
+
+/// The gamepads connected to the computer. They're read as if they were a single gamepad, so any of them can be
+/// used to play.
+pub struct Gamepads {
+    gilrs: Gilrs,
+}
+
+impl Gamepads {
+    /// Returns `None` if gamepads aren't supported, in which case the game is played with the keyboard alone.
+    pub fn new() -> Option<Self> {
+        match Gilrs::new() {
+            Ok(gilrs) => {
+                for (_, gamepad) in gilrs.gamepads() {
+                    println!("[Gamepads] Found {}", gamepad.name());
+                }
+
+                Some(Gamepads { gilrs })
+            }
+            Err(e) => {
+                eprintln!("Failed to open gamepads! {}", e);
+                None
+            }
+        }
+    }
+
+    /// Catch up on everything that happened since the last poll. Call this once a frame, before reading the
+    /// gamepads.
+    pub fn poll(&mut self) {
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::Connected => {
+                    println!(
+                        "[Gamepads] Connected {}",
+                        self.gilrs.gamepad(event.id).name()
+                    )
+                }
+                EventType::Disconnected => {
+                    println!(
+                        "[Gamepads] Disconnected {}",
+                        self.gilrs.gamepad(event.id).name()
+                    )
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl GamepadDevice for Gamepads {
+    fn is_button_held(&self, button: GamepadButton) -> bool {
+        let button = gilrs_button(button);
+        self.gilrs
+            .gamepads()
+            .any(|(_, gamepad)| gamepad.is_pressed(button))
+    }
+
+    /// The axis of whichever gamepad is pushed furthest.
+    fn axis(&self, axis: GamepadAxis) -> f32 {
+        let axis = gilrs_axis(axis);
+        self.gilrs
+            .gamepads()
+            .map(|(_, gamepad)| gamepad.value(axis))
+            .fold(0.0f32, |furthest, value| {
+                if value.abs() > furthest.abs() {
+                    value
+                } else {
+                    furthest
+                }
+            })
+    }
+}
+
+fn gilrs_button(button: GamepadButton) -> Button {
+    match button {
+        GamepadButton::South => Button::South,
+        GamepadButton::East => Button::East,
+        GamepadButton::West => Button::West,
+        GamepadButton::North => Button::North,
+        GamepadButton::LeftShoulder => Button::LeftTrigger,
+        GamepadButton::RightShoulder => Button::RightTrigger,
+        GamepadButton::Select => Button::Select,
+        GamepadButton::Start => Button::Start,
+        GamepadButton::DPadUp => Button::DPadUp,
+        GamepadButton::DPadDown => Button::DPadDown,
+        GamepadButton::DPadLeft => Button::DPadLeft,
+        GamepadButton::DPadRight => Button::DPadRight,
+    }
+}
+
+fn gilrs_axis(axis: GamepadAxis) -> Axis {
+    match axis {
+        GamepadAxis::LeftStickX => Axis::LeftStickX,
+        GamepadAxis::LeftStickY => Axis::LeftStickY,
+        GamepadAxis::RightStickX => Axis::RightStickX,
+        GamepadAxis::RightStickY => Axis::RightStickY,
+    }
+}
@@ -1,13 +1,491 @@
 use ::winit::{event::ElementState, event::KeyboardInput};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub use ::winit::event::VirtualKeyCode;
 
-#[derive(Default, Clone)]
+// How far a stick has to be pushed before it does anything, so sticks that don't rest exactly at the center aren't
+// read as being pushed
+pub const GAMEPAD_STICK_DEADZONE: f32 = 0.2;
+
+// How far past the deadzone a stick has to be pushed in a direction for it to count as held, like a button
+pub const GAMEPAD_STICK_PRESS_THRESHOLD: f32 = 0.5;
+
+macro_rules! key_names {
+    ($($key:ident),* $(,)?) => {
+        // Keys that can be named in files (replays, settings, etc.), named after their `VirtualKeyCode` variant
+        const KEY_NAMES: &[(VirtualKeyCode, &str)] = &[$((VirtualKeyCode::$key, stringify!($key))),*];
+    };
+}
+
+key_names! {
+    Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, A, B, C, D, E, F, G, H, I, J, K, L, M,
+    N, O, P, Q, R, S, T, U, V, W, X, Y, Z, Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Insert, Home, Delete, End, PageDown, PageUp, Left, Up, Right, Down, Back, Return, Space, Tab,
+    LShift, RShift, LControl, RControl, LAlt, RAlt, Numpad0, Numpad1, Numpad2, Numpad3, Numpad4,
+    Numpad5, Numpad6, Numpad7, Numpad8, Numpad9, NumpadEnter, Add, Subtract, Minus, Equals, LBracket,
+    RBracket, Semicolon, Apostrophe, Comma, Period, Slash, Backslash, Grave,
+}
+
+/// Get the name of a key, as used in files. Returns `None` for keys that can't be named.
+pub fn key_name(keycode: VirtualKeyCode) -> Option<&'static str> {
+    KEY_NAMES
+        .iter()
+        .find(|(key, _)| *key == keycode)
+        .map(|(_, name)| *name)
+}
+
+/// Get a key from its name, as returned by `key_name`.
+pub fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+    KEY_NAMES
+        .iter()
+        .find(|(_, key_name)| *key_name == name)
+        .map(|(key, _)| *key)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    // The face buttons, named by where they are so they're the same on every gamepad (e.g. `South` is A on an Xbox
+    // controller and Cross on a PlayStation controller)
+    South,
+    East,
+    West,
+    North,
+    LeftShoulder,
+    RightShoulder,
+    Select,
+    Start,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+impl GamepadButton {
+    pub const ALL: [GamepadButton; 12] = [
+        GamepadButton::South,
+        GamepadButton::East,
+        GamepadButton::West,
+        GamepadButton::North,
+        GamepadButton::LeftShoulder,
+        GamepadButton::RightShoulder,
+        GamepadButton::Select,
+        GamepadButton::Start,
+        GamepadButton::DPadUp,
+        GamepadButton::DPadDown,
+        GamepadButton::DPadLeft,
+        GamepadButton::DPadRight,
+    ];
+
+    /// The name of the button in files, named after its variant.
+    pub fn name(self) -> &'static str {
+        match self {
+            GamepadButton::South => "South",
+            GamepadButton::East => "East",
+            GamepadButton::West => "West",
+            GamepadButton::North => "North",
+            GamepadButton::LeftShoulder => "LeftShoulder",
+            GamepadButton::RightShoulder => "RightShoulder",
+            GamepadButton::Select => "Select",
+            GamepadButton::Start => "Start",
+            GamepadButton::DPadUp => "DPadUp",
+            GamepadButton::DPadDown => "DPadDown",
+            GamepadButton::DPadLeft => "DPadLeft",
+            GamepadButton::DPadRight => "DPadRight",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        GamepadButton::ALL
+            .iter()
+            .find(|button| button.name() == name)
+            .copied()
+    }
+}
+
+/// An axis of one of the sticks, from -1 to 1. Right and up are positive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+}
+
+impl GamepadAxis {
+    pub const ALL: [GamepadAxis; 4] = [
+        GamepadAxis::LeftStickX,
+        GamepadAxis::LeftStickY,
+        GamepadAxis::RightStickX,
+        GamepadAxis::RightStickY,
+    ];
+
+    /// The name of the axis in files, named after its variant.
+    pub fn name(self) -> &'static str {
+        match self {
+            GamepadAxis::LeftStickX => "LeftStickX",
+            GamepadAxis::LeftStickY => "LeftStickY",
+            GamepadAxis::RightStickX => "RightStickX",
+            GamepadAxis::RightStickY => "RightStickY",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        GamepadAxis::ALL
+            .iter()
+            .find(|axis| axis.name() == name)
+            .copied()
+    }
+
+    /// The X and Y axes of the stick this axis belongs to.
+    fn stick(self) -> (GamepadAxis, GamepadAxis) {
+        match self {
+            GamepadAxis::LeftStickX | GamepadAxis::LeftStickY => {
+                (GamepadAxis::LeftStickX, GamepadAxis::LeftStickY)
+            }
+            GamepadAxis::RightStickX | GamepadAxis::RightStickY => {
+                (GamepadAxis::RightStickX, GamepadAxis::RightStickY)
+            }
+        }
+    }
+}
+
+/// Something on a gamepad that can be bound to an action: a button, or a stick pushed one way along an axis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadInput {
+    Button(GamepadButton),
+    Negative(GamepadAxis),
+    Positive(GamepadAxis),
+}
+
+impl GamepadInput {
+    /// Every gamepad input, buttons first.
+    pub fn all() -> impl Iterator<Item = GamepadInput> {
+        let buttons = GamepadButton::ALL
+            .iter()
+            .map(|button| GamepadInput::Button(*button));
+        let directions = GamepadAxis::ALL
+            .iter()
+            .flat_map(|axis| vec![GamepadInput::Negative(*axis), GamepadInput::Positive(*axis)]);
+
+        buttons.chain(directions)
+    }
+
+    /// The name of the input in files: the name of a button, or of an axis followed by `-` or `+`.
+    pub fn name(self) -> String {
+        match self {
+            GamepadInput::Button(button) => button.name().to_owned(),
+            GamepadInput::Negative(axis) => format!("{}-", axis.name()),
+            GamepadInput::Positive(axis) => format!("{}+", axis.name()),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some(axis) = name.strip_suffix('-') {
+            GamepadAxis::from_name(axis).map(GamepadInput::Negative)
+        } else if let Some(axis) = name.strip_suffix('+') {
+            GamepadAxis::from_name(axis).map(GamepadInput::Positive)
+        } else {
+            GamepadButton::from_name(name).map(GamepadInput::Button)
+        }
+    }
+}
+
+/// Somewhere to read the state of a gamepad from, e.g. the gamepads connected to the computer or a
+/// `VirtualGamepad`. Read into an `InputState` with `InputState::update_gamepad`.
+pub trait GamepadDevice {
+    fn is_button_held(&self, button: GamepadButton) -> bool;
+
+    /// How far an axis is pushed, from -1 to 1, before any deadzone is applied.
+    fn axis(&self, axis: GamepadAxis) -> f32;
+}
+
+/// A gamepad that's controlled from code, e.g. by tests or when playing back a replay.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VirtualGamepad {
+    buttons: HashSet<GamepadButton>,
+    axes: HashMap<GamepadAxis, f32>,
+}
+
+impl VirtualGamepad {
+    pub fn new() -> Self {
+        VirtualGamepad::default()
+    }
+
+    pub fn press_button(&mut self, button: GamepadButton) {
+        self.buttons.insert(button);
+    }
+
+    pub fn release_button(&mut self, button: GamepadButton) {
+        self.buttons.remove(&button);
+    }
+
+    pub fn set_axis(&mut self, axis: GamepadAxis, value: f32) {
+        self.axes.insert(axis, value);
+    }
+
+    /// Hold exactly the given buttons, and set every axis that isn't given back to the center.
+    pub fn set_state(&mut self, buttons: &[GamepadButton], axes: &[(GamepadAxis, f32)]) {
+        self.buttons = buttons.iter().copied().collect();
+        self.axes = axes.iter().copied().collect();
+    }
+}
+
+impl GamepadDevice for VirtualGamepad {
+    fn is_button_held(&self, button: GamepadButton) -> bool {
+        self.buttons.contains(&button)
+    }
+
+    fn axis(&self, axis: GamepadAxis) -> f32 {
+        self.axes.get(&axis).copied().unwrap_or(0.0)
+    }
+}
+
+/// Something the player can do, which is bound to keys by `InputBindings`. Game code asks whether an action is
+/// held, pressed or released rather than checking keys itself, so the keys can be changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InputAction {
+    MoveLeft,
+    MoveRight,
+    Launch,
+    Pause,
+    Confirm,
+    Back,
+    MenuUp,
+    MenuDown,
+}
+
+impl InputAction {
+    pub const ALL: [InputAction; 8] = [
+        InputAction::MoveLeft,
+        InputAction::MoveRight,
+        InputAction::Launch,
+        InputAction::Pause,
+        InputAction::Confirm,
+        InputAction::Back,
+        InputAction::MenuUp,
+        InputAction::MenuDown,
+    ];
+
+    /// The name of the action in files (settings, replays, etc.)
+    pub fn name(self) -> &'static str {
+        match self {
+            InputAction::MoveLeft => "move_left",
+            InputAction::MoveRight => "move_right",
+            InputAction::Launch => "launch",
+            InputAction::Pause => "pause",
+            InputAction::Confirm => "confirm",
+            InputAction::Back => "back",
+            InputAction::MenuUp => "menu_up",
+            InputAction::MenuDown => "menu_down",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        InputAction::ALL
+            .iter()
+            .find(|action| action.name() == name)
+            .copied()
+    }
+
+    /// The keys bound to the action when nothing else has been chosen.
+    pub fn default_keys(self) -> &'static [VirtualKeyCode] {
+        match self {
+            InputAction::MoveLeft => &[VirtualKeyCode::A, VirtualKeyCode::Left],
+            InputAction::MoveRight => &[VirtualKeyCode::D, VirtualKeyCode::Right],
+            InputAction::Launch => &[VirtualKeyCode::Space],
+            InputAction::Pause => &[VirtualKeyCode::Escape, VirtualKeyCode::P],
+            InputAction::Confirm => &[VirtualKeyCode::Return, VirtualKeyCode::Space],
+            InputAction::Back => &[VirtualKeyCode::Escape],
+            InputAction::MenuUp => &[VirtualKeyCode::Up],
+            InputAction::MenuDown => &[VirtualKeyCode::Down],
+        }
+    }
+
+    /// The gamepad inputs bound to the action when nothing else has been chosen.
+    pub fn default_gamepad_inputs(self) -> &'static [GamepadInput] {
+        match self {
+            InputAction::MoveLeft => &[
+                GamepadInput::Button(GamepadButton::DPadLeft),
+                GamepadInput::Negative(GamepadAxis::LeftStickX),
+            ],
+            InputAction::MoveRight => &[
+                GamepadInput::Button(GamepadButton::DPadRight),
+                GamepadInput::Positive(GamepadAxis::LeftStickX),
+            ],
+            InputAction::Launch => &[GamepadInput::Button(GamepadButton::South)],
+            InputAction::Pause => &[GamepadInput::Button(GamepadButton::Start)],
+            InputAction::Confirm => &[GamepadInput::Button(GamepadButton::South)],
+            InputAction::Back => &[GamepadInput::Button(GamepadButton::East)],
+            InputAction::MenuUp => &[
+                GamepadInput::Button(GamepadButton::DPadUp),
+                GamepadInput::Positive(GamepadAxis::LeftStickY),
+            ],
+            InputAction::MenuDown => &[
+                GamepadInput::Button(GamepadButton::DPadDown),
+                GamepadInput::Negative(GamepadAxis::LeftStickY),
+            ],
+        }
+    }
+}
+
+/// The keys and gamepad inputs bound to each action. An action can have any number of them, and each can be bound to
+/// more than one action (e.g. `Space` both launches the ball and confirms menus).
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputBindings {
+    keys: HashMap<InputAction, Vec<VirtualKeyCode>>,
+    gamepad_inputs: HashMap<InputAction, Vec<GamepadInput>>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        InputBindings {
+            keys: InputAction::ALL
+                .iter()
+                .map(|action| (*action, action.default_keys().to_vec()))
+                .collect(),
+            gamepad_inputs: InputAction::ALL
+                .iter()
+                .map(|action| (*action, action.default_gamepad_inputs().to_vec()))
+                .collect(),
+        }
+    }
+}
+
+impl InputBindings {
+    pub fn keys(&self, action: InputAction) -> &[VirtualKeyCode] {
+        self.keys
+            .get(&action)
+            .map(|keys| keys.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn set_keys(&mut self, action: InputAction, keys: Vec<VirtualKeyCode>) {
+        self.keys.insert(action, keys);
+    }
+
+    pub fn gamepad_inputs(&self, action: InputAction) -> &[GamepadInput] {
+        self.gamepad_inputs
+            .get(&action)
+            .map(|inputs| inputs.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn set_gamepad_inputs(&mut self, action: InputAction, inputs: Vec<GamepadInput>) {
+        self.gamepad_inputs.insert(action, inputs);
+    }
+
+    /// Bind a key to an action alongside its other keys, or unbind it if it already was. Returns whether the key is
+    /// bound now.
+    pub fn toggle_key(&mut self, action: InputAction, key: VirtualKeyCode) -> bool {
+        toggle_binding(self.keys.entry(action).or_default(), key)
+    }
+
+    /// Bind a gamepad input to an action alongside its other gamepad inputs, or unbind it if it already was. Returns
+    /// whether the input is bound now.
+    pub fn toggle_gamepad_input(&mut self, action: InputAction, input: GamepadInput) -> bool {
+        toggle_binding(self.gamepad_inputs.entry(action).or_default(), input)
+    }
+
+    /// Parse a binding in the form `<action> <keys>`, e.g. `move_left A Left`, replacing the keys of the action.
+    /// Actions and keys are named as in `InputAction::name` and `key_name`.
+    pub fn parse_binding(&mut self, binding: &str) -> Result<(), String> {
+        let (action, keys) = parse_binding_words(binding, |name| {
+            key_from_name(name).ok_or_else(|| format!("Unknown key `{}`", name))
+        })?;
+        self.set_keys(action, keys);
+        Ok(())
+    }
+
+    /// Parse a gamepad binding in the form `<action> <gamepad inputs>`, e.g. `move_left DPadLeft LeftStickX-`,
+    /// replacing the gamepad inputs of the action. Inputs are named as in `GamepadInput::name`.
+    pub fn parse_gamepad_binding(&mut self, binding: &str) -> Result<(), String> {
+        let (action, inputs) = parse_binding_words(binding, |name| {
+            GamepadInput::from_name(name).ok_or_else(|| format!("Unknown gamepad input `{}`", name))
+        })?;
+        self.set_gamepad_inputs(action, inputs);
+        Ok(())
+    }
+
+    /// The binding of every action, in the form read by `parse_binding`.
+    pub fn to_bindings(&self) -> Vec<String> {
+        InputAction::ALL
+            .iter()
+            .map(|action| {
+                let words: Vec<&str> = std::iter::once(action.name())
+                    .chain(self.keys(*action).iter().filter_map(|key| key_name(*key)))
+                    .collect();
+                words.join(" ")
+            })
+            .collect()
+    }
+
+    /// The gamepad binding of every action, in the form read by `parse_gamepad_binding`.
+    pub fn to_gamepad_bindings(&self) -> Vec<String> {
+        InputAction::ALL
+            .iter()
+            .map(|action| {
+                let words: Vec<String> = std::iter::once(action.name().to_owned())
+                    .chain(
+                        self.gamepad_inputs(*action)
+                            .iter()
+                            .map(|input| input.name()),
+                    )
+                    .collect();
+                words.join(" ")
+            })
+            .collect()
+    }
+}
+
+fn toggle_binding<T: PartialEq>(bound: &mut Vec<T>, input: T) -> bool {
+    match bound.iter().position(|other| *other == input) {
+        Some(index) => {
+            bound.remove(index);
+            false
+        }
+        None => {
+            bound.push(input);
+            true
+        }
+    }
+}
+
+/// Split a binding into its action and the names that follow it, parsed with `parse_name`.
+fn parse_binding_words<T>(
+    binding: &str,
+    parse_name: impl Fn(&str) -> Result<T, String>,
+) -> Result<(InputAction, Vec<T>), String> {
+    let mut words = binding.split_whitespace();
+    let name = words.next().unwrap_or("");
+    let action =
+        InputAction::from_name(name).ok_or_else(|| format!("Unknown action `{}`", name))?;
+
+    let parsed = words.map(parse_name).collect::<Result<Vec<_>, _>>()?;
+    Ok((action, parsed))
+}
+
+#[derive(Clone)]
 pub struct InputState {
     current_keys: HashMap<VirtualKeyCode, bool>,
     pressed_keys: HashMap<VirtualKeyCode, bool>,
     released_keys: HashMap<VirtualKeyCode, bool>,
+    bindings: InputBindings,
+
+    // The gamepad as it was last read, with the axes before the deadzone is applied
+    gamepad_buttons: HashSet<GamepadButton>,
+    gamepad_axes: HashMap<GamepadAxis, f32>,
+    gamepad_deadzone: f32,
+
+    // Buttons, and sticks pushed past the press threshold
+    current_gamepad_inputs: HashSet<GamepadInput>,
+    pressed_gamepad_inputs: HashSet<GamepadInput>,
+    released_gamepad_inputs: HashSet<GamepadInput>,
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        InputState::new()
+    }
 }
 
 impl InputState {
@@ -16,30 +494,90 @@ impl InputState {
             current_keys: HashMap::new(),
             pressed_keys: HashMap::new(),
             released_keys: HashMap::new(),
+            bindings: InputBindings::default(),
+            gamepad_buttons: HashSet::new(),
+            gamepad_axes: HashMap::new(),
+            gamepad_deadzone: GAMEPAD_STICK_DEADZONE,
+            current_gamepad_inputs: HashSet::new(),
+            pressed_gamepad_inputs: HashSet::new(),
+            released_gamepad_inputs: HashSet::new(),
         }
     }
 
     pub fn clear_pressed_and_released(&mut self) {
         self.pressed_keys.clear();
         self.released_keys.clear();
+        self.pressed_gamepad_inputs.clear();
+        self.released_gamepad_inputs.clear();
+    }
+
+    /// Build an input state directly from its held, pressed and released keys, e.g. when playing back a replay.
+    pub fn from_keys(
+        held: &[VirtualKeyCode],
+        pressed: &[VirtualKeyCode],
+        released: &[VirtualKeyCode],
+    ) -> InputState {
+        let mut input = InputState::new();
+        input.set_keys(held, pressed, released);
+        input
+    }
+
+    /// Replace the held, pressed and released keys, leaving the gamepad as it is.
+    pub fn set_keys(
+        &mut self,
+        held: &[VirtualKeyCode],
+        pressed: &[VirtualKeyCode],
+        released: &[VirtualKeyCode],
+    ) {
+        self.current_keys = held.iter().map(|key| (*key, true)).collect();
+        self.pressed_keys = pressed.iter().map(|key| (*key, true)).collect();
+        self.released_keys = released.iter().map(|key| (*key, true)).collect();
+    }
+
+    pub fn bindings(&self) -> &InputBindings {
+        &self.bindings
+    }
+
+    /// Change the keys the actions are bound to.
+    pub fn set_bindings(&mut self, bindings: InputBindings) {
+        self.bindings = bindings;
     }
 
     pub fn handle_keyboard_input(&mut self, input: &KeyboardInput) {
         let keycode: VirtualKeyCode = input.virtual_keycode.unwrap();
 
         match input.state {
-            ElementState::Pressed => {
-                if !self.is_key_held(keycode) {
-                    self.pressed_keys.insert(keycode, true);
-                }
+            ElementState::Pressed => self.press_key(keycode),
+            ElementState::Released => self.release_key(keycode),
+        }
+    }
 
-                self.current_keys.insert(keycode, true);
-            }
-            ElementState::Released => {
-                self.released_keys.insert(keycode, true);
-                self.current_keys.insert(keycode, false);
-            }
+    pub fn press_key(&mut self, keycode: VirtualKeyCode) {
+        if !self.is_key_held(keycode) {
+            self.pressed_keys.insert(keycode, true);
         }
+
+        self.current_keys.insert(keycode, true);
+    }
+
+    pub fn release_key(&mut self, keycode: VirtualKeyCode) {
+        self.released_keys.insert(keycode, true);
+        self.current_keys.insert(keycode, false);
+    }
+
+    pub fn held_keys(&self) -> impl Iterator<Item = VirtualKeyCode> + '_ {
+        self.current_keys
+            .iter()
+            .filter(|(_, held)| **held)
+            .map(|(key, _)| *key)
+    }
+
+    pub fn pressed_keys(&self) -> impl Iterator<Item = VirtualKeyCode> + '_ {
+        self.pressed_keys.keys().copied()
+    }
+
+    pub fn released_keys(&self) -> impl Iterator<Item = VirtualKeyCode> + '_ {
+        self.released_keys.keys().copied()
     }
 
     #[allow(dead_code)]
@@ -56,4 +594,144 @@ impl InputState {
     pub fn is_key_held(&self, keycode: VirtualKeyCode) -> bool {
         *self.current_keys.get(&keycode).unwrap_or(&false)
     }
+
+    /// Read the state of a gamepad. Buttons and stick directions that changed since the last read are pressed or
+    /// released, like keys. Should be called once a tick, before the tick runs.
+    pub fn update_gamepad(&mut self, gamepad: &dyn GamepadDevice) {
+        self.gamepad_buttons = GamepadButton::ALL
+            .iter()
+            .copied()
+            .filter(|button| gamepad.is_button_held(*button))
+            .collect();
+        self.gamepad_axes = GamepadAxis::ALL
+            .iter()
+            .map(|axis| (*axis, gamepad.axis(*axis).max(-1.0).min(1.0)))
+            .collect();
+
+        let held: HashSet<GamepadInput> = GamepadInput::all()
+            .filter(|input| match input {
+                GamepadInput::Button(button) => self.gamepad_buttons.contains(button),
+                _ => self.gamepad_input_value(*input) >= GAMEPAD_STICK_PRESS_THRESHOLD,
+            })
+            .collect();
+
+        self.pressed_gamepad_inputs
+            .extend(held.difference(&self.current_gamepad_inputs));
+        self.released_gamepad_inputs
+            .extend(self.current_gamepad_inputs.difference(&held));
+        self.current_gamepad_inputs = held;
+    }
+
+    pub fn set_gamepad_deadzone(&mut self, deadzone: f32) {
+        self.gamepad_deadzone = deadzone;
+    }
+
+    /// The buttons held on the gamepad when it was last read.
+    pub fn gamepad_buttons(&self) -> impl Iterator<Item = GamepadButton> + '_ {
+        self.gamepad_buttons.iter().copied()
+    }
+
+    /// The axes of the gamepad that were away from the center when it was last read, before the deadzone.
+    pub fn gamepad_axes(&self) -> impl Iterator<Item = (GamepadAxis, f32)> + '_ {
+        self.gamepad_axes
+            .iter()
+            .filter(|(_, value)| **value != 0.0)
+            .map(|(axis, value)| (*axis, *value))
+    }
+
+    /// How far an axis is pushed, from -1 to 1. The deadzone is applied to the whole stick rather than each axis, so
+    /// pushing a stick diagonally doesn't snap to the sides, and the rest of the range is stretched to start at 0.
+    pub fn gamepad_axis(&self, axis: GamepadAxis) -> f32 {
+        let raw_value = |axis| self.gamepad_axes.get(&axis).copied().unwrap_or(0.0);
+        let (x, y) = axis.stick();
+        let length = (raw_value(x) * raw_value(x) + raw_value(y) * raw_value(y)).sqrt();
+        if length <= self.gamepad_deadzone {
+            return 0.0;
+        }
+
+        let scaled_length =
+            ((length - self.gamepad_deadzone) / (1.0 - self.gamepad_deadzone)).min(1.0);
+        raw_value(axis) * scaled_length / length
+    }
+
+    /// How far a gamepad input is held, from 0 to 1. Buttons are either held or not, while a stick direction is how
+    /// far the stick is pushed that way.
+    pub fn gamepad_input_value(&self, input: GamepadInput) -> f32 {
+        match input {
+            GamepadInput::Button(button) if self.gamepad_buttons.contains(&button) => 1.0,
+            GamepadInput::Button(_) => 0.0,
+            GamepadInput::Negative(axis) => (-self.gamepad_axis(axis)).max(0.0),
+            GamepadInput::Positive(axis) => self.gamepad_axis(axis).max(0.0),
+        }
+    }
+
+    pub fn is_gamepad_input_pressed(&self, input: GamepadInput) -> bool {
+        self.pressed_gamepad_inputs.contains(&input)
+    }
+
+    pub fn is_gamepad_input_released(&self, input: GamepadInput) -> bool {
+        self.released_gamepad_inputs.contains(&input)
+    }
+
+    pub fn is_gamepad_input_held(&self, input: GamepadInput) -> bool {
+        self.current_gamepad_inputs.contains(&input)
+    }
+
+    /// Whether any key or gamepad input bound to an action is held.
+    pub fn is_action_held(&self, action: InputAction) -> bool {
+        self.bindings
+            .keys(action)
+            .iter()
+            .any(|key| self.is_key_held(*key))
+            || self
+                .bindings
+                .gamepad_inputs(action)
+                .iter()
+                .any(|input| self.is_gamepad_input_held(*input))
+    }
+
+    /// Whether any key or gamepad input bound to an action was pressed this tick.
+    pub fn is_action_pressed(&self, action: InputAction) -> bool {
+        self.bindings
+            .keys(action)
+            .iter()
+            .any(|key| self.is_key_pressed(*key))
+            || self
+                .bindings
+                .gamepad_inputs(action)
+                .iter()
+                .any(|input| self.is_gamepad_input_pressed(*input))
+    }
+
+    /// Whether any key or gamepad input bound to an action was released this tick.
+    pub fn is_action_released(&self, action: InputAction) -> bool {
+        self.bindings
+            .keys(action)
+            .iter()
+            .any(|key| self.is_key_released(*key))
+            || self
+                .bindings
+                .gamepad_inputs(action)
+                .iter()
+                .any(|input| self.is_gamepad_input_released(*input))
+    }
+
+    /// How far an action is held, from 0 to 1. Keys and buttons are all or nothing, while a bound stick gives how
+    /// far it's pushed, even short of the press threshold.
+    pub fn action_value(&self, action: InputAction) -> f32 {
+        if self
+            .bindings
+            .keys(action)
+            .iter()
+            .any(|key| self.is_key_held(*key))
+        {
+            return 1.0;
+        }
+
+        self.bindings
+            .gamepad_inputs(action)
+            .iter()
+            .map(|input| self.gamepad_input_value(*input))
+            .fold(0.0, f32::max)
+    }
 }
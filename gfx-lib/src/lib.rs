@@ -12,10 +12,15 @@ pub extern crate image;
 extern crate nalgebra_glm as glm;
 extern crate winit;
 
+pub mod atlas;
 pub mod color;
+pub mod gamepad;
 pub mod input;
 pub mod mesh;
+pub mod render_backend;
 pub mod renderer;
+pub mod screenshot;
+pub mod software;
 pub mod sprite;
 pub mod texture;
 pub mod window;
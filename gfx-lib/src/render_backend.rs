@@ -0,0 +1,65 @@
+use crate::renderer::{RenderCommand, TextureId};
+use std::collections::HashMap;
+
+/// Something that can draw `RenderCommand`s, so the window and game code aren't tied to one renderer.
+pub trait RenderBackend {
+    /// Create a texture from RGBA pixels (row by row from the top left), replacing any texture with the same id.
+    fn create_texture(&mut self, id: TextureId, w: u32, h: u32, pixels: &[u8]);
+
+    fn destroy_texture(&mut self, id: TextureId);
+
+    /// Hand over the commands for the next frame. They don't need to be sorted.
+    fn submit(&mut self, commands: Vec<RenderCommand>);
+
+    /// Draw the submitted commands and show the frame.
+    fn present(&mut self, scale_factor: f32);
+
+    /// Called when the size of the window changes, in physical pixels.
+    fn resize(&mut self, width: u32, height: u32);
+
+    /// Change how many times larger than its size the game is drawn. Backends with a fixed scale ignore this.
+    fn set_render_scale(&mut self, _render_scale: f32) {}
+
+    /// Choose whether presenting a frame waits for the display's vertical blank. Backends that don't present to a
+    /// display ignore this.
+    fn set_vsync(&mut self, _vsync: bool) {}
+}
+
+/// A backend that doesn't draw anything, and instead keeps what it was given so it can be inspected.
+#[derive(Default)]
+pub struct RecordingRenderer {
+    // Texture sizes by id
+    pub textures: HashMap<TextureId, (u32, u32)>,
+
+    // The commands from the last `submit`
+    pub commands: Vec<RenderCommand>,
+    pub frames_presented: u64,
+}
+
+impl RecordingRenderer {
+    pub fn new() -> Self {
+        RecordingRenderer {
+            ..Default::default()
+        }
+    }
+}
+
+impl RenderBackend for RecordingRenderer {
+    fn create_texture(&mut self, id: TextureId, w: u32, h: u32, _pixels: &[u8]) {
+        self.textures.insert(id, (w, h));
+    }
+
+    fn destroy_texture(&mut self, id: TextureId) {
+        self.textures.remove(&id);
+    }
+
+    fn submit(&mut self, commands: Vec<RenderCommand>) {
+        self.commands = commands;
+    }
+
+    fn present(&mut self, _scale_factor: f32) {
+        self.frames_presented += 1;
+    }
+
+    fn resize(&mut self, _width: u32, _height: u32) {}
+}
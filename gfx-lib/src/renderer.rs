@@ -1,6 +1,7 @@
 use crate::{
     color::*,
     mesh::{self, Mesh, Vertex},
+    render_backend::RenderBackend,
     sprite::*,
     window::*,
     Point2f, Vector2f,
@@ -70,7 +71,7 @@ const MAX_BATCH_VERTICES: u64 = MAX_SPRITES * 4;
 const MAX_BATCH_INDICES: u64 = MAX_SPRITES * 6;
 const MAX_DESCRIPTOR_SETS: usize = 512;
 
-const CLEAR_COLOR: [f32; 4] = [0.2, 0.2, 0.2, 1.0];
+pub(crate) const CLEAR_COLOR: [f32; 4] = [0.2, 0.2, 0.2, 1.0];
 
 pub type RenderKey = u64;
 pub type ShaderProgramId = u16;
@@ -367,6 +368,9 @@ pub struct Renderer {
     viewport: pso::Viewport,
     render_scale: f32,
 
+    // Whether the swapchain waits for the vertical blank
+    vsync: bool,
+
     frame_semaphores: Option<Vec<GfxSemaphore>>,
     frame_fences: Option<Vec<GfxFence>>,
 
@@ -380,6 +384,9 @@ pub struct Renderer {
     textures: HashMap<TextureId, GpuTexture>,
     batches: HashMap<RenderKey, RenderBatch>,
 
+    // Batches from the last `RenderBackend::submit`, waiting for `present`
+    submitted_batch_keys: Vec<RenderKey>,
+
     frames_in_flight: usize,
     current_frame: usize,
 }
@@ -567,7 +574,7 @@ impl Renderer {
             depth: 0.0..1.0,
         };
 
-        Renderer {
+        let mut renderer = Renderer {
             instance,
             surface: Some(surface),
             adapter,
@@ -580,6 +587,7 @@ impl Renderer {
             dimensions,
             viewport,
             render_scale,
+            vsync: true,
             frame_semaphores: Some(frame_semaphores),
             frame_fences: Some(frame_fences),
             render_pass: Some(render_pass),
@@ -589,9 +597,13 @@ impl Renderer {
             uniform_buffer_frame_size,
             textures: HashMap::new(),
             batches: HashMap::new(),
+            submitted_batch_keys: Vec::new(),
             frames_in_flight,
             current_frame: 0,
-        }
+        };
+
+        renderer.rebuild_swapchain();
+        renderer
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
@@ -600,6 +612,13 @@ impl Renderer {
         self.rebuild_swapchain();
     }
 
+    pub fn set_vsync(&mut self, vsync: bool) {
+        if self.vsync != vsync {
+            self.vsync = vsync;
+            self.rebuild_swapchain();
+        }
+    }
+
     pub fn create_render_batch(
         &mut self,
         transparency: Transparency,
@@ -694,7 +713,7 @@ impl Renderer {
     }
 
     fn write_descriptor_sets(
-        &mut self,
+        &self,
         batch: &RenderBatch,
         shader_descriptor_bindings: Vec<ShaderDescriptorBinding>,
     ) {
@@ -1035,11 +1054,28 @@ impl Renderer {
         println!("Rebuilding swapchain.");
 
         let capabilities = surface.capabilities(&self.adapter.physical_device);
-        let swap_config = window::SwapchainConfig::from_caps(
+        let mut swap_config = window::SwapchainConfig::from_caps(
             &capabilities,
             self.surface_color_format,
             self.dimensions,
         );
+
+        // FIFO always waits for the vertical blank and is always supported, so it's the fallback
+        swap_config.present_mode = if self.vsync {
+            window::PresentMode::FIFO
+        } else if capabilities
+            .present_modes
+            .contains(window::PresentMode::IMMEDIATE)
+        {
+            window::PresentMode::IMMEDIATE
+        } else if capabilities
+            .present_modes
+            .contains(window::PresentMode::MAILBOX)
+        {
+            window::PresentMode::MAILBOX
+        } else {
+            window::PresentMode::FIFO
+        };
         println!("swap_config: {:?}", swap_config);
         let extent = swap_config.extent.to_extent();
 
@@ -1053,7 +1089,13 @@ impl Renderer {
         self.viewport.rect.h = extent.height as _;
     }
 
-    pub fn create_gpu_texture(&mut self, id: GpuTextureId, w: u32, h: u32, pixels: &Vec<u8>) {
+    /// Create a texture, replacing any existing texture with the same id.
+    pub fn create_gpu_texture(&mut self, id: GpuTextureId, w: u32, h: u32, pixels: &[u8]) {
+        // The old texture may still be in use by frames in flight
+        if self.textures.contains_key(&id) {
+            self.device.borrow().wait_idle().unwrap();
+        }
+
         let (texture_image, texture_memory, texture_view) = create_image(
             self.device.clone(),
             &self.adapter.physical_device,
@@ -1211,6 +1253,69 @@ impl Renderer {
         };
 
         self.textures.insert(id, tex);
+
+        // Point any batches that were drawing with the old texture at the new one
+        let batch_keys: Vec<RenderKey> = self
+            .batches
+            .values()
+            .filter(|batch| batch.tex_id() == id)
+            .map(|batch| batch.key())
+            .collect();
+        for key in batch_keys {
+            let shader_descriptor_bindings = {
+                let batch = self.batches.get_mut(&key).unwrap();
+                batch.tex_info = (id, w, h);
+                match self.shader_programs.get(&batch.shader_program_id) {
+                    Some(shader_program) => shader_program.shader_descriptor_bindings.clone(),
+                    None => continue,
+                }
+            };
+
+            self.write_descriptor_sets(&self.batches[&key], shader_descriptor_bindings);
+        }
+    }
+
+    /// Destroy a texture. Batches drawing with it are kept, and start drawing with the texture again if one is
+    /// created with the same id.
+    pub fn destroy_gpu_texture(&mut self, id: GpuTextureId) {
+        if !self.textures.contains_key(&id) {
+            return;
+        }
+
+        // The texture may still be in use by frames in flight
+        self.device.borrow().wait_idle().unwrap();
+        self.textures.remove(&id);
+    }
+}
+
+impl RenderBackend for Renderer {
+    fn create_texture(&mut self, id: TextureId, w: u32, h: u32, pixels: &[u8]) {
+        self.create_gpu_texture(id, w, h, pixels);
+    }
+
+    fn destroy_texture(&mut self, id: TextureId) {
+        self.destroy_gpu_texture(id);
+    }
+
+    fn submit(&mut self, commands: Vec<RenderCommand>) {
+        self.submitted_batch_keys = self.process_commands(commands);
+    }
+
+    fn present(&mut self, scale_factor: f32) {
+        let batch_keys = std::mem::take(&mut self.submitted_batch_keys);
+        self.render(scale_factor, batch_keys);
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        Renderer::resize(self, width, height);
+    }
+
+    fn set_render_scale(&mut self, render_scale: f32) {
+        self.render_scale = render_scale;
+    }
+
+    fn set_vsync(&mut self, vsync: bool) {
+        Renderer::set_vsync(self, vsync);
     }
 }
 
@@ -0,0 +1,68 @@
+use image::{Rgba, RgbaImage};
+
+const DIFF_MISMATCH_COLOR: Rgba<u8> = Rgba([255, 0, 0, 255]);
+
+/// The result of comparing two images pixel by pixel.
+pub struct ImageDiff {
+    pub mismatched_pixels: u32,
+
+    // The largest difference found in any channel of any pixel
+    pub max_difference: u8,
+
+    // The expected image faded to gray, with mismatched pixels in red
+    pub image: RgbaImage,
+}
+
+impl ImageDiff {
+    pub fn matches(&self) -> bool {
+        self.mismatched_pixels == 0
+    }
+}
+
+/// Compare two images, counting the pixels where any channel differs by more than `tolerance`.
+/// Images of different sizes are compared over the larger of the two, and pixels only one of them has never match.
+pub fn diff_images(expected: &RgbaImage, actual: &RgbaImage, tolerance: u8) -> ImageDiff {
+    let width = expected.width().max(actual.width());
+    let height = expected.height().max(actual.height());
+
+    let mut diff = ImageDiff {
+        mismatched_pixels: 0,
+        max_difference: 0,
+        image: RgbaImage::new(width, height),
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            let expected_pixel = pixel_at(expected, x, y);
+            let difference = match (expected_pixel, pixel_at(actual, x, y)) {
+                (Some(e), Some(a)) => {
+                    e.0.iter()
+                        .zip(a.0.iter())
+                        .map(|(e, a)| (*e as i16 - *a as i16).abs() as u8)
+                        .max()
+                        .unwrap_or(0)
+                }
+                _ => 255,
+            };
+
+            diff.max_difference = diff.max_difference.max(difference);
+            if difference > tolerance {
+                diff.mismatched_pixels += 1;
+                diff.image.put_pixel(x, y, DIFF_MISMATCH_COLOR);
+            } else if let Some(e) = expected_pixel {
+                let gray = ((e.0[0] as u32 + e.0[1] as u32 + e.0[2] as u32) / 3 / 4) as u8;
+                diff.image.put_pixel(x, y, Rgba([gray, gray, gray, 255]));
+            }
+        }
+    }
+
+    diff
+}
+
+fn pixel_at(image: &RgbaImage, x: u32, y: u32) -> Option<&Rgba<u8>> {
+    if x < image.width() && y < image.height() {
+        Some(image.get_pixel(x, y))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,336 @@
+use crate::{
+    mesh::{self, Mesh, Vertex},
+    render_backend::RenderBackend,
+    renderer::{RenderCommand, Renderable, ShaderProgramId, TextureId, CLEAR_COLOR},
+};
+use image::RgbaImage;
+use std::collections::HashMap;
+
+// Matches the shader programs the GPU renderer creates
+const UNTEXTURED_SHADER_PROGRAM_ID: ShaderProgramId = 0;
+const TEXTURED_SHADER_PROGRAM_ID: ShaderProgramId = 1;
+
+struct SoftwareTexture {
+    w: u32,
+    h: u32,
+
+    // Linear color, decoded from sRGB once when the texture is created
+    texels: Vec<[f32; 4]>,
+}
+
+/// A reference renderer that draws `RenderCommand`s on the CPU into an in-memory framebuffer.
+///
+/// It follows the GPU renderer as closely as it can: commands are drawn in `RenderCommand::key` order, textures
+/// are sampled nearest with wrapping, colors are tinted and alpha blended in linear space, and the framebuffer is
+/// sRGB encoded when read back.
+pub struct SoftwareRenderer {
+    width: u32,
+    height: u32,
+    render_scale: f32,
+    framebuffer: Vec<[f32; 4]>,
+    textures: HashMap<TextureId, SoftwareTexture>,
+    srgb_to_linear: [f32; 256],
+}
+
+impl SoftwareRenderer {
+    /// Create a renderer for a `width` by `height` view, drawn at `render_scale` pixels per unit.
+    pub fn new(width: u32, height: u32, render_scale: f32) -> SoftwareRenderer {
+        let fb_width = (width as f32 * render_scale) as u32;
+        let fb_height = (height as f32 * render_scale) as u32;
+
+        let mut srgb_to_linear = [0.0; 256];
+        for (i, value) in srgb_to_linear.iter_mut().enumerate() {
+            *value = decode_srgb(i as f32 / 255.0);
+        }
+
+        SoftwareRenderer {
+            width: fb_width,
+            height: fb_height,
+            render_scale,
+            framebuffer: vec![CLEAR_COLOR; (fb_width * fb_height) as usize],
+            textures: HashMap::new(),
+            srgb_to_linear,
+        }
+    }
+
+    /// The framebuffer size in pixels.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn create_texture(&mut self, id: TextureId, w: u32, h: u32, pixels: &[u8]) {
+        let texels = pixels
+            .chunks_exact(4)
+            .map(|p| {
+                [
+                    self.srgb_to_linear[p[0] as usize],
+                    self.srgb_to_linear[p[1] as usize],
+                    self.srgb_to_linear[p[2] as usize],
+                    p[3] as f32 / 255.0,
+                ]
+            })
+            .collect();
+
+        self.textures.insert(id, SoftwareTexture { w, h, texels });
+    }
+
+    /// Clear the framebuffer, then draw the commands into it.
+    pub fn render(&mut self, mut commands: Vec<RenderCommand>) {
+        // Stable, so commands with the same key draw in the order they were given, like they do within a batch
+        commands.sort_by_key(|command| command.key());
+
+        for pixel in self.framebuffer.iter_mut() {
+            *pixel = CLEAR_COLOR;
+        }
+
+        let mut command_mesh = Mesh {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+        };
+
+        for command in commands {
+            let texture = match command.shader_program_id {
+                UNTEXTURED_SHADER_PROGRAM_ID => None,
+                TEXTURED_SHADER_PROGRAM_ID => match self.textures.get(&command.tex_id) {
+                    Some(texture) => Some(texture),
+                    None => {
+                        println!(
+                            "[SoftwareRenderer] Skipping command for missing texture {}",
+                            command.tex_id
+                        );
+                        continue;
+                    }
+                },
+                id => {
+                    println!(
+                        "[SoftwareRenderer] Skipping command for unknown shader program {}",
+                        id
+                    );
+                    continue;
+                }
+            };
+
+            // Build the same vertices the GPU renderer would, so both renderers agree on the geometry
+            command_mesh.clear();
+            match command.data {
+                Renderable::Quad {
+                    bl,
+                    br,
+                    tl,
+                    tr,
+                    color,
+                } => mesh::add_quad(&mut command_mesh, bl, br, tl, tr, color),
+                Renderable::Sprite {
+                    x,
+                    y,
+                    origin,
+                    scale,
+                    color,
+                    region,
+                } => {
+                    let (w, h) = texture.map_or((1, 1), |texture| (texture.w, texture.h));
+                    mesh::add_sprite(&mut command_mesh, x, y, origin, scale, color, region, w, h);
+                }
+            }
+
+            for triangle in command_mesh.indices.chunks_exact(3) {
+                rasterize_triangle(
+                    &mut self.framebuffer,
+                    self.width,
+                    self.height,
+                    self.render_scale,
+                    texture,
+                    [
+                        &command_mesh.vertices[triangle[0] as usize],
+                        &command_mesh.vertices[triangle[1] as usize],
+                        &command_mesh.vertices[triangle[2] as usize],
+                    ],
+                );
+            }
+        }
+    }
+
+    /// The framebuffer as sRGB encoded RGBA bytes, row by row from the top left.
+    pub fn pixels(&self) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(self.framebuffer.len() * 4);
+        for pixel in self.framebuffer.iter() {
+            pixels.push(to_byte(encode_srgb(pixel[0])));
+            pixels.push(to_byte(encode_srgb(pixel[1])));
+            pixels.push(to_byte(encode_srgb(pixel[2])));
+            pixels.push(to_byte(pixel[3]));
+        }
+
+        pixels
+    }
+
+    pub fn to_image(&self) -> RgbaImage {
+        RgbaImage::from_raw(self.width, self.height, self.pixels())
+            .expect("Framebuffer size didn't match its dimensions!")
+    }
+}
+
+// Commands are drawn as soon as they're submitted, and the framebuffer keeps the size it was created with
+impl RenderBackend for SoftwareRenderer {
+    fn create_texture(&mut self, id: TextureId, w: u32, h: u32, pixels: &[u8]) {
+        SoftwareRenderer::create_texture(self, id, w, h, pixels);
+    }
+
+    fn destroy_texture(&mut self, id: TextureId) {
+        self.textures.remove(&id);
+    }
+
+    fn submit(&mut self, commands: Vec<RenderCommand>) {
+        self.render(commands);
+    }
+
+    fn present(&mut self, _scale_factor: f32) {}
+
+    fn resize(&mut self, _width: u32, _height: u32) {}
+}
+
+fn rasterize_triangle(
+    framebuffer: &mut [[f32; 4]],
+    width: u32,
+    height: u32,
+    render_scale: f32,
+    texture: Option<&SoftwareTexture>,
+    vertices: [&Vertex; 3],
+) {
+    let mut points = [(0.0, 0.0); 3];
+    for (point, vertex) in points.iter_mut().zip(vertices.iter()) {
+        *point = (
+            vertex.position[0] * render_scale,
+            vertex.position[1] * render_scale,
+        );
+    }
+
+    let area = edge(points[0], points[1], points[2]);
+    if area == 0.0 {
+        return;
+    }
+
+    // Wind every triangle the same way, so edges shared by two triangles are owned by exactly one of them
+    let (points, vertices) = if area < 0.0 {
+        (
+            [points[0], points[2], points[1]],
+            [vertices[0], vertices[2], vertices[1]],
+        )
+    } else {
+        (points, vertices)
+    };
+    let area = area.abs();
+
+    let min_x = points.iter().fold(f32::MAX, |m, p| m.min(p.0));
+    let max_x = points.iter().fold(f32::MIN, |m, p| m.max(p.0));
+    let min_y = points.iter().fold(f32::MAX, |m, p| m.min(p.1));
+    let max_y = points.iter().fold(f32::MIN, |m, p| m.max(p.1));
+
+    let start_x = min_x.floor().max(0.0) as u32;
+    let end_x = (max_x.ceil().max(0.0) as u32).min(width);
+    let start_y = min_y.floor().max(0.0) as u32;
+    let end_y = (max_y.ceil().max(0.0) as u32).min(height);
+
+    let owns_edge = [
+        owns_edge(points[1], points[2]),
+        owns_edge(points[2], points[0]),
+        owns_edge(points[0], points[1]),
+    ];
+
+    for py in start_y..end_y {
+        for px in start_x..end_x {
+            // Sample at the pixel center
+            let p = (px as f32 + 0.5, py as f32 + 0.5);
+            let weights = [
+                edge(points[1], points[2], p),
+                edge(points[2], points[0], p),
+                edge(points[0], points[1], p),
+            ];
+
+            let inside = weights
+                .iter()
+                .zip(owns_edge.iter())
+                .all(|(w, owned)| *w > 0.0 || (*w == 0.0 && *owned));
+            if !inside {
+                continue;
+            }
+
+            let mut color = [0.0; 4];
+            let mut uv = [0.0; 2];
+            for (vertex, weight) in vertices.iter().zip(weights.iter()) {
+                let weight = weight / area;
+                for (channel, vertex_channel) in color.iter_mut().zip(vertex.color.iter()) {
+                    *channel += vertex_channel * weight;
+                }
+
+                uv[0] += vertex.uv[0] * weight;
+                uv[1] += vertex.uv[1] * weight;
+            }
+
+            if let Some(texture) = texture {
+                let texel = sample(texture, uv);
+                for (channel, texel_channel) in color.iter_mut().zip(texel.iter()) {
+                    *channel *= texel_channel;
+                }
+            }
+
+            blend(&mut framebuffer[(py * width + px) as usize], color);
+        }
+    }
+}
+
+// Twice the signed area of the triangle (a, b, p), positive when p is on the inner side of a -> b
+fn edge(a: (f32, f32), b: (f32, f32), p: (f32, f32)) -> f32 {
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+// Decides who draws pixels exactly on an edge, true for exactly one of a -> b and b -> a
+fn owns_edge(a: (f32, f32), b: (f32, f32)) -> bool {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    dy > 0.0 || (dy == 0.0 && dx < 0.0)
+}
+
+// Nearest filtering with wrapping, like the GPU sampler
+fn sample(texture: &SoftwareTexture, uv: [f32; 2]) -> [f32; 4] {
+    if texture.w == 0 || texture.h == 0 {
+        return [0.0; 4];
+    }
+
+    let x = ((uv[0] * texture.w as f32).floor() as i64).rem_euclid(texture.w as i64);
+    let y = ((uv[1] * texture.h as f32).floor() as i64).rem_euclid(texture.h as i64);
+    texture
+        .texels
+        .get((y * texture.w as i64 + x) as usize)
+        .copied()
+        .unwrap_or([0.0; 4])
+}
+
+// Standard alpha blending, the same as `BlendState::ALPHA`
+fn blend(dst: &mut [f32; 4], src: [f32; 4]) {
+    let alpha = src[3].max(0.0).min(1.0);
+    for (dst_channel, src_channel) in dst.iter_mut().zip(src.iter()).take(3) {
+        *dst_channel = src_channel * alpha + *dst_channel * (1.0 - alpha);
+    }
+
+    dst[3] = alpha + dst[3] * (1.0 - alpha);
+}
+
+fn decode_srgb(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn encode_srgb(value: f32) -> f32 {
+    let value = value.max(0.0).min(1.0);
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn to_byte(value: f32) -> u8 {
+    (value.max(0.0).min(1.0) * 255.0).round() as u8
+}
@@ -1,10 +1,10 @@
-use crate::{input::InputState, renderer::Renderer};
+use crate::{gamepad::Gamepads, input::InputState, render_backend::RenderBackend};
 use ::winit::{
-    dpi::LogicalSize,
+    dpi::{LogicalSize, PhysicalSize},
     event::Event as WinitEvent,
     event::WindowEvent as WinitWindowEvent,
     event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    window::{Fullscreen, WindowBuilder},
 };
 use std::time::{Duration, Instant};
 
@@ -12,51 +12,71 @@ pub use ::winit::window::Window as WinitWindow;
 
 const SIXTY_FPS_DT: f64 = 1.0 / 60.0;
 
+/// Settings for the window, which can be changed while the game is running by changing `WindowState::settings`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WindowSettings {
+    // How many times larger than its size the game is drawn, when windowed
+    pub render_scale: f32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+}
+
 pub struct WindowState {
     pub fps: u32,
+
+    // How many times larger than its size the game is currently drawn. Resized and fullscreen windows scale the
+    // game to fit.
     pub window_scale: f32,
     pub dpi_scale_factor: f32,
+    pub settings: WindowSettings,
 }
 
 pub type DeltaTime = f64;
 
-pub fn run<T>(
+/// Open a window and run the game loop, drawing with the backend made by `create_backend`. Changes the tick
+/// callback makes to the window settings are applied after the tick.
+pub fn run<T, B>(
     title: &str,
     width: u32,
     height: u32,
-    render_scale: f32,
+    settings: WindowSettings,
+    create_backend: impl FnOnce(&WinitWindow, f32) -> B,
     app_state: T,
-    init_callback: impl FnMut(&mut T, &mut Renderer) + 'static,
-    tick_callback: impl FnMut(&mut T, &WindowState, &InputState, DeltaTime) + 'static,
-    render_callback: impl FnMut(&T, u128, f64, &WindowState, &mut Renderer) + 'static,
+    init_callback: impl FnMut(&mut T, &mut B) + 'static,
+    tick_callback: impl FnMut(&mut T, &mut WindowState, &InputState, DeltaTime) + 'static,
+    render_callback: impl FnMut(&T, u128, f64, &WindowState, &mut B) + 'static,
 ) where
     T: 'static,
+    B: RenderBackend + 'static,
 {
     let event_loop = EventLoop::new();
-    let window_size = LogicalSize::new(
-        (width as f32 * render_scale) as u32,
-        (height as f32 * render_scale) as u32,
-    );
     let window: WinitWindow = WindowBuilder::new()
         .with_title(title)
-        .with_min_inner_size(window_size)
-        .with_inner_size(window_size)
-        .with_resizable(false)
+        .with_min_inner_size(LogicalSize::new(width, height))
+        .with_inner_size(scaled_size(width, height, settings.render_scale))
+        .with_resizable(true)
         .build(&event_loop)
         .expect("Failed to create window!");
+    if settings.fullscreen {
+        window.set_fullscreen(fullscreen_mode(&window, true));
+    }
 
     let mut init_callback = Box::new(init_callback);
     let mut tick_callback = Box::new(tick_callback);
     let mut render_callback = Box::new(render_callback);
 
     let mut app_state: T = app_state;
-    let mut renderer: Renderer = Renderer::new(&window, render_scale);
+    let mut renderer: B = create_backend(&window, settings.render_scale);
+    renderer.set_vsync(settings.vsync);
     let mut input_state: InputState = InputState::new();
+    let mut gamepads = Gamepads::new();
     let mut window_state = WindowState {
         fps: 0,
-        window_scale: render_scale,
+        window_scale: settings.render_scale,
         dpi_scale_factor: window.scale_factor() as f32,
+        settings,
     };
+    let mut applied_settings = settings;
 
     let one_second: Duration = Duration::from_secs(1);
     let mut fps_timer: Duration = Duration::from_secs(0);
@@ -71,7 +91,6 @@ pub fn run<T>(
     let mut ticks: u128 = 0;
 
     init_callback(&mut app_state, &mut renderer);
-    renderer.rebuild_swapchain();
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
@@ -86,6 +105,9 @@ pub fn run<T>(
                 WinitWindowEvent::Resized(size) => {
                     println!("[Window] Resized to ({}, {})", size.width, size.height);
 
+                    window_state.window_scale =
+                        fit_scale(width, height, size, window_state.dpi_scale_factor);
+                    renderer.set_render_scale(window_state.window_scale);
                     renderer.resize(size.width, size.height);
                     window.request_redraw();
                 }
@@ -99,6 +121,13 @@ pub fn run<T>(
                     );
 
                     window_state.dpi_scale_factor = scale_factor as f32;
+                    window_state.window_scale = fit_scale(
+                        width,
+                        height,
+                        *new_inner_size,
+                        window_state.dpi_scale_factor,
+                    );
+                    renderer.set_render_scale(window_state.window_scale);
                     renderer.resize(new_inner_size.width, new_inner_size.height);
                     window.request_redraw();
                 }
@@ -124,10 +153,18 @@ pub fn run<T>(
                 frame_time = frame_time.min(std::time::Duration::from_secs_f64(0.1));
                 current_time = new_time;
 
+                if let Some(gamepads) = &mut gamepads {
+                    gamepads.poll();
+                }
+
                 let dt = frame_time.as_secs_f64();
                 accumulator += dt;
                 while accumulator >= target_dt {
-                    tick_callback(&mut app_state, &window_state, &input_state, dt);
+                    // Read before every tick, the same way replays play the gamepad back
+                    if let Some(gamepads) = &gamepads {
+                        input_state.update_gamepad(gamepads);
+                    }
+                    tick_callback(&mut app_state, &mut window_state, &input_state, dt);
                     input_state.clear_pressed_and_released();
 
                     accumulator -= target_dt;
@@ -136,6 +173,26 @@ pub fn run<T>(
                     fps_counter += 1;
                 }
 
+                // Apply any changes the ticks made to the window settings. Leaving fullscreen goes back to the size
+                // for the render scale.
+                let settings = window_state.settings;
+                if settings != applied_settings {
+                    if settings.fullscreen != applied_settings.fullscreen {
+                        window.set_fullscreen(fullscreen_mode(&window, settings.fullscreen));
+                    }
+                    if !settings.fullscreen
+                        && (settings.fullscreen != applied_settings.fullscreen
+                            || settings.render_scale != applied_settings.render_scale)
+                    {
+                        window.set_inner_size(scaled_size(width, height, settings.render_scale));
+                    }
+                    if settings.vsync != applied_settings.vsync {
+                        renderer.set_vsync(settings.vsync);
+                    }
+
+                    applied_settings = settings;
+                }
+
                 fps_timer = fps_timer + frame_time;
                 if fps_timer >= one_second {
                     fps_timer = std::time::Duration::from_secs(0);
@@ -151,3 +208,26 @@ pub fn run<T>(
         }
     });
 }
+
+fn scaled_size(width: u32, height: u32, scale: f32) -> LogicalSize<u32> {
+    LogicalSize::new(
+        (width as f32 * scale) as u32,
+        (height as f32 * scale) as u32,
+    )
+}
+
+/// The largest scale the game can be drawn at while fitting in a window of some physical size.
+fn fit_scale(width: u32, height: u32, size: PhysicalSize<u32>, dpi_scale_factor: f32) -> f32 {
+    let scale_x = size.width as f32 / dpi_scale_factor / width as f32;
+    let scale_y = size.height as f32 / dpi_scale_factor / height as f32;
+    scale_x.min(scale_y).max(1.0)
+}
+
+/// Fullscreen windows cover the monitor the window is on, without changing its video mode.
+fn fullscreen_mode(window: &WinitWindow, fullscreen: bool) -> Option<Fullscreen> {
+    if fullscreen {
+        Some(Fullscreen::Borderless(window.current_monitor()))
+    } else {
+        None
+    }
+}
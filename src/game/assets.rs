@@ -0,0 +1,451 @@
+use crate::game::{
+    font::{BitmapFont, FontError},
+    music::MusicTrack,
+};
+use gfx::{image, render_backend::RenderBackend, renderer::TextureId};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::{self, File},
+    io::{self, BufReader, Cursor},
+    path::{Path, PathBuf},
+};
+
+pub const ASSET_MANIFEST_PATH: &str = "res/assets.txt";
+
+// Texture id 0 is what `RenderState` binds by default, so it's never given out
+const FIRST_TEXTURE_ID: TextureId = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureHandle(TextureId);
+
+impl TextureHandle {
+    pub fn id(self) -> TextureId {
+        self.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SoundHandle(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MusicHandle(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FontHandle(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssetKind {
+    Texture,
+    Sound,
+    Music,
+    Font,
+}
+
+impl AssetKind {
+    fn from_name(name: &str) -> Option<AssetKind> {
+        match name {
+            "texture" => Some(AssetKind::Texture),
+            "sound" => Some(AssetKind::Sound),
+            "music" => Some(AssetKind::Music),
+            "font" => Some(AssetKind::Font),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AssetEntry {
+    pub kind: AssetKind,
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum AssetError {
+    Manifest {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    Missing(PathBuf, io::Error),
+    Undecodable(PathBuf, String),
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetError::Manifest {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            AssetError::Missing(path, e) => write!(f, "{}: {}", path.display(), e),
+            AssetError::Undecodable(path, e) => {
+                write!(f, "{}: Couldn't be decoded, {}", path.display(), e)
+            }
+        }
+    }
+}
+
+/// Every problem found while loading assets, so they can all be fixed at once.
+#[derive(Debug, Default)]
+pub struct AssetErrors(pub Vec<AssetError>);
+
+impl fmt::Display for AssetErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} asset(s) failed to load:", self.0.len())?;
+        for error in self.0.iter() {
+            write!(f, "\n  {}", error)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The list of assets to load, read from a manifest file with one asset per line: `kind name path`, where
+/// `kind` is `texture`, `sound`, `music` or `font`. Lines starting with `//` are comments.
+pub struct AssetManifest {
+    pub entries: Vec<AssetEntry>,
+}
+
+impl AssetManifest {
+    /// Parse a manifest, collecting every bad line rather than stopping at the first.
+    pub fn parse(path: &Path, source: &str) -> Result<Self, AssetErrors> {
+        let mut entries: Vec<AssetEntry> = Vec::new();
+        let mut errors = AssetErrors::default();
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let mut error = |message: String| {
+                errors.0.push(AssetError::Manifest {
+                    path: path.to_owned(),
+                    line: i + 1,
+                    message,
+                })
+            };
+
+            let mut fields = line.splitn(3, char::is_whitespace);
+            let (kind, name, asset_path) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(kind), Some(name), Some(asset_path)) => (kind, name, asset_path.trim()),
+                _ => {
+                    error("Expected `kind name path`".to_owned());
+                    continue;
+                }
+            };
+
+            let kind = match AssetKind::from_name(kind) {
+                Some(kind) => kind,
+                None => {
+                    error(format!(
+                        "Unknown asset kind `{}`, expected texture, sound, music or font",
+                        kind
+                    ));
+                    continue;
+                }
+            };
+
+            if entries.iter().any(|e| e.kind == kind && e.name == name) {
+                error(format!("`{}` is listed twice", name));
+                continue;
+            }
+
+            entries.push(AssetEntry {
+                kind,
+                name: name.to_owned(),
+                path: PathBuf::from(asset_path),
+            });
+        }
+
+        if errors.0.is_empty() {
+            Ok(AssetManifest { entries })
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+// Where the data loaded from a file ended up, so the file can be reloaded in place
+#[derive(Clone, Copy, Debug)]
+enum AssetSlot {
+    Texture(TextureId),
+    Sound(usize),
+    Music(usize),
+    Font(usize),
+}
+
+pub struct TextureData {
+    pub w: u32,
+    pub h: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Every asset the game uses, looked up by name. Textures (and font pages) are given ids in the order they're listed in
+/// the manifest, and are kept in memory so they can be created on any `RenderBackend`. Sounds are kept in memory
+/// too, but music is streamed from disk when it plays, so only its loop points are loaded.
+#[derive(Default)]
+pub struct Assets {
+    textures: HashMap<String, TextureHandle>,
+    fonts: HashMap<String, FontHandle>,
+    sounds: HashMap<String, SoundHandle>,
+    music: HashMap<String, MusicHandle>,
+    texture_data: BTreeMap<TextureId, TextureData>,
+    sound_data: Vec<Vec<u8>>,
+    music_tracks: Vec<MusicTrack>,
+    font_data: Vec<BitmapFont>,
+    sources: Vec<(PathBuf, AssetSlot)>,
+}
+
+impl Assets {
+    /// Load every asset in a manifest file. Assets that fail to load are left out, and the problems with all of
+    /// them are returned together.
+    pub fn load(manifest_path: &Path) -> (Self, Result<(), AssetErrors>) {
+        let manifest = fs::read_to_string(manifest_path)
+            .map_err(|e| AssetErrors(vec![AssetError::Missing(manifest_path.to_owned(), e)]))
+            .and_then(|source| AssetManifest::parse(manifest_path, &source));
+
+        match manifest {
+            Ok(manifest) => Assets::from_manifest(&manifest),
+            Err(errors) => (Assets::default(), Err(errors)),
+        }
+    }
+
+    pub fn from_manifest(manifest: &AssetManifest) -> (Self, Result<(), AssetErrors>) {
+        let mut assets = Assets::default();
+        let mut errors = AssetErrors::default();
+        let mut next_tex_id = FIRST_TEXTURE_ID;
+
+        for entry in manifest.entries.iter() {
+            match entry.kind {
+                AssetKind::Texture => {
+                    // Ids are taken even if the file fails to load, so the ids of other textures don't shift
+                    let tex_id = next_tex_id;
+                    next_tex_id += 1;
+
+                    let data = match load_texture(&entry.path) {
+                        Ok(data) => data,
+                        Err(e) => {
+                            errors.0.push(e);
+                            continue;
+                        }
+                    };
+
+                    assets.texture_data.insert(tex_id, data);
+                    assets
+                        .sources
+                        .push((entry.path.clone(), AssetSlot::Texture(tex_id)));
+                    assets
+                        .textures
+                        .insert(entry.name.clone(), TextureHandle(tex_id));
+                }
+                AssetKind::Font => {
+                    // The font's page takes a texture id, the same way a texture would
+                    let tex_id = next_tex_id;
+                    next_tex_id += 1;
+
+                    let (mut font, data) = match load_font(&entry.path) {
+                        Ok(loaded) => loaded,
+                        Err(e) => {
+                            errors.0.push(e);
+                            continue;
+                        }
+                    };
+
+                    font.set_tex_id(tex_id);
+                    let index = assets.font_data.len();
+                    assets.texture_data.insert(tex_id, data);
+                    assets
+                        .sources
+                        .push((entry.path.clone(), AssetSlot::Font(index)));
+                    assets
+                        .sources
+                        .push((font.page_path().to_owned(), AssetSlot::Texture(tex_id)));
+                    assets.font_data.push(font);
+                    assets.fonts.insert(entry.name.clone(), FontHandle(index));
+                }
+                AssetKind::Sound => {
+                    let bytes = match load_sound(&entry.path) {
+                        Ok(bytes) => bytes,
+                        Err(e) => {
+                            errors.0.push(e);
+                            continue;
+                        }
+                    };
+
+                    let index = assets.sound_data.len();
+                    assets.sound_data.push(bytes);
+                    assets
+                        .sources
+                        .push((entry.path.clone(), AssetSlot::Sound(index)));
+                    assets.sounds.insert(entry.name.clone(), SoundHandle(index));
+                }
+                AssetKind::Music => {
+                    let track = match load_music(&entry.name, &entry.path) {
+                        Ok(track) => track,
+                        Err(e) => {
+                            errors.0.push(e);
+                            continue;
+                        }
+                    };
+
+                    let index = assets.music_tracks.len();
+                    assets.music_tracks.push(track);
+                    assets
+                        .sources
+                        .push((entry.path.clone(), AssetSlot::Music(index)));
+                    assets.music.insert(entry.name.clone(), MusicHandle(index));
+                }
+            }
+        }
+
+        let result = if errors.0.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        };
+
+        (assets, result)
+    }
+
+    pub fn texture(&self, name: &str) -> Option<TextureHandle> {
+        self.textures.get(name).copied()
+    }
+
+    pub fn font(&self, name: &str) -> Option<FontHandle> {
+        self.fonts.get(name).copied()
+    }
+
+    pub fn sound(&self, name: &str) -> Option<SoundHandle> {
+        self.sounds.get(name).copied()
+    }
+
+    pub fn music(&self, name: &str) -> Option<MusicHandle> {
+        self.music.get(name).copied()
+    }
+
+    /// The texture id for a texture, or 0 (no texture) if it wasn't loaded.
+    pub fn tex_id(&self, name: &str) -> TextureId {
+        self.texture(name).map_or(0, |texture| texture.id())
+    }
+
+    pub fn sound_data(&self, sound: SoundHandle) -> &[u8] {
+        &self.sound_data[sound.0]
+    }
+
+    pub fn music_track(&self, music: MusicHandle) -> &MusicTrack {
+        &self.music_tracks[music.0]
+    }
+
+    pub fn bitmap_font(&self, font: FontHandle) -> &BitmapFont {
+        &self.font_data[font.0]
+    }
+
+    /// Create every loaded texture and font on a render backend.
+    pub fn create_textures(&self, backend: &mut impl RenderBackend) {
+        for tex_id in self.texture_data.keys() {
+            self.create_texture(*tex_id, backend);
+        }
+    }
+
+    /// Create (or replace) a single loaded texture on a render backend.
+    pub fn create_texture(&self, tex_id: TextureId, backend: &mut impl RenderBackend) {
+        if let Some(data) = self.texture_data.get(&tex_id) {
+            backend.create_texture(tex_id, data.w, data.h, &data.pixels);
+        }
+    }
+
+    /// Whether any asset was loaded from a file.
+    pub fn uses_file(&self, path: &Path) -> bool {
+        self.sources.iter().any(|(source, _)| source == path)
+    }
+
+    /// Load a file again, replacing every asset that was loaded from it. Returns the ids of the textures that
+    /// changed, which need to be created again on the render backend. If the file can't be loaded, the old
+    /// assets are kept.
+    pub fn reload_file(&mut self, path: &Path) -> Result<Vec<TextureId>, AssetError> {
+        let mut changed_tex_ids = Vec::new();
+        for (source, slot) in self.sources.iter() {
+            if source != path {
+                continue;
+            }
+
+            match *slot {
+                AssetSlot::Texture(tex_id) => {
+                    self.texture_data.insert(tex_id, load_texture(path)?);
+                    changed_tex_ids.push(tex_id);
+                }
+                AssetSlot::Sound(index) => {
+                    self.sound_data[index] = load_sound(path)?;
+                }
+                AssetSlot::Music(index) => {
+                    let name = self.music_tracks[index].name.clone();
+                    self.music_tracks[index] = load_music(&name, path)?;
+                }
+                AssetSlot::Font(index) => {
+                    // The page is loaded again too, in case the descriptor now points at another one
+                    let tex_id = self.font_data[index].tex_id();
+                    let (mut font, data) = load_font(path)?;
+                    font.set_tex_id(tex_id);
+                    self.font_data[index] = font;
+                    self.texture_data.insert(tex_id, data);
+                    changed_tex_ids.push(tex_id);
+                }
+            }
+        }
+
+        Ok(changed_tex_ids)
+    }
+}
+
+fn load_texture(path: &Path) -> Result<TextureData, AssetError> {
+    let bytes = fs::read(path).map_err(|e| AssetError::Missing(path.to_owned(), e))?;
+    let image = image::load_from_memory(&bytes)
+        .map_err(|e| AssetError::Undecodable(path.to_owned(), e.to_string()))?
+        .to_rgba();
+
+    Ok(TextureData {
+        w: image.width(),
+        h: image.height(),
+        pixels: image.into_raw(),
+    })
+}
+
+fn load_sound(path: &Path) -> Result<Vec<u8>, AssetError> {
+    let bytes = fs::read(path).map_err(|e| AssetError::Missing(path.to_owned(), e))?;
+
+    // Only the header is read here, the rest is decoded as it plays
+    if let Err(e) = rodio::Decoder::new(Cursor::new(bytes.clone())) {
+        return Err(AssetError::Undecodable(path.to_owned(), e.to_string()));
+    }
+
+    Ok(bytes)
+}
+
+/// Check that a music file can be decoded and read its loop points, without loading the rest of it.
+fn load_music(name: &str, path: &Path) -> Result<MusicTrack, AssetError> {
+    let file = File::open(path).map_err(|e| AssetError::Missing(path.to_owned(), e))?;
+    if let Err(e) = rodio::Decoder::new(BufReader::new(file)) {
+        return Err(AssetError::Undecodable(path.to_owned(), e.to_string()));
+    }
+
+    MusicTrack::open(name, path)
+        .map_err(|e| AssetError::Undecodable(path.to_owned(), e.to_string()))
+}
+
+/// Load a font descriptor along with its page texture.
+fn load_font(path: &Path) -> Result<(BitmapFont, TextureData), AssetError> {
+    let font = BitmapFont::load(path).map_err(|e| match e {
+        FontError::Io(path, e) => AssetError::Missing(path, e),
+        FontError::Parse {
+            path,
+            line,
+            message,
+        } => AssetError::Undecodable(path, format!("line {}: {}", line, message)),
+    })?;
+    let data = load_texture(font.page_path())?;
+
+    Ok((font, data))
+}
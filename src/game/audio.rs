@@ -1,55 +1,398 @@
-use std::{io::{Cursor, Read}, collections::HashMap};
-use rodio::Source;
+use crate::game::{
+    audio_backend::{
+        AudioBackend, AudioSource, CaptureBackend, CapturedSound, NullBackend, PlayRequest,
+        RodioBackend,
+    },
+    music::MusicTrack,
+};
 
-pub fn play(id: AudioAssetId, audio_db: &AudioAssetDb, is_looping: bool) {
-    let device = rodio::default_output_device().unwrap();
+// Sounds playing at once (music included). Past this, the oldest sound effect is cut off to make room.
+pub const AUDIO_MAX_VOICES: usize = 16;
 
-    if let Some(clip) = audio_db.asset(&id).cloned() {
-        let s = rodio::Decoder::new(std::io::BufReader::new(Cursor::new(clip))).unwrap();
-        if is_looping {
-            rodio::play_raw(&device, s.convert_samples().repeat_infinite());
-        } else {
-            rodio::play_raw(&device, s.convert_samples());
+// How long it takes one music track to fade into the next
+pub const MUSIC_CROSSFADE_TICKS: u32 = 90;
+
+/// Where played audio goes. The null output discards everything, so the game can run without a sound card, and
+/// the capture output records what was played for tests.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioOutput {
+    Device,
+    Null,
+    Capture,
+}
+
+/// Groups of sounds that share a volume.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioBus {
+    Music,
+    Sfx,
+    Ui,
+}
+
+impl AudioBus {
+    fn index(self) -> usize {
+        match self {
+            AudioBus::Music => 0,
+            AudioBus::Sfx => 1,
+            AudioBus::Ui => 2,
         }
-    } else {
-        eprintln!("Failed to play audio file! Audio asset with id {:?} did not exist!", id);
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
-pub enum AudioAssetId {
-    MusicBackground = 0,
-    SfxBallBounce0 = 1,
-    SfxBallBounce1 = 2,
-    SfxBallWallHit0 = 3,
-    SfxBallWallHit1 = 4,
-    SfxBrickBreak0 = 5,
-    SfxBrickBreak1 = 6,
-    SfxBallDeath0 = 7,
+/// Refers to a sound that was played, to stop or fade it later. Handles to sounds that have finished (or were
+/// cut off) are ignored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VoiceHandle {
+    index: usize,
+    generation: u32,
+}
+
+struct Fade {
+    from: f32,
+    to: f32,
+    ticks: u32,
+    ticks_elapsed: u32,
+    stop_when_done: bool,
+}
+
+impl Fade {
+    fn volume(&self) -> f32 {
+        let t = self.ticks_elapsed as f32 / self.ticks.max(1) as f32;
+        self.from + (self.to - self.from) * t.min(1.0)
+    }
+
+    fn is_done(&self) -> bool {
+        self.ticks_elapsed >= self.ticks
+    }
+}
+
+struct Voice {
+    is_active: bool,
+    generation: u32,
+    bus: AudioBus,
+    volume: f32,
+    fade: Option<Fade>,
+
+    // When the voice was started, so the oldest can be stolen
+    started: u64,
 }
 
-pub struct AudioAssetDb {
-    assets: HashMap<AudioAssetId, Vec<u8>>,
+/// Owns the audio backend and everything playing on it. Each sound plays on its own voice, whose volume is
+/// scaled by its bus and the master volume.
+pub struct AudioMixer {
+    output: AudioOutput,
+    backend: Box<dyn AudioBackend>,
+    master_volume: f32,
+    bus_volumes: [f32; 3],
+    voices: Vec<Voice>,
+    music: Option<VoiceHandle>,
+    voices_started: u64,
+
+    // Counts calls to `update`, which happen once per game tick
+    tick: u64,
 }
 
-impl AudioAssetDb {
-    pub fn new() -> Self {
-        AudioAssetDb {
-            assets: HashMap::new(),
+impl AudioMixer {
+    pub fn new(output: AudioOutput) -> Self {
+        let backend: Box<dyn AudioBackend> = match output {
+            AudioOutput::Device => Box::new(RodioBackend::new()),
+            AudioOutput::Null => Box::new(NullBackend),
+            AudioOutput::Capture => Box::new(CaptureBackend::new()),
+        };
+
+        AudioMixer {
+            output,
+            backend,
+            master_volume: 1.0,
+            bus_volumes: [1.0; 3],
+            voices: Vec::new(),
+            music: None,
+            voices_started: 0,
+            tick: 0,
         }
     }
 
-    pub fn import(&mut self, id: AudioAssetId, path: &str) -> std::io::Result<()> {
-        let mut file = std::fs::File::open(path).unwrap();
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
+    pub fn output(&self) -> AudioOutput {
+        self.output
+    }
 
-        self.assets.insert(id, buffer);
+    /// The number of ticks the mixer has been updated for.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
 
-        Ok(())
+    /// Every sound played so far, if the output is `AudioOutput::Capture`.
+    pub fn captured_sounds(&self) -> &[CapturedSound] {
+        self.backend.captured_sounds()
     }
 
-    pub fn asset(&self, id: &AudioAssetId) -> Option<&Vec<u8>> {
-        self.assets.get(id)
+    pub fn master_volume(&self) -> f32 {
+        self.master_volume
+    }
+
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.master_volume = volume.max(0.0);
+        self.apply_volumes();
+    }
+
+    pub fn bus_volume(&self, bus: AudioBus) -> f32 {
+        self.bus_volumes[bus.index()]
+    }
+
+    pub fn set_bus_volume(&mut self, bus: AudioBus, volume: f32) {
+        self.bus_volumes[bus.index()] = volume.max(0.0);
+        self.apply_volumes();
+    }
+
+    /// Play an encoded clip (e.g. a WAV file) on a bus. The name is only used to report what was played.
+    /// Returns `None` if nothing could be played.
+    pub fn play(
+        &mut self,
+        name: &str,
+        clip: &[u8],
+        bus: AudioBus,
+        is_looping: bool,
+    ) -> Option<VoiceHandle> {
+        self.play_with(name, clip, bus, is_looping, 1.0, 1.0)
+    }
+
+    /// Play a clip at a volume (before the bus and master volumes) and pitch, where 1 leaves it unchanged.
+    pub fn play_with(
+        &mut self,
+        name: &str,
+        clip: &[u8],
+        bus: AudioBus,
+        is_looping: bool,
+        volume: f32,
+        pitch: f32,
+    ) -> Option<VoiceHandle> {
+        let source = AudioSource::Clip(clip);
+        self.play_source(name, source, bus, is_looping, volume, pitch)
+    }
+
+    fn play_source(
+        &mut self,
+        name: &str,
+        source: AudioSource,
+        bus: AudioBus,
+        is_looping: bool,
+        volume: f32,
+        pitch: f32,
+    ) -> Option<VoiceHandle> {
+        self.remove_finished_voices();
+        let index = match self.free_voice_index() {
+            Some(index) => index,
+            None => {
+                eprintln!(
+                    "Failed to play audio clip `{}`! All voices are playing music.",
+                    name
+                );
+                return None;
+            }
+        };
+
+        let request = PlayRequest {
+            name,
+            source,
+            bus,
+            volume: volume * self.bus_volumes[bus.index()] * self.master_volume,
+            pitch,
+            is_looping,
+            tick: self.tick,
+        };
+        if !self.backend.start(index, &request) {
+            return None;
+        }
+
+        self.voices_started += 1;
+        let voice = &mut self.voices[index];
+        voice.is_active = true;
+        voice.generation += 1;
+        voice.bus = bus;
+        voice.volume = volume;
+        voice.fade = None;
+        voice.started = self.voices_started;
+
+        Some(VoiceHandle {
+            index,
+            generation: voice.generation,
+        })
+    }
+
+    pub fn is_playing(&self, handle: VoiceHandle) -> bool {
+        self.voice(handle).is_some() && self.backend.is_playing(handle.index)
+    }
+
+    pub fn stop(&mut self, handle: VoiceHandle) {
+        if let Some(voice) = self.voice_mut(handle) {
+            voice.is_active = false;
+            voice.fade = None;
+            self.backend.stop(handle.index);
+        }
+    }
+
+    pub fn set_volume(&mut self, handle: VoiceHandle, volume: f32) {
+        if let Some(voice) = self.voice_mut(handle) {
+            voice.volume = volume.max(0.0);
+            voice.fade = None;
+        }
+        self.apply_volumes();
+    }
+
+    /// Fade a sound to a volume over a number of ticks.
+    pub fn fade_to(&mut self, handle: VoiceHandle, volume: f32, ticks: u32) {
+        self.start_fade(handle, volume.max(0.0), ticks, false);
+    }
+
+    /// Fade a sound out over a number of ticks, then stop it.
+    pub fn fade_out(&mut self, handle: VoiceHandle, ticks: u32) {
+        self.start_fade(handle, 0.0, ticks, true);
+    }
+
+    /// Stream a music track from disk, crossfading from whatever music was playing before. A looping track
+    /// repeats between its loop points.
+    pub fn play_music(
+        &mut self,
+        track: &MusicTrack,
+        is_looping: bool,
+        fade_ticks: u32,
+    ) -> Option<VoiceHandle> {
+        if let Some(previous) = self.music.take() {
+            self.fade_out(previous, fade_ticks);
+        }
+
+        // Start silent and fade in
+        let start_volume = if fade_ticks > 0 { 0.0 } else { 1.0 };
+        let source = AudioSource::Stream(track);
+        self.music = self.play_source(
+            &track.name,
+            source,
+            AudioBus::Music,
+            is_looping,
+            start_volume,
+            1.0,
+        );
+        if let Some(music) = self.music {
+            self.fade_to(music, 1.0, fade_ticks);
+        }
+
+        self.music
+    }
+
+    /// Fade out the music that's playing, if any.
+    pub fn stop_music(&mut self, fade_ticks: u32) {
+        if let Some(music) = self.music.take() {
+            self.fade_out(music, fade_ticks);
+        }
+    }
+
+    /// Step the fades along by a tick, and free the voices of sounds that have finished.
+    pub fn update(&mut self) {
+        self.tick += 1;
+
+        for (index, voice) in self.voices.iter_mut().enumerate() {
+            let fade_done = match voice.fade.as_mut() {
+                Some(fade) => {
+                    fade.ticks_elapsed += 1;
+                    voice.volume = fade.volume();
+                    fade.is_done()
+                }
+                None => false,
+            };
+
+            if fade_done {
+                let fade = voice.fade.take().unwrap();
+                if fade.stop_when_done {
+                    voice.is_active = false;
+                    self.backend.stop(index);
+                }
+            }
+        }
+
+        self.remove_finished_voices();
+        self.apply_volumes();
+    }
+
+    /// The number of sounds playing.
+    pub fn voices_playing(&self) -> usize {
+        (0..self.voices.len())
+            .filter(|index| self.voices[*index].is_active && self.backend.is_playing(*index))
+            .count()
+    }
+
+    fn voice(&self, handle: VoiceHandle) -> Option<&Voice> {
+        self.voices
+            .get(handle.index)
+            .filter(|voice| voice.generation == handle.generation && voice.is_active)
+    }
+
+    fn voice_mut(&mut self, handle: VoiceHandle) -> Option<&mut Voice> {
+        self.voices
+            .get_mut(handle.index)
+            .filter(|voice| voice.generation == handle.generation && voice.is_active)
+    }
+
+    fn start_fade(&mut self, handle: VoiceHandle, volume: f32, ticks: u32, stop_when_done: bool) {
+        if let Some(voice) = self.voice_mut(handle) {
+            voice.fade = Some(Fade {
+                from: voice.volume,
+                to: volume,
+                ticks,
+                ticks_elapsed: 0,
+                stop_when_done,
+            });
+        }
+    }
+
+    fn remove_finished_voices(&mut self) {
+        for (index, voice) in self.voices.iter_mut().enumerate() {
+            if voice.is_active && !self.backend.is_playing(index) {
+                voice.is_active = false;
+                voice.fade = None;
+            }
+        }
+    }
+
+    /// A voice to play a new sound on, stealing the oldest sound effect if they're all in use.
+    fn free_voice_index(&mut self) -> Option<usize> {
+        if let Some(index) = self.voices.iter().position(|voice| !voice.is_active) {
+            return Some(index);
+        }
+
+        if self.voices.len() < AUDIO_MAX_VOICES {
+            self.voices.push(Voice {
+                is_active: false,
+                generation: 0,
+                bus: AudioBus::Sfx,
+                volume: 1.0,
+                fade: None,
+                started: 0,
+            });
+            return Some(self.voices.len() - 1);
+        }
+
+        let oldest = self
+            .voices
+            .iter()
+            .enumerate()
+            .filter(|(_, voice)| voice.bus != AudioBus::Music)
+            .min_by_key(|(_, voice)| voice.started)
+            .map(|(index, _)| index)?;
+
+        self.voices[oldest].is_active = false;
+        self.backend.stop(oldest);
+
+        Some(oldest)
+    }
+
+    fn apply_volumes(&mut self) {
+        for (index, voice) in self.voices.iter().enumerate() {
+            if voice.is_active {
+                self.backend.set_volume(
+                    index,
+                    voice.volume * self.bus_volumes[voice.bus.index()] * self.master_volume,
+                );
+            }
+        }
     }
 }
@@ -0,0 +1,325 @@
+use crate::game::{audio::AudioBus, music::MusicTrack};
+use rodio::{Decoder, Device, Sink, Source};
+use std::{
+    fs::File,
+    io::{self, BufReader, Cursor},
+    time::Duration,
+};
+
+/// What a sound is played from.
+#[derive(Clone, Copy)]
+pub enum AudioSource<'a> {
+    // An encoded clip that's already in memory, e.g. a WAV file
+    Clip(&'a [u8]),
+
+    // A file that's decoded as it plays, looping between its loop points if the sound loops
+    Stream(&'a MusicTrack),
+}
+
+/// A sound the mixer wants started.
+pub struct PlayRequest<'a> {
+    pub name: &'a str,
+    pub source: AudioSource<'a>,
+    pub bus: AudioBus,
+
+    // The final volume, after the bus and master volumes
+    pub volume: f32,
+
+    // Playback speed, where 1 is unchanged
+    pub pitch: f32,
+    pub is_looping: bool,
+
+    // The mixer's tick count when the sound was played
+    pub tick: u64,
+}
+
+/// Where the mixer's voices end up. Voices are numbered by the mixer, and a voice is only started again once it's
+/// been stopped or has finished.
+pub trait AudioBackend: Send + Sync {
+    /// Start a sound on a voice. Returns false if it couldn't be played.
+    fn start(&mut self, voice: usize, request: &PlayRequest) -> bool;
+
+    fn stop(&mut self, voice: usize);
+
+    fn set_volume(&mut self, voice: usize, volume: f32);
+
+    fn is_playing(&self, voice: usize) -> bool;
+
+    /// The sounds recorded so far, for backends that record them.
+    fn captured_sounds(&self) -> &[CapturedSound] {
+        &[]
+    }
+}
+
+/// Plays through the default output device, which is opened the first time anything is played.
+#[derive(Default)]
+pub struct RodioBackend {
+    device: Option<Device>,
+    device_opened: bool,
+    sinks: Vec<Option<Sink>>,
+}
+
+impl RodioBackend {
+    pub fn new() -> Self {
+        RodioBackend {
+            ..Default::default()
+        }
+    }
+
+    fn device(&mut self) -> Option<&Device> {
+        if !self.device_opened {
+            self.device_opened = true;
+            self.device = rodio::default_output_device();
+            if self.device.is_none() {
+                eprintln!("Failed to open audio device! Sounds won't be played.");
+            }
+        }
+
+        self.device.as_ref()
+    }
+}
+
+impl AudioBackend for RodioBackend {
+    fn start(&mut self, voice: usize, request: &PlayRequest) -> bool {
+        let device = match self.device() {
+            Some(device) => device,
+            None => return false,
+        };
+
+        let sink = Sink::new(device);
+        sink.set_volume(request.volume);
+
+        let result = match request.source {
+            AudioSource::Clip(clip) => Decoder::new(Cursor::new(clip.to_vec())).map(|source| {
+                let source = source.convert_samples::<f32>().speed(request.pitch);
+                if request.is_looping {
+                    sink.append(source.repeat_infinite());
+                } else {
+                    sink.append(source);
+                }
+            }),
+            AudioSource::Stream(track) => match StreamingSource::open(track, request.is_looping) {
+                Ok(source) => {
+                    sink.append(source.convert_samples::<f32>().speed(request.pitch));
+                    Ok(())
+                }
+                Err(e) => {
+                    eprintln!("Failed to stream music `{}`! {}", request.name, e);
+                    return false;
+                }
+            },
+        };
+
+        if let Err(e) = result {
+            eprintln!("Failed to decode audio clip `{}`! {}", request.name, e);
+            return false;
+        }
+
+        if self.sinks.len() <= voice {
+            self.sinks.resize_with(voice + 1, || None);
+        }
+        if let Some(previous) = self.sinks[voice].replace(sink) {
+            previous.stop();
+        }
+
+        true
+    }
+
+    fn stop(&mut self, voice: usize) {
+        if let Some(sink) = self.sinks.get_mut(voice).and_then(|sink| sink.take()) {
+            sink.stop();
+        }
+    }
+
+    fn set_volume(&mut self, voice: usize, volume: f32) {
+        if let Some(Some(sink)) = self.sinks.get(voice) {
+            sink.set_volume(volume);
+        }
+    }
+
+    fn is_playing(&self, voice: usize) -> bool {
+        match self.sinks.get(voice) {
+            Some(Some(sink)) => !sink.empty(),
+            _ => false,
+        }
+    }
+}
+
+/// Decodes a music file as it plays. When looping, playback jumps back to the loop start once it reaches the loop
+/// end (or the end of the file), by opening the file again and decoding up to the loop start.
+struct StreamingSource {
+    track: MusicTrack,
+    decoder: Decoder<BufReader<File>>,
+    channels: u16,
+    sample_rate: u32,
+    is_looping: bool,
+
+    // Position in the file, in samples (a frame has a sample for each channel)
+    position: u64,
+}
+
+impl StreamingSource {
+    fn open(track: &MusicTrack, is_looping: bool) -> io::Result<Self> {
+        let decoder = open_decoder(track)?;
+
+        Ok(StreamingSource {
+            track: track.clone(),
+            channels: decoder.channels(),
+            sample_rate: decoder.sample_rate(),
+            decoder,
+            is_looping,
+            position: 0,
+        })
+    }
+
+    /// Start decoding from the loop start again. Returns false if the file can't be opened any more.
+    fn restart(&mut self) -> bool {
+        let mut decoder = match open_decoder(&self.track) {
+            Ok(decoder) => decoder,
+            Err(e) => {
+                eprintln!("Failed to loop music `{}`! {}", self.track.name, e);
+                return false;
+            }
+        };
+
+        let loop_start = self.track.loop_start * self.channels as u64;
+        for _ in 0..loop_start {
+            if decoder.next().is_none() {
+                return false;
+            }
+        }
+
+        self.decoder = decoder;
+        self.position = loop_start;
+        true
+    }
+}
+
+fn open_decoder(track: &MusicTrack) -> io::Result<Decoder<BufReader<File>>> {
+    let file = BufReader::new(File::open(&track.path)?);
+    Decoder::new(file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+impl Iterator for StreamingSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        let loop_end = self.track.loop_end.map(|end| end * self.channels as u64);
+        let past_loop_end = loop_end.map_or(false, |end| self.position >= end);
+        if self.is_looping && past_loop_end && !self.restart() {
+            return None;
+        }
+
+        let sample = match self.decoder.next() {
+            Some(sample) => sample,
+            None if self.is_looping && self.position > 0 => {
+                if !self.restart() {
+                    return None;
+                }
+                self.decoder.next()?
+            }
+            None => return None,
+        };
+
+        self.position += 1;
+        Some(sample)
+    }
+}
+
+impl Source for StreamingSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Discards everything, so the game can run without a sound card.
+pub struct NullBackend;
+
+impl AudioBackend for NullBackend {
+    fn start(&mut self, _voice: usize, _request: &PlayRequest) -> bool {
+        false
+    }
+
+    fn stop(&mut self, _voice: usize) {}
+
+    fn set_volume(&mut self, _voice: usize, _volume: f32) {}
+
+    fn is_playing(&self, _voice: usize) -> bool {
+        false
+    }
+}
+
+/// A sound that was played on the capture backend.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CapturedSound {
+    pub tick: u64,
+    pub name: String,
+    pub bus: AudioBus,
+    pub volume: f32,
+    pub pitch: f32,
+    pub is_looping: bool,
+}
+
+/// Doesn't play anything, and instead records every sound that was started, so tests can check what was heard.
+/// Clips aren't decoded, so sounds finish as soon as they start, except looping ones which play until stopped.
+#[derive(Default)]
+pub struct CaptureBackend {
+    pub sounds: Vec<CapturedSound>,
+    looping_voices: Vec<bool>,
+}
+
+impl CaptureBackend {
+    pub fn new() -> Self {
+        CaptureBackend {
+            ..Default::default()
+        }
+    }
+}
+
+impl AudioBackend for CaptureBackend {
+    fn start(&mut self, voice: usize, request: &PlayRequest) -> bool {
+        self.sounds.push(CapturedSound {
+            tick: request.tick,
+            name: request.name.to_owned(),
+            bus: request.bus,
+            volume: request.volume,
+            pitch: request.pitch,
+            is_looping: request.is_looping,
+        });
+
+        if self.looping_voices.len() <= voice {
+            self.looping_voices.resize(voice + 1, false);
+        }
+        self.looping_voices[voice] = request.is_looping;
+
+        true
+    }
+
+    fn stop(&mut self, voice: usize) {
+        if let Some(looping) = self.looping_voices.get_mut(voice) {
+            *looping = false;
+        }
+    }
+
+    fn set_volume(&mut self, _voice: usize, _volume: f32) {}
+
+    fn is_playing(&self, voice: usize) -> bool {
+        self.looping_voices.get(voice).copied().unwrap_or(false)
+    }
+
+    fn captured_sounds(&self) -> &[CapturedSound] {
+        &self.sounds
+    }
+}
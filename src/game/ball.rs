@@ -1,14 +1,17 @@
 use crate::game::{
+    assets::Assets,
+    audio::AudioMixer,
     brick::BrickComponent,
-    paddle::{PlayerPaddleComponent, PADDLE_HIT_BOX_WIDTH},
+    paddle::PlayerPaddleComponent,
     physics::{ColliderComponent, CollisionEvent, RigidbodyComponent},
-    render::SpriteComponent,
+    powerup::{PowerUpKind, PowerUpState},
+    render::{ScreenShake, SpriteComponent, SCREEN_SHAKE_BALL_DEATH},
+    sound_cue::SoundCues,
     transform::TransformComponent,
-    audio::{self, AudioAssetId, AudioAssetDb},
     LevelState, Point2f, Vector2d, Vector2f,
 };
-use gfx::{color::*, renderer::Transparency, sprite::SpriteRegion};
-use nalgebra::Vector2;
+use gfx::{atlas::TextureAtlas, color::*, renderer::Transparency};
+use nalgebra::{Rotation2, Vector2};
 use ncollide2d::shape::Ball;
 use nphysics2d::{math::Velocity, object::BodyStatus};
 use shrev::EventChannel;
@@ -40,6 +43,34 @@ impl BallComponent {
             holding_paddle_ent,
         }
     }
+
+    /// Split this ball into `count` balls, by building spawn events for `count - 1` new balls at `position`.
+    /// The new balls alternate either side of this ball's direction, with each pair fanned out another `spread` radians.
+    /// A held ball is split as if it had just been launched straight up at `launch_speed`.
+    pub fn split(
+        &self,
+        position: Vector2d,
+        count: usize,
+        spread: f64,
+        launch_speed: f64,
+    ) -> Vec<SpawnBallEvent> {
+        let velocity = match self.holding_paddle_ent {
+            Some(_) => Vector2d::new(0.0, -launch_speed),
+            None => self.velocity.linear,
+        };
+
+        (0..count.saturating_sub(1))
+            .map(|i| {
+                let side = if i % 2 == 0 { 1.0 } else { -1.0 };
+                let angle = side * spread * ((i / 2) + 1) as f64;
+                SpawnBallEvent {
+                    position,
+                    linear_velocity: Rotation2::new(angle) * velocity,
+                    owning_paddle_ent: None,
+                }
+            })
+            .collect()
+    }
 }
 
 impl Component for BallComponent {
@@ -55,13 +86,17 @@ impl<'a> System<'a> for BallSystem {
     type SystemData = (
         Entities<'a>,
         Write<'a, LevelState>,
-        ReadExpect<'a, AudioAssetDb>,
+        Read<'a, PowerUpState>,
+        ReadExpect<'a, Assets>,
+        WriteExpect<'a, AudioMixer>,
+        WriteExpect<'a, SoundCues>,
+        Write<'a, ScreenShake>,
         Read<'a, EventChannel<CollisionEvent>>,
         Write<'a, EventChannel<SpawnBallEvent>>,
         WriteStorage<'a, TransformComponent>,
         WriteStorage<'a, BallComponent>,
         ReadStorage<'a, BrickComponent>,
-        ReadStorage<'a, PlayerPaddleComponent>,
+        WriteStorage<'a, PlayerPaddleComponent>,
         WriteStorage<'a, RigidbodyComponent>,
     );
 
@@ -79,13 +114,17 @@ impl<'a> System<'a> for BallSystem {
         (
             ents,
             mut level,
-            audio_db,
+            powerups,
+            assets,
+            mut mixer,
+            mut cues,
+            mut shake,
             collision_events,
             mut spawn_ball_events,
             mut transforms,
             mut balls,
             bricks,
-            paddles,
+            mut paddles,
             mut rigidbodies,
         ): Self::SystemData,
     ) {
@@ -101,7 +140,7 @@ impl<'a> System<'a> for BallSystem {
             };
 
             if let Some(ball) = balls.get_mut(entity_a) {
-                if let Some(_) = paddles.get(entity_b) {
+                if let Some(paddle) = paddles.get_mut(entity_b) {
                     let paddle_transform = transforms.get(entity_b).unwrap();
                     let hit_x = match event.collision_point {
                         Some(p) => p.x,
@@ -116,30 +155,26 @@ impl<'a> System<'a> for BallSystem {
 
                     // Get the x hit value, relative to the paddle hit box width. -1.0 means the ball hit the far left side of the paddle, while 1.0 means it hit the far right.
                     let hit_x_ratio =
-                        (hit_x - paddle_transform.position.x) / (PADDLE_HIT_BOX_WIDTH / 2.0);
+                        (hit_x - paddle_transform.position.x) / (paddle.hit_box_width / 2.0);
 
                     let mut vel = ball.velocity.linear;
                     vel.y = ((vel.x.abs() * 0.25) + vel.y) * -0.97;
-                    vel.x = hit_x_ratio * BALL_DEFAULT_FORCE;
+                    vel.x = hit_x_ratio * level.ball_speed;
 
                     vel = vel.normalize()
                         * nalgebra::clamp(vel.magnitude(), 0.0, BALL_MAX_LINEAR_VELOCITY);
                     ball.velocity = Velocity::new(vel, 0.0);
                     println!("reflected off paddle: {:?}", ball.velocity);
 
-                    // Pick and play one of the ball paddle bounce audio clips
-                    let clip_id = {
-                        use rand::Rng;
-                        let roll: f32 = rand::thread_rng().gen();
-
-                        if roll <= 0.5 {
-                            AudioAssetId::SfxBallBounce0
-                        } else {
-                            AudioAssetId::SfxBallBounce1
-                        }
-                    };
+                    // A sticky paddle catches the ball, unless it's already holding one
+                    if powerups.is_active(PowerUpKind::StickyPaddle)
+                        && paddle.held_ball_ent.is_none()
+                    {
+                        ball.holding_paddle_ent = Some(entity_b);
+                        paddle.held_ball_ent = Some(entity_a);
+                    }
 
-                    audio::play(clip_id, &audio_db, false);
+                    cues.trigger("ball_paddle_bounce", &assets, &mut mixer);
 
                     continue;
                 }
@@ -169,19 +204,7 @@ impl<'a> System<'a> for BallSystem {
                         ball.velocity, normal
                     );
 
-                    // Pick and play one of the ball hit audio clips
-                    let clip_id = {
-                        use rand::Rng;
-                        let roll: f32 = rand::thread_rng().gen();
-
-                        if roll <= 0.5 {
-                            AudioAssetId::SfxBallWallHit0
-                        } else {
-                            AudioAssetId::SfxBallWallHit1
-                        }
-                    };
-
-                    audio::play(clip_id, &audio_db, false);
+                    cues.trigger("ball_wall_hit", &assets, &mut mixer);
                 } else {
                     println!(
                         "Ball collision had no normal! ball ent = {}, other ent = {}",
@@ -192,6 +215,8 @@ impl<'a> System<'a> for BallSystem {
             }
         }
 
+        // Counted from the balls themselves, so however balls are spawned or cleared the count can't drift
+        let mut balls_in_play = (&balls).join().count();
         for (ent, mut transform, rigidbody, ball) in
             (&ents, &mut transforms, &mut rigidbodies, &mut balls).join()
         {
@@ -209,13 +234,23 @@ impl<'a> System<'a> for BallSystem {
 
             // Directly set the ball velocity every tick to keep the physics engine from affecting it
             rigidbody.status = BodyStatus::Dynamic;
-            rigidbody.velocity = ball.velocity;
+            rigidbody.velocity = Velocity::new(
+                ball.velocity.linear * powerups.ball_speed_scale(),
+                ball.velocity.angular,
+            );
 
             // TODO replace this with a sensor collider?
             if transform.position.y > 235.0 {
                 ents.delete(ent).expect("Failed to delete ball ent!");
 
-                audio::play(AudioAssetId::SfxBallDeath0, &audio_db, false);
+                cues.trigger("ball_death", &assets, &mut mixer);
+                shake.shake(SCREEN_SHAKE_BALL_DEATH);
+
+                // Only lose a life once the last ball in play has left the field
+                balls_in_play -= 1;
+                if balls_in_play > 0 {
+                    continue;
+                }
 
                 level.lives -= 1;
                 println!("{} balls remaining.", level.lives);
@@ -245,6 +280,7 @@ impl<'a> System<'a> for SpawnBallSystem {
     type SystemData = (
         Entities<'a>,
         Read<'a, LazyUpdate>,
+        ReadExpect<'a, TextureAtlas>,
         Read<'a, EventChannel<SpawnBallEvent>>,
         WriteStorage<'a, PlayerPaddleComponent>,
     );
@@ -258,7 +294,10 @@ impl<'a> System<'a> for SpawnBallSystem {
         );
     }
 
-    fn run(&mut self, (ents, lazy_updater, spawn_ball_events, mut paddles): Self::SystemData) {
+    fn run(
+        &mut self,
+        (ents, lazy_updater, atlas, spawn_ball_events, mut paddles): Self::SystemData,
+    ) {
         for event in spawn_ball_events.read(&mut self.spawn_ball_event_reader.as_mut().unwrap()) {
             let ent = ents.create();
 
@@ -284,13 +323,8 @@ impl<'a> System<'a> for SpawnBallSystem {
                 ent,
                 SpriteComponent {
                     color: COLOR_WHITE,
-                    spritesheet_tex_id: 2,
-                    region: SpriteRegion {
-                        x: 64,
-                        y: 0,
-                        w: 32,
-                        h: 32,
-                    },
+                    spritesheet_tex_id: atlas.tex_id(),
+                    region: atlas.sprite("ball"),
                     layer: 2,
                     transparency: Transparency::Opaque,
                 },
@@ -1,18 +1,111 @@
-use crate::game::{audio::{self, AudioAssetId, AudioAssetDb}, ball::BallComponent, physics::CollisionEvent, LevelState};
+use crate::game::{
+    assets::Assets,
+    audio::AudioMixer,
+    ball::{BallComponent, SpawnBallEvent},
+    physics::CollisionEvent,
+    powerup::{LaserBoltComponent, PowerUpKind, SpawnPowerUpEvent},
+    render::{ScreenShake, SpriteComponent, SCREEN_SHAKE_BRICK_BREAK},
+    rng::RngState,
+    sound_cue::SoundCues,
+    transform::TransformComponent,
+    LevelState, Vector2d,
+};
+use gfx::atlas::TextureAtlas;
+use rand::Rng;
 use shrev::EventChannel;
 use specs::prelude::*;
 
-pub const BRICK_DEFAULT_HP: i32 = 2;
 pub const BRICK_SPRITE_WIDTH: u32 = 32;
 pub const BRICK_SPRITE_HEIGHT: u32 = 16;
+pub const BRICK_EXPLOSION_RADIUS: f64 = 40.0;
+pub const BRICK_EXPLOSION_DAMAGE: i32 = 1;
+
+// Sprite names for each damage state of a brick, starting with the undamaged sprite
+const WEAK_BRICK_SPRITES: [&str; 1] = ["brick/yellow"];
+const STANDARD_BRICK_SPRITES: [&str; 2] = ["brick/red", "brick/red/cracked"];
+const TOUGH_BRICK_SPRITES: [&str; 3] = ["brick/blue", "brick/blue/cracked", "brick/blue/broken"];
+const STEEL_BRICK_SPRITES: [&str; 1] = ["brick/steel"];
+const EXPLOSIVE_BRICK_SPRITES: [&str; 1] = ["brick/explosive"];
+const SPLITTER_BRICK_SPRITES: [&str; 1] = ["brick/splitter"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BrickKind {
+    Weak,
+    Standard,
+    Tough,
+    Steel,
+    Explosive,
+    Splitter,
+}
+
+impl BrickKind {
+    /// Get the brick kind for a brick code in a level file.
+    pub fn from_code(code: char) -> Option<BrickKind> {
+        match code {
+            'Y' => Some(BrickKind::Weak),
+            '#' | 'R' => Some(BrickKind::Standard),
+            'B' => Some(BrickKind::Tough),
+            'S' => Some(BrickKind::Steel),
+            'X' => Some(BrickKind::Explosive),
+            'O' => Some(BrickKind::Splitter),
+            _ => None,
+        }
+    }
+
+    pub fn max_hp(self) -> i32 {
+        match self {
+            BrickKind::Weak => 1,
+            BrickKind::Standard => 2,
+            BrickKind::Tough => 3,
+            BrickKind::Steel => 1,
+            BrickKind::Explosive => 1,
+            BrickKind::Splitter => 1,
+        }
+    }
+
+    pub fn score(self) -> u32 {
+        match self {
+            BrickKind::Weak => 50,
+            BrickKind::Standard => 100,
+            BrickKind::Tough => 200,
+            BrickKind::Steel => 0,
+            BrickKind::Explosive => 150,
+            BrickKind::Splitter => 150,
+        }
+    }
+
+    /// Indestructible bricks never take damage, and don't need to be broken to clear a level.
+    pub fn is_destructible(self) -> bool {
+        self != BrickKind::Steel
+    }
+
+    /// Get the sprite name for a brick of this kind with the given hp, so damaged bricks look damaged.
+    pub fn sprite_name(self, hp: i32) -> &'static str {
+        let names: &[&'static str] = match self {
+            BrickKind::Weak => &WEAK_BRICK_SPRITES,
+            BrickKind::Standard => &STANDARD_BRICK_SPRITES,
+            BrickKind::Tough => &TOUGH_BRICK_SPRITES,
+            BrickKind::Steel => &STEEL_BRICK_SPRITES,
+            BrickKind::Explosive => &EXPLOSIVE_BRICK_SPRITES,
+            BrickKind::Splitter => &SPLITTER_BRICK_SPRITES,
+        };
+
+        let damage = (self.max_hp() - hp).max(0) as usize;
+        names[damage.min(names.len() - 1)]
+    }
+}
 
 pub struct BrickComponent {
+    pub kind: BrickKind,
     pub hp: i32,
 }
 
 impl BrickComponent {
-    pub fn new(hp: i32) -> Self {
-        BrickComponent { hp }
+    pub fn new(kind: BrickKind) -> Self {
+        BrickComponent {
+            kind,
+            hp: kind.max_hp(),
+        }
     }
 }
 
@@ -28,11 +121,21 @@ pub struct BrickSystem {
 impl<'a> System<'a> for BrickSystem {
     type SystemData = (
         Entities<'a>,
-        ReadExpect<'a, AudioAssetDb>,
+        ReadExpect<'a, Assets>,
+        WriteExpect<'a, AudioMixer>,
+        WriteExpect<'a, SoundCues>,
+        Write<'a, ScreenShake>,
+        ReadExpect<'a, TextureAtlas>,
         Write<'a, LevelState>,
+        Write<'a, RngState>,
         Read<'a, EventChannel<CollisionEvent>>,
+        Write<'a, EventChannel<SpawnBallEvent>>,
+        Write<'a, EventChannel<SpawnPowerUpEvent>>,
         WriteStorage<'a, BrickComponent>,
+        WriteStorage<'a, SpriteComponent>,
+        ReadStorage<'a, TransformComponent>,
         ReadStorage<'a, BallComponent>,
+        ReadStorage<'a, LaserBoltComponent>,
     );
 
     fn setup(&mut self, world: &mut World) {
@@ -44,7 +147,27 @@ impl<'a> System<'a> for BrickSystem {
         );
     }
 
-    fn run(&mut self, (ents, audio_db, mut level, collision_events, mut bricks, balls): Self::SystemData) {
+    fn run(
+        &mut self,
+        (
+            ents,
+            assets,
+            mut mixer,
+            mut cues,
+            mut shake,
+            atlas,
+            mut level,
+            mut rng,
+            collision_events,
+            mut spawn_ball_events,
+            mut spawn_powerup_events,
+            mut bricks,
+            mut sprites,
+            transforms,
+            balls,
+            laser_bolts,
+        ): Self::SystemData,
+    ) {
         let mut bricks_hit_this_tick: BitSet = BitSet::new();
         for event in collision_events.read(&mut self.collision_event_reader.as_mut().unwrap()) {
             // Get the entities involved in the event, ignoring it entirely if either of them are not an entity
@@ -56,33 +179,97 @@ impl<'a> System<'a> for BrickSystem {
                 (event.entity_a.unwrap(), event.entity_b.unwrap())
             };
 
-            // If the collision was between a brick and a ball entity, mark the brick as hit so we can damage it
-            if bricks.get(entity_a).is_some() && balls.get(entity_b).is_some() {
+            // If the collision was between a brick and a ball or laser bolt entity, mark the brick as hit so we can damage it
+            if bricks.get(entity_a).is_some()
+                && (balls.get(entity_b).is_some() || laser_bolts.get(entity_b).is_some())
+            {
                 bricks_hit_this_tick.add(entity_a.id());
             }
         }
 
-        for (ent, mut brick, _) in (&ents, &mut bricks, &bricks_hit_this_tick).join() {
-            brick.hp -= 1;
-            if brick.hp <= 0 {
-                ents.delete(ent).unwrap();
+        // Damage is processed as a queue, so explosions can damage (and chain into) neighbouring bricks
+        let mut pending_damage: Vec<(Entity, i32)> = (&ents, &bricks_hit_this_tick)
+            .join()
+            .map(|(ent, _)| (ent, 1))
+            .collect();
+        let mut destroyed_bricks: BitSet = BitSet::new();
+
+        while let Some((ent, damage)) = pending_damage.pop() {
+            if destroyed_bricks.contains(ent.id()) {
+                continue;
+            }
 
-                level.score += 100;
+            let brick = match bricks.get_mut(ent) {
+                Some(brick) => brick,
+                None => continue,
+            };
 
-                // Pick and play one of the brick break audio clips
-                let clip_id = {
-                    use rand::Rng;
-                    let roll: f32 = rand::thread_rng().gen();
+            if !brick.kind.is_destructible() {
+                continue;
+            }
 
-                    if roll <= 0.5 {
-                        AudioAssetId::SfxBrickBreak0
-                    } else {
-                        AudioAssetId::SfxBrickBreak1
-                    }
-                };
+            brick.hp -= damage;
+            if brick.hp > 0 {
+                if let Some(sprite) = sprites.get_mut(ent) {
+                    sprite.region = atlas.sprite(brick.kind.sprite_name(brick.hp));
+                }
+
+                continue;
+            }
+
+            let kind = brick.kind;
+            destroyed_bricks.add(ent.id());
+            ents.delete(ent).unwrap();
+
+            level.score += kind.score();
+
+            cues.trigger("brick_break", &assets, &mut mixer);
+            shake.shake(SCREEN_SHAKE_BRICK_BREAK);
 
-                audio::play(clip_id, &audio_db, false);
+            let center = match transforms.get(ent) {
+                Some(transform) => brick_center(transform),
+                None => continue,
+            };
+
+            // Maybe drop a power-up capsule
+            let drop_roll: f32 = rng.gen();
+            if drop_roll < level.powerup_drop_chance {
+                spawn_powerup_events.single_write(SpawnPowerUpEvent {
+                    position: center,
+                    kind: PowerUpKind::random(&mut *rng),
+                });
+            }
+
+            match kind {
+                BrickKind::Explosive => {
+                    for (other_ent, _, other_transform) in (&ents, &bricks, &transforms).join() {
+                        if other_ent == ent || destroyed_bricks.contains(other_ent.id()) {
+                            continue;
+                        }
+
+                        let distance = (brick_center(other_transform) - center).magnitude();
+                        if distance <= BRICK_EXPLOSION_RADIUS {
+                            pending_damage.push((other_ent, BRICK_EXPLOSION_DAMAGE));
+                        }
+                    }
+                }
+                BrickKind::Splitter => {
+                    spawn_ball_events.single_write(SpawnBallEvent {
+                        position: center,
+                        linear_velocity: Vector2d::new(level.ball_speed * 0.5, level.ball_speed),
+                        owning_paddle_ent: None,
+                    });
+                }
+                _ => {}
             }
         }
     }
 }
+
+fn brick_center(transform: &TransformComponent) -> Vector2d {
+    transform.position
+        + Vector2d::new(
+            BRICK_SPRITE_WIDTH as f64 / 2.0,
+            BRICK_SPRITE_HEIGHT as f64 / 2.0,
+        )
+}
@@ -0,0 +1,100 @@
+use crate::game::{
+    ball::BallComponent,
+    brick::BrickComponent,
+    flow::FlowState,
+    level::{self, LevelState},
+    paddle::PlayerPaddleComponent,
+};
+use specs::prelude::*;
+
+pub const CAMPAIGN_PLAYLIST_PATH: &str = "res/levels/campaign.txt";
+pub const LEVEL_COMPLETE_TICKS: u32 = 180;
+
+#[derive(Default)]
+pub struct CampaignState {
+    pub playlist: Vec<String>,
+    pub current_level_index: Option<usize>,
+}
+
+impl CampaignState {
+    pub fn new(playlist: Vec<String>) -> Self {
+        CampaignState {
+            playlist,
+            current_level_index: None,
+        }
+    }
+
+    /// Load the ordered list of level ids from a playlist file, one level id per line.
+    pub fn load(path: &str) -> std::io::Result<Self> {
+        let source = std::fs::read_to_string(path)?;
+        let playlist = source
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with("//"))
+            .map(|line| line.to_owned())
+            .collect();
+
+        Ok(CampaignState::new(playlist))
+    }
+
+    pub fn first_level_id(&self) -> &str {
+        self.playlist
+            .first()
+            .map_or(level::FIRST_LEVEL_ID, |id| id.as_str())
+    }
+
+    pub fn next_level_id(&self) -> Option<&str> {
+        self.current_level_index
+            .and_then(|i| self.playlist.get(i + 1))
+            .map(|id| id.as_str())
+    }
+
+    pub fn begin_level(&mut self, level_id: &str) {
+        self.current_level_index = self.playlist.iter().position(|id| id == level_id);
+    }
+}
+
+/// Detects when every destructible brick in the level has been broken.
+pub struct CampaignSystem;
+
+impl<'a> System<'a> for CampaignSystem {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, LevelState>,
+        Write<'a, FlowState>,
+        ReadStorage<'a, BrickComponent>,
+        ReadStorage<'a, BallComponent>,
+        WriteStorage<'a, PlayerPaddleComponent>,
+    );
+
+    fn run(&mut self, (ents, mut level, mut flow, bricks, balls, mut paddles): Self::SystemData) {
+        // Nothing can be cleared until a level has loaded, e.g. if the first level failed to
+        if level.load_level_event.is_some() || level.player_paddle_ent.is_none() {
+            return;
+        }
+
+        // Indestructible bricks don't need to be broken to clear the level
+        let bricks_remaining = (&ents, &bricks)
+            .join()
+            .filter(|(_, brick)| brick.kind.is_destructible())
+            .count();
+        if level.lives == 0 || bricks_remaining > 0 {
+            return;
+        }
+
+        println!("Level {} complete!", level.level_id);
+
+        // Clear the field so nothing happens while the interstitial is up
+        for (ent, _) in (&ents, &balls).join() {
+            ents.delete(ent).expect("Failed to delete ball ent!");
+        }
+
+        for paddle in (&mut paddles).join() {
+            paddle.held_ball_ent = None;
+        }
+
+        *flow = FlowState::LevelComplete {
+            ticks_remaining: LEVEL_COMPLETE_TICKS,
+        };
+    }
+}
@@ -0,0 +1,808 @@
+use crate::game::{
+    campaign::LEVEL_COMPLETE_TICKS,
+    font::{BitmapFont, TextAlign},
+    high_score::{HighScoreEntry, HighScoreTable},
+    level::{LevelState, LoadLevelEvent},
+    render::RenderState,
+    settings::{
+        Settings, SETTINGS_RENDER_SCALE_MAX, SETTINGS_RENDER_SCALE_MIN, SETTINGS_RENDER_SCALE_STEP,
+        SETTINGS_VOLUME_STEP,
+    },
+    ui::{Ui, UiFocus, UiInput, UiPainter, UiRect},
+};
+use gfx::{
+    color::*,
+    input::{self, GamepadInput, InputAction, InputBindings, InputState},
+    renderer::Transparency,
+};
+use specs::prelude::*;
+
+pub const HIGH_SCORE_INITIALS_LENGTH: usize = 3;
+
+// Top of the title screen's menu, below the title
+const TITLE_MENU_Y: f32 = 48.0;
+
+/// The top level state of the game, which decides which systems run each tick and which UI is shown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FlowState {
+    Title,
+    Playing,
+    Paused,
+
+    // The options screen, opened from the pause menu or the title screen
+    Options {
+        paused: bool,
+    },
+
+    // The controls screen, opened from the options screen. While `waiting` for an action, the next key pressed is
+    // bound to it.
+    Controls {
+        paused: bool,
+        waiting: Option<InputAction>,
+    },
+    LevelComplete {
+        ticks_remaining: u32,
+    },
+    GameOver {
+        campaign_complete: bool,
+    },
+    EnterInitials {
+        initials: [u8; HIGH_SCORE_INITIALS_LENGTH],
+        cursor: usize,
+    },
+}
+
+impl Default for FlowState {
+    fn default() -> Self {
+        FlowState::Title
+    }
+}
+
+impl FlowState {
+    /// Whether gameplay and physics systems should run this tick.
+    pub fn is_simulating(&self) -> bool {
+        *self == FlowState::Playing
+    }
+
+    /// Whether the level (and the HUD) should be drawn behind the UI for this state.
+    pub fn shows_field(&self) -> bool {
+        match self {
+            FlowState::Title | FlowState::EnterInitials { .. } => false,
+            FlowState::Options { paused } | FlowState::Controls { paused, .. } => *paused,
+            _ => true,
+        }
+    }
+}
+
+enum TitleAction {
+    Start,
+    Options,
+}
+
+/// The title screen's menu, a list of buttons below the title.
+fn title_menu(ui: &mut Ui, screen_width: u32) -> Option<TitleAction> {
+    let width = 100.0;
+    ui.list(
+        (screen_width as f32 - width) / 2.0,
+        TITLE_MENU_Y,
+        width,
+        TextAlign::Center,
+    );
+
+    let start = ui.button("Start game");
+    ui.space(2.0);
+    let options = ui.button("Options");
+
+    if start {
+        Some(TitleAction::Start)
+    } else if options {
+        Some(TitleAction::Options)
+    } else {
+        None
+    }
+}
+
+enum PauseAction {
+    Resume,
+    Options,
+    Quit,
+}
+
+/// The pause menu, which is handled during the tick and drawn the same way, see `Ui`.
+fn pause_menu(ui: &mut Ui, screen_width: u32, screen_height: u32) -> Option<PauseAction> {
+    let (width, height) = (140.0, 66.0);
+    ui.panel(UiRect {
+        x: (screen_width as f32 - width) / 2.0,
+        y: (screen_height as f32 - height) / 2.0,
+        w: width,
+        h: height,
+    });
+
+    ui.label_colored("Paused", COLOR_GREEN);
+    ui.space(6.0);
+    let resume = ui.button("Resume");
+    ui.space(2.0);
+    let options = ui.button("Options");
+    ui.space(2.0);
+    let quit = ui.button("Quit to title");
+
+    if resume {
+        Some(PauseAction::Resume)
+    } else if options {
+        Some(PauseAction::Options)
+    } else if quit {
+        Some(PauseAction::Quit)
+    } else {
+        None
+    }
+}
+
+enum OptionsAction {
+    Controls,
+    Back,
+}
+
+/// The options screen, which changes the settings as it's used.
+fn options_menu(
+    ui: &mut Ui,
+    settings: &mut Settings,
+    screen_width: u32,
+    screen_height: u32,
+) -> Option<OptionsAction> {
+    let (width, height) = (200.0, 150.0);
+    ui.panel(UiRect {
+        x: (screen_width as f32 - width) / 2.0,
+        y: (screen_height as f32 - height) / 2.0,
+        w: width,
+        h: height,
+    });
+
+    ui.label_colored("Options", COLOR_GREEN);
+    ui.space(6.0);
+    ui.slider(
+        &format!("Window scale {}x", settings.render_scale),
+        &mut settings.render_scale,
+        SETTINGS_RENDER_SCALE_MIN,
+        SETTINGS_RENDER_SCALE_MAX,
+        SETTINGS_RENDER_SCALE_STEP,
+    );
+    ui.checkbox("Fullscreen", &mut settings.fullscreen);
+    ui.checkbox("Vsync", &mut settings.vsync);
+
+    ui.slider(
+        &format!("Volume {:.0}%", settings.master_volume * 100.0),
+        &mut settings.master_volume,
+        0.0,
+        1.0,
+        SETTINGS_VOLUME_STEP,
+    );
+    ui.slider(
+        &format!("Music {:.0}%", settings.music_volume * 100.0),
+        &mut settings.music_volume,
+        0.0,
+        1.0,
+        SETTINGS_VOLUME_STEP,
+    );
+    ui.slider(
+        &format!("Effects {:.0}%", settings.sfx_volume * 100.0),
+        &mut settings.sfx_volume,
+        0.0,
+        1.0,
+        SETTINGS_VOLUME_STEP,
+    );
+
+    ui.checkbox("Show FPS", &mut settings.show_fps);
+    ui.checkbox("Screen shake", &mut settings.screen_shake);
+    ui.space(2.0);
+    let controls = ui.button("Controls");
+    ui.space(2.0);
+    let back = ui.button("Back");
+
+    if controls {
+        Some(OptionsAction::Controls)
+    } else if back {
+        Some(OptionsAction::Back)
+    } else {
+        None
+    }
+}
+
+enum ControlsAction {
+    Rebind(InputAction),
+    Reset,
+    Back,
+}
+
+/// The controls screen, which lists the keys and gamepad inputs bound to each action. Picking an action waits for a
+/// key or gamepad input to add to it, or remove from it if it's already bound.
+fn controls_menu(
+    ui: &mut Ui,
+    bindings: &InputBindings,
+    waiting: Option<InputAction>,
+    screen_width: u32,
+    screen_height: u32,
+) -> Option<ControlsAction> {
+    let (width, height) = (280.0, 150.0);
+    ui.panel(UiRect {
+        x: (screen_width as f32 - width) / 2.0,
+        y: (screen_height as f32 - height) / 2.0,
+        w: width,
+        h: height,
+    });
+
+    ui.label_colored("Controls", COLOR_GREEN);
+    ui.space(6.0);
+
+    let mut chosen = None;
+    for action in InputAction::ALL.iter() {
+        let bound = if waiting == Some(*action) {
+            "Press a key or button".to_owned()
+        } else {
+            let key_names = bindings
+                .keys(*action)
+                .iter()
+                .filter_map(|key| input::key_name(*key))
+                .map(|name| name.to_owned());
+            let gamepad_names = bindings
+                .gamepad_inputs(*action)
+                .iter()
+                .map(|gamepad_input| gamepad_input.name());
+            let names: Vec<String> = key_names.chain(gamepad_names).collect();
+            names.join(", ")
+        };
+
+        if ui.button(&format!("{}: {}", action_label(*action), bound)) {
+            chosen = Some(ControlsAction::Rebind(*action));
+        }
+    }
+
+    ui.space(2.0);
+    if ui.button("Reset to defaults") {
+        chosen = Some(ControlsAction::Reset);
+    }
+    ui.space(2.0);
+    if ui.button("Back") {
+        chosen = Some(ControlsAction::Back);
+    }
+
+    chosen
+}
+
+/// The name of an action shown to the player.
+fn action_label(action: InputAction) -> &'static str {
+    match action {
+        InputAction::MoveLeft => "Move left",
+        InputAction::MoveRight => "Move right",
+        InputAction::Launch => "Launch",
+        InputAction::Pause => "Pause",
+        InputAction::Confirm => "Confirm",
+        InputAction::Back => "Back",
+        InputAction::MenuUp => "Menu up",
+        InputAction::MenuDown => "Menu down",
+    }
+}
+
+/// What to tell the player to press for an action: its first bound key, or the action itself if it has none.
+fn prompt_key_name(bindings: &InputBindings, action: InputAction) -> &'static str {
+    bindings
+        .keys(action)
+        .iter()
+        .find_map(|key| input::key_name(*key))
+        .unwrap_or_else(|| action_label(action))
+}
+
+/// Handles all transitions between flow states. Runs every tick, before any gameplay systems.
+pub struct FlowSystem;
+
+impl<'a> System<'a> for FlowSystem {
+    type SystemData = (
+        Write<'a, FlowState>,
+        Write<'a, LevelState>,
+        Write<'a, HighScoreTable>,
+        Write<'a, UiFocus>,
+        Write<'a, Settings>,
+        Read<'a, InputState>,
+    );
+
+    fn run(
+        &mut self,
+        (mut flow, mut level, mut high_scores, mut focus, mut settings, input): Self::SystemData,
+    ) {
+        let next_state = match *flow {
+            FlowState::Title => {
+                let mut ui = Ui::new(&mut focus, UiInput::from_input(&input), None);
+                let action = title_menu(&mut ui, level.level_width);
+                ui.finish();
+
+                match action {
+                    Some(TitleAction::Start) => {
+                        let event = LoadLevelEvent::new_game(level.campaign.first_level_id());
+                        level.load_level_event = Some(event);
+                        FlowState::Playing
+                    }
+                    Some(TitleAction::Options) => FlowState::Options { paused: false },
+                    None => return,
+                }
+            }
+            FlowState::Playing => {
+                // Wait for pending level loads, so the lives of the previous game aren't mistaken for a game over
+                if level.load_level_event.is_some() {
+                    return;
+                }
+
+                if level.lives == 0 {
+                    FlowState::GameOver {
+                        campaign_complete: false,
+                    }
+                } else if input.is_action_pressed(InputAction::Pause) {
+                    FlowState::Paused
+                } else {
+                    return;
+                }
+            }
+            FlowState::Paused => {
+                let mut ui = Ui::new(&mut focus, UiInput::from_input(&input), None);
+                let action = pause_menu(&mut ui, level.level_width, level.level_height);
+                ui.finish();
+
+                match action {
+                    Some(PauseAction::Resume) => FlowState::Playing,
+                    Some(PauseAction::Options) => FlowState::Options { paused: true },
+                    Some(PauseAction::Quit) => FlowState::Title,
+                    None if input.is_action_pressed(InputAction::Pause)
+                        || input.is_action_pressed(InputAction::Back) =>
+                    {
+                        FlowState::Playing
+                    }
+                    None => return,
+                }
+            }
+            FlowState::Options { paused } => {
+                let mut ui = Ui::new(&mut focus, UiInput::from_input(&input), None);
+                let action = options_menu(
+                    &mut ui,
+                    &mut settings,
+                    level.level_width,
+                    level.level_height,
+                );
+                ui.finish();
+
+                match action {
+                    Some(OptionsAction::Controls) => FlowState::Controls {
+                        paused,
+                        waiting: None,
+                    },
+                    Some(OptionsAction::Back) => close_options(&settings, paused),
+                    None if input.is_action_pressed(InputAction::Back) => {
+                        close_options(&settings, paused)
+                    }
+                    None => return,
+                }
+            }
+            FlowState::Controls {
+                paused,
+                waiting: Some(action),
+            } => {
+                // Back cancels. Any other key or gamepad input is added to the action's bindings, or removed if it's
+                // already one of them. Keys have to have a name to be saved.
+                if input.is_action_pressed(InputAction::Back) {
+                    FlowState::Controls {
+                        paused,
+                        waiting: None,
+                    }
+                } else if let Some(key) = input
+                    .pressed_keys()
+                    .find(|key| input::key_name(*key).is_some())
+                {
+                    let bound = settings.key_bindings.toggle_key(action, key);
+                    println!(
+                        "[FlowSystem] {} {:?} for {:?}",
+                        if bound { "Bound" } else { "Unbound" },
+                        key,
+                        action
+                    );
+                    FlowState::Controls {
+                        paused,
+                        waiting: None,
+                    }
+                } else if let Some(gamepad_input) = GamepadInput::all()
+                    .find(|gamepad_input| input.is_gamepad_input_pressed(*gamepad_input))
+                {
+                    let bound = settings
+                        .key_bindings
+                        .toggle_gamepad_input(action, gamepad_input);
+                    println!(
+                        "[FlowSystem] {} {:?} for {:?}",
+                        if bound { "Bound" } else { "Unbound" },
+                        gamepad_input,
+                        action
+                    );
+                    FlowState::Controls {
+                        paused,
+                        waiting: None,
+                    }
+                } else {
+                    return;
+                }
+            }
+            FlowState::Controls {
+                paused,
+                waiting: None,
+            } => {
+                let mut ui = Ui::new(&mut focus, UiInput::from_input(&input), None);
+                let action = controls_menu(
+                    &mut ui,
+                    &settings.key_bindings,
+                    None,
+                    level.level_width,
+                    level.level_height,
+                );
+                ui.finish();
+
+                match action {
+                    Some(ControlsAction::Rebind(action)) => FlowState::Controls {
+                        paused,
+                        waiting: Some(action),
+                    },
+                    Some(ControlsAction::Reset) => {
+                        settings.key_bindings = InputBindings::default();
+                        return;
+                    }
+                    Some(ControlsAction::Back) => FlowState::Options { paused },
+                    None if input.is_action_pressed(InputAction::Back) => {
+                        FlowState::Options { paused }
+                    }
+                    None => return,
+                }
+            }
+            FlowState::LevelComplete { ticks_remaining } => {
+                if ticks_remaining > 0 && !input.is_action_pressed(InputAction::Confirm) {
+                    FlowState::LevelComplete {
+                        ticks_remaining: ticks_remaining - 1,
+                    }
+                } else {
+                    let event = level
+                        .campaign
+                        .next_level_id()
+                        .map(LoadLevelEvent::next_level);
+                    match event {
+                        Some(event) => {
+                            level.load_level_event = Some(event);
+                            FlowState::Playing
+                        }
+                        None => {
+                            println!("Campaign complete! Final score: {}", level.score);
+                            FlowState::GameOver {
+                                campaign_complete: true,
+                            }
+                        }
+                    }
+                }
+            }
+            FlowState::GameOver { .. } => {
+                if !input.is_action_pressed(InputAction::Confirm) {
+                    return;
+                }
+
+                if high_scores.qualifies(level.score) {
+                    FlowState::EnterInitials {
+                        initials: [b'A'; HIGH_SCORE_INITIALS_LENGTH],
+                        cursor: 0,
+                    }
+                } else {
+                    FlowState::Title
+                }
+            }
+            FlowState::EnterInitials {
+                mut initials,
+                mut cursor,
+            } => {
+                if input.is_action_pressed(InputAction::Confirm) {
+                    let initials = String::from_utf8_lossy(&initials).into_owned();
+                    let entry = HighScoreEntry::new(&initials, level.score, &level.level_name);
+                    if let Some(rank) = high_scores.insert(entry) {
+                        println!(
+                            "[FlowSystem] {} placed #{} with {}",
+                            initials,
+                            rank + 1,
+                            level.score
+                        );
+                        if let Err(e) = high_scores.save() {
+                            eprintln!("Failed to save high scores! {}", e);
+                        }
+                    }
+
+                    FlowState::Title
+                } else {
+                    if input.is_action_pressed(InputAction::MenuUp) {
+                        initials[cursor] = if initials[cursor] == b'Z' {
+                            b'A'
+                        } else {
+                            initials[cursor] + 1
+                        };
+                    } else if input.is_action_pressed(InputAction::MenuDown) {
+                        initials[cursor] = if initials[cursor] == b'A' {
+                            b'Z'
+                        } else {
+                            initials[cursor] - 1
+                        };
+                    }
+
+                    if input.is_action_pressed(InputAction::MoveLeft)
+                        || input.is_action_pressed(InputAction::Back)
+                    {
+                        cursor = cursor.saturating_sub(1);
+                    } else if input.is_action_pressed(InputAction::MoveRight) {
+                        cursor = (cursor + 1).min(HIGH_SCORE_INITIALS_LENGTH - 1);
+                    }
+
+                    FlowState::EnterInitials { initials, cursor }
+                }
+            }
+        };
+
+        if next_state != *flow {
+            println!("[FlowSystem] {:?} -> {:?}", *flow, next_state);
+        }
+
+        // Each menu starts with its first row focused. Waiting for a key on the controls screen keeps its focus.
+        if std::mem::discriminant(&next_state) != std::mem::discriminant(&*flow) {
+            *focus = UiFocus::default();
+        }
+
+        *flow = next_state;
+    }
+}
+
+/// Save the settings as the options screen is closed, and go back to where it was opened from.
+fn close_options(settings: &Settings, paused: bool) -> FlowState {
+    if let Err(e) = settings.save() {
+        eprintln!("Failed to save settings! {}", e);
+    }
+
+    if paused {
+        FlowState::Paused
+    } else {
+        FlowState::Title
+    }
+}
+
+/// Draw the UI for the current flow state on top of everything else.
+pub fn draw_ui(world: &World, painter: UiPainter, screen_width: u32, screen_height: u32) {
+    let flow = *world.read_resource::<FlowState>();
+    let level = world.read_resource::<LevelState>();
+    let high_scores = world.read_resource::<HighScoreTable>();
+    let mut focus = *world.read_resource::<UiFocus>();
+
+    let UiPainter {
+        render,
+        font,
+        atlas,
+    } = painter;
+    render.bind_layer(0);
+    render.bind_transparency(Transparency::Transparent);
+
+    let bottom_line_y = screen_height as f32 - 10.0;
+    let bottom_title_y = screen_height as f32 - 22.0;
+
+    match flow {
+        FlowState::Title => {
+            let title_y = 20.0;
+            render.bind_color(COLOR_GREEN);
+            draw_centered_text(render, font, screen_width, title_y, 1.5, "Brickbonker");
+
+            let painter = UiPainter {
+                render: &mut *render,
+                font,
+                atlas,
+            };
+            let mut ui = Ui::new(&mut focus, UiInput::default(), Some(painter));
+            title_menu(&mut ui, screen_width);
+            render.bind_layer(0);
+            render.bind_transparency(Transparency::Transparent);
+
+            // High score table, below the menu
+            let table_y = TITLE_MENU_Y + 36.0;
+            render.bind_color(COLOR_GREEN);
+            draw_centered_text(render, font, screen_width, table_y, 0.5, "High Scores");
+
+            render.bind_color(COLOR_WHITE);
+            if high_scores.entries.is_empty() {
+                draw_centered_text(
+                    render,
+                    font,
+                    screen_width,
+                    table_y + 14.0,
+                    0.5,
+                    "No high scores yet.",
+                );
+            }
+
+            for (i, entry) in high_scores.entries.iter().enumerate() {
+                let level_reached: String = entry.level_reached.chars().take(12).collect();
+                let msg = format!(
+                    "{:>2}. {:<3} {:>7}  {:<12} {}",
+                    i + 1,
+                    entry.initials,
+                    entry.score,
+                    level_reached,
+                    entry.date
+                );
+                draw_centered_text(
+                    render,
+                    font,
+                    screen_width,
+                    table_y + 14.0 + i as f32 * 10.0,
+                    0.5,
+                    &msg,
+                );
+            }
+        }
+        FlowState::Playing => {}
+        FlowState::Paused => {
+            let painter = UiPainter {
+                render,
+                font,
+                atlas,
+            };
+            let mut ui = Ui::new(&mut focus, UiInput::default(), Some(painter));
+            pause_menu(&mut ui, screen_width, screen_height);
+        }
+        FlowState::Options { .. } => {
+            // Nothing is changed while drawing, so a copy of the settings will do
+            let mut settings = world.read_resource::<Settings>().clone();
+            let painter = UiPainter {
+                render,
+                font,
+                atlas,
+            };
+            let mut ui = Ui::new(&mut focus, UiInput::default(), Some(painter));
+            options_menu(&mut ui, &mut settings, screen_width, screen_height);
+        }
+        FlowState::Controls { waiting, .. } => {
+            let settings = world.read_resource::<Settings>();
+            let painter = UiPainter {
+                render,
+                font,
+                atlas,
+            };
+            let mut ui = Ui::new(&mut focus, UiInput::default(), Some(painter));
+            controls_menu(
+                &mut ui,
+                &settings.key_bindings,
+                waiting,
+                screen_width,
+                screen_height,
+            );
+        }
+        FlowState::LevelComplete { .. } => {
+            // Level Complete text
+            render.bind_color(COLOR_GREEN);
+            render.text(
+                font,
+                2.0,
+                bottom_title_y,
+                0.75,
+                &format!("{} Complete!", level.level_name),
+            );
+
+            // Continue text
+            let settings = world.read_resource::<Settings>();
+            let confirm_key = prompt_key_name(&settings.key_bindings, InputAction::Confirm);
+            render.bind_color(COLOR_WHITE);
+            render.text(
+                font,
+                2.0,
+                bottom_line_y,
+                0.5,
+                &format!("Press '{}' to continue.", confirm_key),
+            );
+        }
+        FlowState::GameOver { campaign_complete } => {
+            // Game Over / Victory text
+            let (color, msg) = if campaign_complete {
+                (
+                    COLOR_GREEN,
+                    format!("Victory! Final score: {}", level.score),
+                )
+            } else {
+                (COLOR_RED, "Game Over!".to_owned())
+            };
+            render.bind_color(color);
+            render.text(font, 2.0, bottom_title_y, 0.75, &msg);
+
+            // Continue text
+            let settings = world.read_resource::<Settings>();
+            let confirm_key = prompt_key_name(&settings.key_bindings, InputAction::Confirm);
+            render.bind_color(COLOR_WHITE);
+            render.text(
+                font,
+                2.0,
+                bottom_line_y,
+                0.5,
+                &format!("Press '{}' to continue.", confirm_key),
+            );
+        }
+        FlowState::EnterInitials { initials, cursor } => {
+            let heading_y = screen_height as f32 / 3.0;
+            render.bind_color(COLOR_GREEN);
+            draw_centered_text(
+                render,
+                font,
+                screen_width,
+                heading_y,
+                1.0,
+                "New High Score!",
+            );
+
+            render.bind_color(COLOR_WHITE);
+            draw_centered_text(
+                render,
+                font,
+                screen_width,
+                heading_y + 20.0,
+                0.5,
+                &format!("Score: {}  Reached: {}", level.score, level.level_name),
+            );
+
+            // Initials, with a marker under the letter being edited. Each letter is centered in a cell twice as wide
+            // as a `W`.
+            let initials_scale = 1.0;
+            let letter_width = font.measure("W", initials_scale) * 2.0;
+            let initials_x =
+                (screen_width as f32 - letter_width * HIGH_SCORE_INITIALS_LENGTH as f32) / 2.0;
+            let initials_y = heading_y + 36.0;
+            for (i, letter) in initials.iter().enumerate() {
+                let letter_x = initials_x + i as f32 * letter_width + letter_width / 2.0;
+                render.bind_color(if i == cursor {
+                    COLOR_GREEN
+                } else {
+                    COLOR_WHITE
+                });
+                render.text_aligned(
+                    font,
+                    letter_x,
+                    initials_y,
+                    initials_scale,
+                    TextAlign::Center,
+                    &(*letter as char).to_string(),
+                );
+
+                if i == cursor {
+                    render.text_aligned(
+                        font,
+                        letter_x,
+                        initials_y + 14.0,
+                        initials_scale,
+                        TextAlign::Center,
+                        "^",
+                    );
+                }
+            }
+
+            let settings = world.read_resource::<Settings>();
+            let confirm_key = prompt_key_name(&settings.key_bindings, InputAction::Confirm);
+            render.bind_color(COLOR_WHITE);
+            draw_centered_text(
+                render,
+                font,
+                screen_width,
+                bottom_line_y,
+                0.5,
+                &format!("Choose your initials and press '{}'.", confirm_key),
+            );
+        }
+    }
+}
+
+fn draw_centered_text(
+    render: &mut RenderState,
+    font: &BitmapFont,
+    screen_width: u32,
+    y: f32,
+    scale: f32,
+    text: &str,
+) {
+    let center_x = screen_width as f32 / 2.0;
+    render.text_aligned(font, center_x, y, scale, TextAlign::Center, text);
+}
@@ -0,0 +1,348 @@
+use gfx::{renderer::TextureId, sprite::SpriteRegion};
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+// Drawn in place of characters the font doesn't have
+pub const FONT_FALLBACK_CHAR: char = '?';
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(PathBuf, io::Error),
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FontError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            FontError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+/// Where a character is on the font texture, and how it's placed. Offsets and advances are in font pixels, before
+/// the text is scaled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glyph {
+    pub region: SpriteRegion,
+    pub x_offset: i32,
+    pub y_offset: i32,
+
+    // How far to move along after drawing the glyph
+    pub x_advance: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+/// A glyph positioned by `BitmapFont::layout`, ready to be drawn as a sprite.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlacedGlyph {
+    pub x: f32,
+    pub y: f32,
+    pub region: SpriteRegion,
+}
+
+/// A variable-width font, drawn from the glyphs on a single texture page. Loaded from a BMFont text descriptor.
+#[derive(Clone, Debug, Default)]
+pub struct BitmapFont {
+    tex_id: TextureId,
+    page_path: PathBuf,
+    line_height: u32,
+    glyphs: HashMap<char, Glyph>,
+    kernings: HashMap<(char, char), i32>,
+}
+
+impl BitmapFont {
+    pub fn load(path: &Path) -> Result<Self, FontError> {
+        let source = fs::read_to_string(path).map_err(|e| FontError::Io(path.to_owned(), e))?;
+        BitmapFont::parse(path, &source)
+    }
+
+    /// Parse a BMFont text descriptor. The `common` line sets the line height, the `page` line names the texture
+    /// (relative to the descriptor), and each `char` and `kerning` line adds a glyph or a kerning pair. Other lines
+    /// are ignored.
+    pub fn parse(path: &Path, source: &str) -> Result<Self, FontError> {
+        let mut font = BitmapFont::default();
+        let mut found_page = false;
+
+        for (i, line) in source.lines().enumerate() {
+            let parse_error = |message: String| FontError::Parse {
+                path: path.to_owned(),
+                line: i + 1,
+                message,
+            };
+
+            let (tag, fields) = match parse_fields(line).map_err(parse_error)? {
+                Some(parsed) => parsed,
+                None => continue,
+            };
+
+            match tag {
+                "common" => {
+                    font.line_height = field(&fields, tag, "lineHeight").map_err(parse_error)?;
+                }
+                "page" => {
+                    let id: u32 = field(&fields, tag, "id").map_err(parse_error)?;
+                    if id != 0 || found_page {
+                        return Err(parse_error(
+                            "Only fonts with a single page are supported".to_owned(),
+                        ));
+                    }
+
+                    let file: String = field(&fields, tag, "file").map_err(parse_error)?;
+                    font.page_path = path.parent().unwrap_or_else(|| Path::new("")).join(file);
+                    found_page = true;
+                }
+                "char" => {
+                    let c = char_field(&fields, tag, "id").map_err(parse_error)?;
+                    let glyph = Glyph {
+                        region: SpriteRegion {
+                            x: field(&fields, tag, "x").map_err(parse_error)?,
+                            y: field(&fields, tag, "y").map_err(parse_error)?,
+                            w: field(&fields, tag, "width").map_err(parse_error)?,
+                            h: field(&fields, tag, "height").map_err(parse_error)?,
+                        },
+                        x_offset: field(&fields, tag, "xoffset").map_err(parse_error)?,
+                        y_offset: field(&fields, tag, "yoffset").map_err(parse_error)?,
+                        x_advance: field(&fields, tag, "xadvance").map_err(parse_error)?,
+                    };
+                    font.glyphs.insert(c, glyph);
+                }
+                "kerning" => {
+                    let first = char_field(&fields, tag, "first").map_err(parse_error)?;
+                    let second = char_field(&fields, tag, "second").map_err(parse_error)?;
+                    let amount = field(&fields, tag, "amount").map_err(parse_error)?;
+                    font.kernings.insert((first, second), amount);
+                }
+                _ => {}
+            }
+        }
+
+        if !found_page {
+            return Err(FontError::Parse {
+                path: path.to_owned(),
+                line: source.lines().count(),
+                message: "Expected a `page` line".to_owned(),
+            });
+        }
+
+        Ok(font)
+    }
+
+    pub fn tex_id(&self) -> TextureId {
+        self.tex_id
+    }
+
+    pub fn set_tex_id(&mut self, tex_id: TextureId) {
+        self.tex_id = tex_id;
+    }
+
+    /// The texture the glyphs are on.
+    pub fn page_path(&self) -> &Path {
+        &self.page_path
+    }
+
+    pub fn line_height(&self) -> u32 {
+        self.line_height
+    }
+
+    /// The glyph for a character, or the fallback glyph if the font doesn't have it.
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs
+            .get(&c)
+            .or_else(|| self.glyphs.get(&FONT_FALLBACK_CHAR))
+    }
+
+    /// How much closer (or further apart, if positive) two characters are drawn when one follows the other.
+    pub fn kerning(&self, first: char, second: char) -> i32 {
+        self.kernings.get(&(first, second)).copied().unwrap_or(0)
+    }
+
+    /// The width of the widest line of some text.
+    pub fn measure(&self, text: &str, scale: f32) -> f32 {
+        text.split('\n')
+            .map(|line| self.line_width(line))
+            .max()
+            .unwrap_or(0) as f32
+            * scale
+    }
+
+    /// Break text into lines that fit within a width, between words where possible. Existing line breaks are kept.
+    pub fn wrap(&self, text: &str, max_width: f32, scale: f32) -> Vec<String> {
+        let mut lines = Vec::new();
+        for paragraph in text.split('\n') {
+            let mut line = String::new();
+            for word in paragraph.split_whitespace() {
+                let joined = format!("{} {}", line, word);
+                if line.is_empty() {
+                    line = word.to_owned();
+                } else if self.measure(&joined, scale) <= max_width {
+                    line = joined;
+                } else {
+                    lines.push(std::mem::replace(&mut line, word.to_owned()));
+                }
+
+                // A word that's too wide by itself is broken wherever it has to be
+                while self.measure(&line, scale) > max_width {
+                    let boundaries: Vec<usize> =
+                        line.char_indices().skip(1).map(|(i, _)| i).collect();
+                    let split = boundaries
+                        .iter()
+                        .rev()
+                        .find(|i| self.measure(&line[..**i], scale) <= max_width)
+                        .or_else(|| boundaries.first());
+                    match split {
+                        Some(split) => {
+                            let rest = line.split_off(*split);
+                            lines.push(std::mem::replace(&mut line, rest));
+                        }
+                        None => break,
+                    }
+                }
+            }
+
+            lines.push(line);
+        }
+
+        lines
+    }
+
+    /// Place each glyph of some text. Lines are separated by `\n`, and each is aligned to `x` on its own: starting
+    /// at it, centered on it or ending at it.
+    pub fn layout(
+        &self,
+        text: &str,
+        x: f32,
+        y: f32,
+        scale: f32,
+        align: TextAlign,
+    ) -> Vec<PlacedGlyph> {
+        let mut placed = Vec::new();
+        for (i, line) in text.split('\n').enumerate() {
+            let width = self.line_width(line) as f32 * scale;
+            let line_x = match align {
+                TextAlign::Left => x,
+                TextAlign::Center => x - width / 2.0,
+                TextAlign::Right => x - width,
+            };
+            let line_y = y + (i as u32 * self.line_height) as f32 * scale;
+
+            let mut pen = 0;
+            let mut previous = None;
+            for c in line.chars() {
+                let glyph = match self.glyph(c) {
+                    Some(glyph) => glyph,
+                    None => continue,
+                };
+                if let Some(previous) = previous {
+                    pen += self.kerning(previous, c);
+                }
+
+                // Glyphs with nothing to draw (e.g. spaces) still move the pen along
+                if glyph.region.w > 0 && glyph.region.h > 0 {
+                    placed.push(PlacedGlyph {
+                        x: line_x + (pen + glyph.x_offset) as f32 * scale,
+                        y: line_y + glyph.y_offset as f32 * scale,
+                        region: glyph.region,
+                    });
+                }
+
+                pen += glyph.x_advance;
+                previous = Some(c);
+            }
+        }
+
+        placed
+    }
+
+    /// The width of a single line, in font pixels.
+    fn line_width(&self, line: &str) -> i32 {
+        let mut width = 0;
+        let mut previous = None;
+        for c in line.chars() {
+            if let Some(glyph) = self.glyph(c) {
+                if let Some(previous) = previous {
+                    width += self.kerning(previous, c);
+                }
+                width += glyph.x_advance;
+                previous = Some(c);
+            }
+        }
+
+        width.max(0)
+    }
+}
+
+/// Split a descriptor line into its tag and `key=value` fields, where values can be quoted. Returns `None` for
+/// blank lines.
+fn parse_fields(line: &str) -> Result<Option<(&str, HashMap<&str, &str>)>, String> {
+    let line = line.trim();
+    let (tag, mut rest) = match line.find(char::is_whitespace) {
+        Some(end) => (&line[..end], line[end..].trim_start()),
+        None => (line, ""),
+    };
+    if tag.is_empty() {
+        return Ok(None);
+    }
+
+    let mut fields = HashMap::new();
+    while !rest.is_empty() {
+        let separator = rest
+            .find('=')
+            .ok_or_else(|| format!("Expected `key=value`, found `{}`", rest))?;
+        let key = &rest[..separator];
+        rest = &rest[separator + 1..];
+
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted
+                .find('"')
+                .ok_or_else(|| format!("Missing closing quote for `{}`", key))?;
+            rest = &quoted[end + 1..];
+            &quoted[..end]
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or_else(|| rest.len());
+            let value = &rest[..end];
+            rest = &rest[end..];
+            value
+        };
+
+        fields.insert(key, value);
+        rest = rest.trim_start();
+    }
+
+    Ok(Some((tag, fields)))
+}
+
+fn field<T: FromStr>(fields: &HashMap<&str, &str>, tag: &str, key: &str) -> Result<T, String> {
+    let value = fields
+        .get(key)
+        .ok_or_else(|| format!("Expected `{}` in `{}`", key, tag))?;
+
+    value
+        .parse::<T>()
+        .map_err(|_| format!("Invalid `{}` in `{}`: `{}`", key, tag, value))
+}
+
+/// A field holding a character as its Unicode code point.
+fn char_field(fields: &HashMap<&str, &str>, tag: &str, key: &str) -> Result<char, String> {
+    let code: u32 = field(fields, tag, key)?;
+    std::char::from_u32(code)
+        .ok_or_else(|| format!("`{}` in `{}` isn't a character: {}", key, tag, code))
+}
@@ -0,0 +1,233 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const HIGH_SCORE_TABLE_SIZE: usize = 10;
+pub const HIGH_SCORE_DATA_DIRECTORY: &str = "brickbonker";
+pub const HIGH_SCORE_FILE_NAME: &str = "high-scores.txt";
+
+// The first line of a high score file, so future format changes can be detected
+const HIGH_SCORE_FILE_HEADER: &str = "brickbonker-high-scores 1";
+const HIGH_SCORE_FIELD_SEPARATOR: char = '\t';
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HighScoreEntry {
+    pub initials: String,
+    pub score: u32,
+    pub level_reached: String,
+    pub date: String,
+}
+
+impl HighScoreEntry {
+    pub fn new(initials: &str, score: u32, level_reached: &str) -> Self {
+        HighScoreEntry {
+            initials: initials.to_owned(),
+            score,
+            level_reached: level_reached.to_owned(),
+            date: current_date(),
+        }
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "{}{sep}{}{sep}{}{sep}{}",
+            self.score,
+            sanitize_field(&self.initials),
+            sanitize_field(&self.level_reached),
+            sanitize_field(&self.date),
+            sep = HIGH_SCORE_FIELD_SEPARATOR
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.split(HIGH_SCORE_FIELD_SEPARATOR);
+        let score = fields.next()?.trim().parse::<u32>().ok()?;
+        let initials = fields.next()?.trim();
+        let level_reached = fields.next()?.trim();
+        let date = fields.next()?.trim();
+        if fields.next().is_some() || initials.is_empty() {
+            return None;
+        }
+
+        Some(HighScoreEntry {
+            initials: initials.to_owned(),
+            score,
+            level_reached: level_reached.to_owned(),
+            date: date.to_owned(),
+        })
+    }
+}
+
+/// The top scores, highest first. Saved to `path` whenever a new score is added.
+#[derive(Default)]
+pub struct HighScoreTable {
+    pub entries: Vec<HighScoreEntry>,
+    pub path: Option<PathBuf>,
+}
+
+impl HighScoreTable {
+    /// Load the high score table from the default location in the user's data directory.
+    pub fn load_default() -> Self {
+        match default_high_score_path() {
+            Some(path) => HighScoreTable::load(&path),
+            None => {
+                eprintln!("Failed to find a data directory! High scores won't be saved.");
+                HighScoreTable::default()
+            }
+        }
+    }
+
+    /// Create an unsaved table with placeholder entries for the given scores, so a replay decides whether scores
+    /// qualify the same way as when it was recorded.
+    pub fn from_scores(scores: &[u32]) -> Self {
+        let mut table = HighScoreTable {
+            entries: scores
+                .iter()
+                .map(|score| HighScoreEntry {
+                    initials: "---".to_owned(),
+                    score: *score,
+                    level_reached: String::new(),
+                    date: String::new(),
+                })
+                .collect(),
+            path: None,
+        };
+
+        table.sort_and_truncate();
+        table
+    }
+
+    /// Load the high score table from a file. A missing file is an empty table, and lines that
+    /// can't be read are skipped, so a damaged file loses as few scores as possible.
+    pub fn load(path: &Path) -> Self {
+        let mut table = HighScoreTable {
+            entries: Vec::new(),
+            path: Some(path.to_owned()),
+        };
+
+        let source = match fs::read(path) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(e) => {
+                if e.kind() != io::ErrorKind::NotFound {
+                    eprintln!("Failed to read high scores {}! {}", path.display(), e);
+                }
+
+                return table;
+            }
+        };
+
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line == HIGH_SCORE_FILE_HEADER {
+                continue;
+            }
+
+            match HighScoreEntry::from_line(line) {
+                Some(entry) => table.entries.push(entry),
+                None => eprintln!(
+                    "Skipping unreadable high score on line {} of {}",
+                    i + 1,
+                    path.display()
+                ),
+            }
+        }
+
+        table.sort_and_truncate();
+        table
+    }
+
+    /// Write the table to a temporary file first, so a crash mid-save can't destroy the old scores.
+    pub fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let temp_path = path.with_extension("tmp");
+        {
+            let mut file = fs::File::create(&temp_path)?;
+            writeln!(file, "{}", HIGH_SCORE_FILE_HEADER)?;
+            for entry in self.entries.iter() {
+                writeln!(file, "{}", entry.to_line())?;
+            }
+
+            file.sync_all()?;
+        }
+
+        fs::rename(&temp_path, path)
+    }
+
+    pub fn qualifies(&self, score: u32) -> bool {
+        if score == 0 {
+            return false;
+        }
+
+        self.entries.len() < HIGH_SCORE_TABLE_SIZE
+            || self.entries.last().map_or(true, |last| score > last.score)
+    }
+
+    /// Add an entry to the table, returning its rank (starting at 0) if it made the cut.
+    pub fn insert(&mut self, entry: HighScoreEntry) -> Option<usize> {
+        if !self.qualifies(entry.score) {
+            return None;
+        }
+
+        // Ties go below the existing entries, so the earlier score keeps its place
+        let rank = self
+            .entries
+            .iter()
+            .position(|existing| entry.score > existing.score)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(rank, entry);
+        self.sort_and_truncate();
+
+        Some(rank)
+    }
+
+    fn sort_and_truncate(&mut self) {
+        self.entries.sort_by(|a, b| b.score.cmp(&a.score));
+        self.entries.truncate(HIGH_SCORE_TABLE_SIZE);
+    }
+}
+
+pub fn default_high_score_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| {
+        dir.join(HIGH_SCORE_DATA_DIRECTORY)
+            .join(HIGH_SCORE_FILE_NAME)
+    })
+}
+
+fn sanitize_field(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect()
+}
+
+/// Get today's date (UTC) formatted as `YYYY-MM-DD`.
+fn current_date() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 86400) as i64;
+
+    // Convert days since the epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
@@ -0,0 +1,143 @@
+use crate::game::{
+    assets::Assets,
+    level::{LevelState, LoadLevelEvent},
+    level_file,
+    music::{Playlists, PLAYLISTS_PATH},
+    render::SPRITE_ATLAS_PATH,
+    sound_cue::{SoundCues, SOUND_CUES_PATH},
+};
+use gfx::{atlas::TextureAtlas, renderer::TextureId};
+use specs::World;
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+pub const HOT_RELOAD_ROOT: &str = "res";
+
+// How often the files are checked for changes. Walking the tree every frame is wasted work.
+pub const HOT_RELOAD_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches a directory tree for files that are added or modified, by polling their modification times.
+pub struct FileWatcher {
+    root: PathBuf,
+    modified_times: HashMap<PathBuf, SystemTime>,
+    last_poll: Instant,
+}
+
+impl FileWatcher {
+    pub fn new(root: &Path) -> Self {
+        let mut modified_times = HashMap::new();
+        if let Err(e) = collect_modified_times(root, &mut modified_times) {
+            eprintln!("Failed to watch {}! {}", root.display(), e);
+        }
+
+        FileWatcher {
+            root: root.to_owned(),
+            modified_times,
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// The files that were added or modified since the last poll. Nothing is checked until the poll interval has
+    /// passed, so this can be called every frame.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < HOT_RELOAD_POLL_INTERVAL {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        let mut modified_times = HashMap::new();
+        if let Err(e) = collect_modified_times(&self.root, &mut modified_times) {
+            eprintln!("Failed to watch {}! {}", self.root.display(), e);
+            return Vec::new();
+        }
+
+        let mut changed: Vec<PathBuf> = modified_times
+            .iter()
+            .filter(|(path, time)| self.modified_times.get(*path) != Some(*time))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.sort();
+
+        self.modified_times = modified_times;
+        changed
+    }
+}
+
+fn collect_modified_times(dir: &Path, times: &mut HashMap<PathBuf, SystemTime>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            collect_modified_times(&path, times)?;
+        } else {
+            times.insert(path, metadata.modified()?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Reload whatever was loaded from the changed files: assets from the manifest, the sprite atlas, sound cues, music
+/// playlists, or the level being played (keeping the score and lives). Returns the ids of the textures that need to be created
+/// again.
+pub fn reload_changed_files(world: &World, changed: &[PathBuf]) -> Vec<TextureId> {
+    let mut changed_tex_ids = Vec::new();
+    for path in changed.iter() {
+        let mut reloaded = false;
+
+        {
+            let mut assets = world.write_resource::<Assets>();
+            if assets.uses_file(path) {
+                reloaded = true;
+                match assets.reload_file(path) {
+                    Ok(tex_ids) => changed_tex_ids.extend(tex_ids),
+                    Err(e) => eprintln!("Failed to reload asset! {}", e),
+                }
+            }
+        }
+
+        if path == Path::new(SPRITE_ATLAS_PATH) {
+            reloaded = true;
+            let tex_id = world.read_resource::<TextureAtlas>().tex_id();
+            match TextureAtlas::load_manifest(tex_id, path) {
+                Ok(atlas) => *world.write_resource::<TextureAtlas>() = atlas,
+                Err(e) => eprintln!("Failed to reload sprite atlas! {}", e),
+            }
+        }
+
+        if path == Path::new(SOUND_CUES_PATH) {
+            reloaded = true;
+            if let Err(e) = world.write_resource::<SoundCues>().reload(path) {
+                eprintln!("Failed to reload sound cues! {}", e);
+            }
+        }
+
+        if path == Path::new(PLAYLISTS_PATH) {
+            reloaded = true;
+            match Playlists::load(path) {
+                Ok(playlists) => *world.write_resource::<Playlists>() = playlists,
+                Err(e) => eprintln!("Failed to reload music playlists! {}", e),
+            }
+        }
+
+        {
+            let mut level = world.write_resource::<LevelState>();
+            if !level.level_id.is_empty() && *path == level_file::level_path(&level.level_id) {
+                reloaded = true;
+                let event = LoadLevelEvent::reload(&level.level_id);
+                level.load_level_event = Some(event);
+            }
+        }
+
+        if reloaded {
+            println!("[HotReload] Reloaded {}", path.display());
+        }
+    }
+
+    changed_tex_ids
+}
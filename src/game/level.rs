@@ -1,13 +1,18 @@
 use crate::game::{
     ball::SpawnBallEvent,
     brick::{self, BrickComponent},
+    campaign::CampaignState,
+    flow::FlowState,
+    level_file::{self, LevelDefinition},
+    music,
     paddle::{self, PlayerPaddleComponent},
     physics::ColliderComponent,
+    powerup::{self, PowerUpState},
     render::SpriteComponent,
     transform::TransformComponent,
     Point2f, Vector2d, Vector2f, WORLD_UNIT_RATIO,
 };
-use gfx::{color::*, renderer::Transparency, sprite::SpriteRegion};
+use gfx::{atlas::TextureAtlas, color::*, renderer::Transparency};
 use nalgebra::Vector2;
 use ncollide2d::shape::Cuboid;
 use shrev::EventChannel;
@@ -16,7 +21,8 @@ use specs::prelude::*;
 pub const PLAYER_DEFAULT_BALLS: u32 = 3;
 pub const LEVEL_BRICKS_Y_OFFSET: f64 = 22.0;
 pub const LEVEL_BRICKS_WIDTH: u32 = 10;
-pub const LEVEL_BRICKS_HEIGHT: u32 = 5;
+pub const LEVEL_BRICKS_MAX_HEIGHT: u32 = 10;
+pub const FIRST_LEVEL_ID: &str = "level-01";
 
 #[derive(Default)]
 pub struct LevelState {
@@ -26,39 +32,119 @@ pub struct LevelState {
     pub load_level_event: Option<LoadLevelEvent>,
     pub level_width: u32,
     pub level_height: u32,
+    pub level_id: String,
+    pub level_name: String,
+    pub ball_speed: f64,
+    pub background: String,
+    pub music: String,
+    pub powerup_drop_chance: f32,
+    pub campaign: CampaignState,
 }
 
 impl LevelState {
-    pub fn new(
-        level_width: u32,
-        level_height: u32,
-        load_level_event: LoadLevelEvent,
-    ) -> LevelState {
+    pub fn new(level_width: u32, level_height: u32, campaign: CampaignState) -> LevelState {
+        // Nothing is loaded until a game is started from the title screen
         LevelState {
             score: 0,
             lives: 3,
             player_paddle_ent: None,
-            load_level_event: Some(load_level_event),
+            load_level_event: None,
             level_width,
             level_height,
+            level_id: String::new(),
+            level_name: String::new(),
+            ball_speed: crate::game::ball::BALL_DEFAULT_FORCE,
+            background: level_file::LEVEL_DEFAULT_BACKGROUND.to_owned(),
+            music: music::DEFAULT_PLAYLIST.to_owned(),
+            powerup_drop_chance: powerup::POWERUP_DEFAULT_DROP_CHANCE,
+            campaign,
         }
     }
 
-    pub fn reset(&mut self, player_paddle_ent: Entity) {
+    pub fn reset(&mut self) {
         self.score = 0;
         self.lives = PLAYER_DEFAULT_BALLS;
+    }
+
+    pub fn begin_level(
+        &mut self,
+        player_paddle_ent: Entity,
+        level_id: &str,
+        definition: &LevelDefinition,
+    ) {
         self.player_paddle_ent = Some(player_paddle_ent);
         self.load_level_event = None;
+        self.campaign.begin_level(level_id);
+        self.level_id = level_id.to_owned();
+        self.level_name = definition.name.clone();
+        self.ball_speed = definition.ball_speed;
+        self.background = definition.background.clone();
+        self.music = definition.music.clone();
+        self.powerup_drop_chance = definition.powerup_drop_chance;
     }
 }
 
-#[derive(Clone, Copy)]
-pub struct LoadLevelEvent;
+#[derive(Clone, Debug)]
+pub struct LoadLevelEvent {
+    pub level_id: String,
+    pub reset_progress: bool,
+
+    // Reloading the level being played after its file changed, which keeps it running if the new file is broken
+    pub is_reload: bool,
+}
+
+impl LoadLevelEvent {
+    /// Load a level as the start of a new game, resetting the score and lives.
+    pub fn new_game(level_id: &str) -> Self {
+        LoadLevelEvent {
+            level_id: level_id.to_owned(),
+            reset_progress: true,
+            is_reload: false,
+        }
+    }
+
+    /// Load a level while carrying the score and lives over from the previous one.
+    pub fn next_level(level_id: &str) -> Self {
+        LoadLevelEvent {
+            level_id: level_id.to_owned(),
+            reset_progress: false,
+            is_reload: false,
+        }
+    }
+
+    /// Load the level being played again, e.g. after its file was edited, keeping the score and lives.
+    pub fn reload(level_id: &str) -> Self {
+        LoadLevelEvent {
+            level_id: level_id.to_owned(),
+            reset_progress: false,
+            is_reload: true,
+        }
+    }
+}
 
 pub fn load_level(world: &mut World) {
-    println!("Loading level...");
+    let event = match world.write_resource::<LevelState>().load_level_event.take() {
+        Some(event) => event,
+        None => return,
+    };
+
+    println!("Loading level {}...", event.level_id);
+
+    // Parse the level file before touching the world, so a broken level leaves the current one as it was. A broken
+    // reload keeps it running, otherwise there's no level to play and the game goes back to the title screen.
+    let definition = match level_file::load_level_file(&event.level_id) {
+        Ok(definition) => definition,
+        Err(e) => {
+            eprintln!("Failed to load level! {}", e);
+            if !event.is_reload {
+                *world.write_resource::<FlowState>() = FlowState::Title;
+            }
+            return;
+        }
+    };
 
     world.delete_all();
+    world.write_resource::<PowerUpState>().clear();
 
     let solid_collision_groups = ncollide2d::pipeline::CollisionGroups::new().with_membership(&[1]);
 
@@ -68,6 +154,10 @@ pub fn load_level(world: &mut World) {
     };
 
     // Spawn player paddle
+    let (sprites_tex_id, paddle_region) = {
+        let atlas = world.read_resource::<TextureAtlas>();
+        (atlas.tex_id(), atlas.sprite("paddle"))
+    };
     let paddle_position = Vector2d::new(level_width as f64 / 2.0, level_height as f64 - 10.0);
     let player_paddle_ent = world
         .create_entity()
@@ -88,54 +178,45 @@ pub fn load_level(world: &mut World) {
         .with(PlayerPaddleComponent::new(level_width))
         .with(SpriteComponent {
             color: COLOR_WHITE,
-            spritesheet_tex_id: 2,
-            region: SpriteRegion {
-                x: 0,
-                y: 0,
-                w: paddle::PADDLE_SPRITE_WIDTH,
-                h: paddle::PADDLE_SPRITE_HEIGHT,
-            },
+            spritesheet_tex_id: sprites_tex_id,
+            region: paddle_region,
             layer: 1,
             transparency: Transparency::Opaque,
         })
         .build();
 
     // Spawn bricks
-    for y in 0..LEVEL_BRICKS_HEIGHT {
-        for x in 0..LEVEL_BRICKS_WIDTH {
-            let position = Vector2d::new(
-                x as f64 * brick::BRICK_SPRITE_WIDTH as f64,
-                LEVEL_BRICKS_Y_OFFSET + (y as f64 * brick::BRICK_SPRITE_HEIGHT as f64),
-            );
-
-            world
-                .create_entity()
-                .with(TransformComponent::new(
-                    position,
-                    Point2f::origin(),
-                    Vector2f::new(1.0, 1.0),
-                ))
-                .with(ColliderComponent::new(
-                    Cuboid::new(Vector2::new(0.5, 0.25)),
-                    Vector2::new(16.0, 8.0),
-                    solid_collision_groups,
-                    0.0,
-                ))
-                .with(BrickComponent::new(brick::BRICK_DEFAULT_HP))
-                .with(SpriteComponent {
-                    color: COLOR_WHITE,
-                    spritesheet_tex_id: 2,
-                    region: SpriteRegion {
-                        x: 96,
-                        y: 0,
-                        w: brick::BRICK_SPRITE_WIDTH,
-                        h: brick::BRICK_SPRITE_HEIGHT,
-                    },
-                    layer: 2,
-                    transparency: Transparency::Opaque,
-                })
-                .build();
-        }
+    for level_brick in definition.bricks.iter() {
+        let position = Vector2d::new(
+            level_brick.x as f64 * brick::BRICK_SPRITE_WIDTH as f64,
+            LEVEL_BRICKS_Y_OFFSET + (level_brick.y as f64 * brick::BRICK_SPRITE_HEIGHT as f64),
+        );
+        let region = world
+            .read_resource::<TextureAtlas>()
+            .sprite(level_brick.kind.sprite_name(level_brick.kind.max_hp()));
+
+        world
+            .create_entity()
+            .with(TransformComponent::new(
+                position,
+                Point2f::origin(),
+                Vector2f::new(1.0, 1.0),
+            ))
+            .with(ColliderComponent::new(
+                Cuboid::new(Vector2::new(0.5, 0.25)),
+                Vector2::new(16.0, 8.0),
+                solid_collision_groups,
+                0.0,
+            ))
+            .with(BrickComponent::new(level_brick.kind))
+            .with(SpriteComponent {
+                color: COLOR_WHITE,
+                spritesheet_tex_id: sprites_tex_id,
+                region,
+                layer: 2,
+                transparency: Transparency::Opaque,
+            })
+            .build();
     }
 
     // Spawn initial ball
@@ -192,9 +273,14 @@ pub fn load_level(world: &mut World) {
         ))
         .build();
 
-    world
-        .write_resource::<LevelState>()
-        .reset(player_paddle_ent);
+    {
+        let mut level = world.write_resource::<LevelState>();
+        if event.reset_progress {
+            level.reset();
+        }
+
+        level.begin_level(player_paddle_ent, &event.level_id, &definition);
+    }
 
     world.maintain();
 }
@@ -0,0 +1,304 @@
+use crate::game::{ball, brick::BrickKind, level, music, powerup};
+use std::{fmt, path::PathBuf};
+
+pub const LEVELS_DIRECTORY: &str = "res/levels";
+pub const LEVEL_FILE_EXTENSION: &str = "lvl";
+
+// Name of the texture in the asset manifest drawn behind levels that don't set `background`
+pub const LEVEL_DEFAULT_BACKGROUND: &str = "bg";
+
+// Brick code used for empty cells in the `bricks:` grid of a level file, see `BrickKind::from_code` for the others
+pub const BRICK_CODE_EMPTY: char = '.';
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LevelBrick {
+    pub x: u32,
+    pub y: u32,
+    pub kind: BrickKind,
+}
+
+#[derive(Clone, Debug)]
+pub struct LevelDefinition {
+    pub name: String,
+    pub ball_speed: f64,
+    pub background: String,
+
+    // Name of the playlist in res/audio/playlists.txt played during the level
+    pub music: String,
+    pub powerup_drop_chance: f32,
+    pub bricks: Vec<LevelBrick>,
+}
+
+#[derive(Debug)]
+pub struct LevelParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl LevelParseError {
+    fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        LevelParseError {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for LevelParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub enum LevelLoadError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, LevelParseError),
+}
+
+impl fmt::Display for LevelLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelLoadError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            LevelLoadError::Parse(path, e) => write!(f, "{}:{}", path.display(), e),
+        }
+    }
+}
+
+pub fn level_path(level_id: &str) -> PathBuf {
+    PathBuf::from(LEVELS_DIRECTORY).join(format!("{}.{}", level_id, LEVEL_FILE_EXTENSION))
+}
+
+pub fn load_level_file(level_id: &str) -> Result<LevelDefinition, LevelLoadError> {
+    let path = level_path(level_id);
+    let source = match std::fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(e) => return Err(LevelLoadError::Io(path, e)),
+    };
+
+    parse_level(level_id, &source).map_err(|e| LevelLoadError::Parse(path, e))
+}
+
+/// Parse the contents of a level file.
+///
+/// A level file is a list of `key: value` metadata lines, followed by a `bricks:` line and then
+/// the brick grid, one row per line. Lines starting with `//` are comments.
+pub fn parse_level(level_id: &str, source: &str) -> Result<LevelDefinition, LevelParseError> {
+    let mut definition = LevelDefinition {
+        name: level_id.to_owned(),
+        ball_speed: ball::BALL_DEFAULT_FORCE,
+        background: LEVEL_DEFAULT_BACKGROUND.to_owned(),
+        music: music::DEFAULT_PLAYLIST.to_owned(),
+        powerup_drop_chance: powerup::POWERUP_DEFAULT_DROP_CHANCE,
+        bricks: Vec::new(),
+    };
+
+    let mut lines = source.lines().enumerate();
+    let mut bricks_line_num = None;
+
+    // Metadata section
+    for (i, line) in lines.by_ref() {
+        let line_num = i + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with("//") {
+            continue;
+        }
+
+        // Columns are counted in characters, the same as in the brick grid
+        let indent = line.chars().count() - line.trim_start().chars().count();
+        let separator = match trimmed.find(':') {
+            Some(separator) => separator,
+            None => {
+                return Err(LevelParseError::new(
+                    line_num,
+                    indent + 1,
+                    format!("Expected `key: value`, found `{}`", trimmed),
+                ))
+            }
+        };
+
+        let key = trimmed[..separator].trim();
+        let raw_value = &trimmed[separator + 1..];
+        let value = raw_value.trim();
+        let value_column = indent
+            + trimmed[..separator].chars().count()
+            + 2
+            + (raw_value.chars().count() - raw_value.trim_start().chars().count());
+
+        match key {
+            "bricks" => {
+                if !value.is_empty() {
+                    return Err(LevelParseError::new(
+                        line_num,
+                        value_column,
+                        "The brick grid must start on the line after `bricks:`",
+                    ));
+                }
+
+                bricks_line_num = Some(line_num);
+                break;
+            }
+            "name" => {
+                definition.name = parse_string(value, line_num, value_column)?;
+            }
+            "ball_speed" => {
+                definition.ball_speed = match value.parse::<f64>() {
+                    Ok(speed) if speed > 0.0 => speed,
+                    _ => {
+                        return Err(LevelParseError::new(
+                            line_num,
+                            value_column,
+                            format!(
+                                "Expected a positive number for `ball_speed`, found `{}`",
+                                value
+                            ),
+                        ))
+                    }
+                };
+            }
+            "background" => {
+                if value.is_empty() || value.contains(char::is_whitespace) {
+                    return Err(LevelParseError::new(
+                        line_num,
+                        value_column,
+                        format!(
+                            "Expected a texture name for `background`, found `{}`",
+                            value
+                        ),
+                    ));
+                }
+
+                definition.background = value.to_owned();
+            }
+            "music" => {
+                if value.is_empty() || value.contains(char::is_whitespace) {
+                    return Err(LevelParseError::new(
+                        line_num,
+                        value_column,
+                        format!("Expected a playlist name for `music`, found `{}`", value),
+                    ));
+                }
+
+                definition.music = value.to_owned();
+            }
+            "powerup_chance" => {
+                definition.powerup_drop_chance = match value.parse::<f32>() {
+                    Ok(chance) if (0.0..=1.0).contains(&chance) => chance,
+                    _ => {
+                        return Err(LevelParseError::new(
+                            line_num,
+                            value_column,
+                            format!(
+                                "Expected a number from 0 to 1 for `powerup_chance`, found `{}`",
+                                value
+                            ),
+                        ))
+                    }
+                };
+            }
+            _ => {
+                return Err(LevelParseError::new(
+                    line_num,
+                    indent + 1,
+                    format!("Unknown level property `{}`", key),
+                ))
+            }
+        }
+    }
+
+    let bricks_line_num = match bricks_line_num {
+        Some(line_num) => line_num,
+        None => {
+            return Err(LevelParseError::new(
+                source.lines().count() + 1,
+                1,
+                "Missing `bricks:` section",
+            ))
+        }
+    };
+
+    // Brick grid section. Trailing blank lines are ignored, but blank lines in between rows count as empty rows.
+    let rows: Vec<(usize, &str)> = lines.map(|(i, line)| (i + 1, line.trim_end())).collect();
+    let row_count = rows
+        .iter()
+        .rposition(|(_, row)| !row.is_empty())
+        .map_or(0, |last| last + 1);
+
+    if row_count as u32 > level::LEVEL_BRICKS_MAX_HEIGHT {
+        let (line_num, _) = rows[level::LEVEL_BRICKS_MAX_HEIGHT as usize];
+        return Err(LevelParseError::new(
+            line_num,
+            1,
+            format!(
+                "Too many brick rows, levels can have at most {}",
+                level::LEVEL_BRICKS_MAX_HEIGHT
+            ),
+        ));
+    }
+
+    for (y, (line_num, row)) in rows.iter().take(row_count).enumerate() {
+        for (x, code) in row.chars().enumerate() {
+            let column = x + 1;
+            if x as u32 >= level::LEVEL_BRICKS_WIDTH {
+                return Err(LevelParseError::new(
+                    *line_num,
+                    column,
+                    format!(
+                        "Brick row is too wide, levels can have at most {} columns",
+                        level::LEVEL_BRICKS_WIDTH
+                    ),
+                ));
+            }
+
+            if code == BRICK_CODE_EMPTY || code == ' ' {
+                continue;
+            }
+
+            let kind = match BrickKind::from_code(code) {
+                Some(kind) => kind,
+                None => {
+                    return Err(LevelParseError::new(
+                        *line_num,
+                        column,
+                        format!("Unknown brick code `{}`", code),
+                    ))
+                }
+            };
+
+            definition.bricks.push(LevelBrick {
+                x: x as u32,
+                y: y as u32,
+                kind,
+            });
+        }
+    }
+
+    // A level is cleared once its destructible bricks are broken, so a level without any would be over at once
+    if !definition
+        .bricks
+        .iter()
+        .any(|brick| brick.kind.is_destructible())
+    {
+        return Err(LevelParseError::new(
+            bricks_line_num,
+            1,
+            "The brick grid has no bricks that can be broken",
+        ));
+    }
+
+    Ok(definition)
+}
+
+fn parse_string(value: &str, line: usize, column: usize) -> Result<String, LevelParseError> {
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return Err(LevelParseError::new(
+            line,
+            column,
+            format!("Expected a quoted string, found `{}`", value),
+        ));
+    }
+
+    Ok(value[1..value.len() - 1].to_owned())
+}
@@ -1,23 +1,53 @@
+pub mod assets;
 pub mod audio;
+pub mod audio_backend;
 pub mod ball;
 pub mod brick;
+pub mod campaign;
+pub mod flow;
+pub mod font;
+pub mod high_score;
+pub mod hot_reload;
 pub mod level;
+pub mod level_file;
+pub mod music;
 pub mod paddle;
 pub mod physics;
+pub mod powerup;
 pub mod render;
+pub mod replay;
+pub mod rng;
+pub mod settings;
+pub mod sound_cue;
 pub mod transform;
+pub mod ui;
 
-use audio::AudioAssetDb;
+use assets::{Assets, ASSET_MANIFEST_PATH};
+use audio::{AudioMixer, AudioOutput};
 use ball::{BallSystem, SpawnBallSystem};
 use brick::BrickSystem;
+use campaign::{CampaignState, CampaignSystem, CAMPAIGN_PLAYLIST_PATH};
+use flow::{FlowState, FlowSystem};
+use gfx::{atlas::TextureAtlas, input::InputState};
+use high_score::HighScoreTable;
 use level::{LevelState, LoadLevelEvent};
+use music::{MusicSystem, Playlists, PLAYLISTS_PATH};
 use paddle::PlayerPaddleSystem;
 use physics::{
     ColliderSendPhysicsSystem, PhysicsState, RigidbodyReceivePhysicsSystem,
     RigidbodySendPhysicsSystem, WorldStepPhysicsSystem,
 };
-use render::{RenderState, SpriteRenderSystem};
+use powerup::{
+    LaserBoltSystem, PowerUpEffectSystem, PowerUpPickupSystem, PowerUpState, SpawnPowerUpSystem,
+};
+use render::{
+    RenderState, ScreenShakeSystem, SpriteRenderSystem, SPRITES_TEXTURE, SPRITE_ATLAS_PATH,
+};
+use rng::RngState;
+use settings::{Settings, SettingsSystem};
+use sound_cue::{SoundCues, SOUND_CUES_PATH};
 use specs::prelude::*;
+use std::path::Path;
 use transform::TransformComponent;
 
 pub type Vector2f = nalgebra::Vector2<f32>;
@@ -30,20 +60,50 @@ pub const WORLD_UNIT_RATIO: f64 = (1.0 / PIXELS_PER_WORLD_UNIT as f64);
 
 pub struct GameState<'a, 'b> {
     pub world: World,
+    pub flow_dispatcher: Dispatcher<'a, 'b>,
     pub tick_dispatcher: Dispatcher<'a, 'b>,
     pub physics_dispatcher: Dispatcher<'a, 'b>,
+    pub render_dispatcher: Dispatcher<'a, 'b>,
+    pub rendering_enabled: bool,
 }
 
 impl<'a, 'b> GameState<'a, 'b> {
-    pub fn new(width: u32, height: u32) -> GameState<'a, 'b> {
+    /// The high scores and settings are the player's, loaded from their data directory by the real game. Headless
+    /// runs pass in defaults instead, so they never touch the player's files.
+    pub fn new(
+        width: u32,
+        height: u32,
+        seed: u64,
+        high_scores: HighScoreTable,
+        settings: Settings,
+        audio_output: AudioOutput,
+    ) -> GameState<'a, 'b> {
         let mut world = World::new();
 
+        let mut flow_dispatcher = DispatcherBuilder::new()
+            .with(FlowSystem, "flow", &[])
+            .with(MusicSystem, "music", &["flow"])
+            .with(SettingsSystem, "settings", &["flow"])
+            .build();
+
+        flow_dispatcher.setup(&mut world);
+
         let mut tick_dispatcher = DispatcherBuilder::new()
             .with(PlayerPaddleSystem, "player_paddle", &[])
             .with(BallSystem::default(), "ball", &[])
-            .with(BrickSystem::default(), "brick", &[])
+            // Systems drawing from the RngState must run in a fixed order, so runs can be reproduced from a seed
+            .with(BrickSystem::default(), "brick", &["ball"])
+            .with(CampaignSystem, "campaign", &["brick"])
+            .with(PowerUpPickupSystem::default(), "powerup_pickup", &[])
+            .with(
+                PowerUpEffectSystem,
+                "powerup_effect",
+                &["player_paddle", "powerup_pickup"],
+            )
+            .with(LaserBoltSystem::default(), "laser_bolt", &[])
+            .with(ScreenShakeSystem, "screen_shake", &["ball", "brick"])
             .with_thread_local(SpawnBallSystem::default())
-            .with_thread_local(SpriteRenderSystem::default())
+            .with_thread_local(SpawnPowerUpSystem::default())
             .build();
 
         tick_dispatcher.setup(&mut world);
@@ -57,16 +117,107 @@ impl<'a, 'b> GameState<'a, 'b> {
 
         physics_dispatcher.setup(&mut world);
 
+        let mut render_dispatcher = DispatcherBuilder::new()
+            .with_thread_local(SpriteRenderSystem::default())
+            .build();
+
+        render_dispatcher.setup(&mut world);
+
         // Resources
+        world.insert(FlowState::Title);
         world.insert(RenderState::new());
-        world.insert(LevelState::new(width, height, LoadLevelEvent));
+        let campaign = CampaignState::load(CAMPAIGN_PLAYLIST_PATH).unwrap_or_else(|e| {
+            eprintln!(
+                "Failed to load campaign playlist {}! {}",
+                CAMPAIGN_PLAYLIST_PATH, e
+            );
+            CampaignState::default()
+        });
+        world.insert(LevelState::new(width, height, campaign));
         world.insert(PhysicsState::new());
-        world.insert(AudioAssetDb::new());
+        world.insert(PowerUpState::new());
+
+        // Anything that failed to load is left out, and looked up by name as missing later
+        let (assets, result) = Assets::load(Path::new(ASSET_MANIFEST_PATH));
+        if let Err(errors) = result {
+            eprintln!("Failed to load assets! {}", errors);
+        }
+
+        let sprites_tex_id = assets.tex_id(SPRITES_TEXTURE);
+        let atlas = TextureAtlas::load_manifest(sprites_tex_id, Path::new(SPRITE_ATLAS_PATH))
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to load sprite atlas! {}", e);
+                TextureAtlas::new(sprites_tex_id)
+            });
+        world.insert(atlas);
+        world.insert(assets);
+        world.insert(RngState::new(seed));
+        let cues = SoundCues::load(Path::new(SOUND_CUES_PATH), seed).unwrap_or_else(|e| {
+            eprintln!("Failed to load sound cues! {}", e);
+            SoundCues::new(Vec::new(), seed)
+        });
+        world.insert(cues);
+        let playlists = Playlists::load(Path::new(PLAYLISTS_PATH)).unwrap_or_else(|e| {
+            eprintln!("Failed to load music playlists! {}", e);
+            Playlists::default()
+        });
+        world.insert(playlists);
+        world.insert(high_scores);
+        world.insert(settings);
+        world.insert(AudioMixer::new(audio_output));
 
         GameState {
             world,
+            flow_dispatcher,
             tick_dispatcher,
             physics_dispatcher,
+            render_dispatcher,
+            rendering_enabled: true,
         }
     }
+
+    /// Skip the title screen and start a new game on the given level.
+    pub fn start_game(&mut self, level_id: &str) {
+        self.world.write_resource::<LevelState>().load_level_event =
+            Some(LoadLevelEvent::new_game(level_id));
+        *self.world.write_resource::<FlowState>() = FlowState::Playing;
+    }
+
+    /// Run a single game tick. Gameplay and physics only run while the flow state is simulating, so pausing freezes the field.
+    pub fn tick(&mut self) {
+        // Handle any level loads
+        let load_level_pending = self
+            .world
+            .read_resource::<LevelState>()
+            .load_level_event
+            .is_some();
+        if load_level_pending {
+            level::load_level(&mut self.world);
+        }
+
+        // Input actions follow the bindings in the settings, however the input was made (window, replay or test)
+        let bindings = self.world.read_resource::<Settings>().key_bindings.clone();
+        self.world
+            .write_resource::<InputState>()
+            .set_bindings(bindings);
+
+        self.world.write_resource::<RenderState>().clear_commands();
+        self.flow_dispatcher.dispatch(&mut self.world);
+
+        let flow = *self.world.read_resource::<FlowState>();
+        if flow.is_simulating() {
+            self.tick_dispatcher.dispatch(&mut self.world);
+            self.physics_dispatcher.dispatch(&mut self.world);
+        }
+
+        if self.rendering_enabled && flow.shows_field() {
+            self.render_dispatcher.dispatch(&mut self.world);
+        }
+
+        // Fades keep going while the game is paused. This runs last, so sounds played during a tick are
+        // captured with that tick's number.
+        self.world.write_resource::<AudioMixer>().update();
+
+        self.world.maintain();
+    }
 }
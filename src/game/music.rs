@@ -0,0 +1,340 @@
+use crate::game::{
+    assets::Assets,
+    audio::{AudioMixer, VoiceHandle, MUSIC_CROSSFADE_TICKS},
+    flow::FlowState,
+    level::LevelState,
+};
+use specs::prelude::*;
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::{self, File},
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+pub const PLAYLISTS_PATH: &str = "res/audio/playlists.txt";
+
+// Playlist for the title and high score screens
+pub const TITLE_PLAYLIST: &str = "title";
+
+// Playlist for levels that don't set `music`
+pub const DEFAULT_PLAYLIST: &str = "default";
+
+// How far into an Ogg file to look for its comments
+const OGG_COMMENT_SEARCH_BYTES: usize = 64 * 1024;
+
+/// A music file, which is streamed from disk as it plays rather than loaded up front.
+#[derive(Clone, Debug)]
+pub struct MusicTrack {
+    pub name: String,
+    pub path: PathBuf,
+
+    // Once playback reaches the loop end (or the end of the file), it jumps back to the loop start. Both are in
+    // sample frames, so they don't depend on the number of channels.
+    pub loop_start: u64,
+    pub loop_end: Option<u64>,
+}
+
+impl MusicTrack {
+    /// Read a track's loop points from the file's own metadata: the `smpl` chunk of a WAV file, or the
+    /// `LOOPSTART` and `LOOPLENGTH` (or `LOOPEND`) comments of an Ogg Vorbis or FLAC file. Files without loop
+    /// points loop from start to end.
+    pub fn open(name: &str, path: &Path) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 4];
+        file.read_exact(&mut magic)?;
+        file.seek(SeekFrom::Start(0))?;
+
+        let (loop_start, loop_end) = match &magic {
+            b"RIFF" => read_wav_loop_points(&mut file)?,
+            b"fLaC" => loop_points_from_comments(&read_flac_comments(&mut file)?),
+            b"OggS" => loop_points_from_comments(&read_ogg_comments(&mut file)?),
+            _ => (0, None),
+        };
+
+        Ok(MusicTrack {
+            name: name.to_owned(),
+            path: path.to_owned(),
+            loop_start,
+            loop_end: loop_end.filter(|end| *end > loop_start),
+        })
+    }
+}
+
+fn read_u32_le(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_wav_loop_points(file: &mut (impl Read + Seek)) -> io::Result<(u64, Option<u64>)> {
+    // Skip `RIFF`, the file size and `WAVE`
+    file.seek(SeekFrom::Start(12))?;
+
+    let mut chunk_id = [0u8; 4];
+    loop {
+        if file.read_exact(&mut chunk_id).is_err() {
+            return Ok((0, None));
+        }
+        let chunk_size = read_u32_le(file)?;
+
+        if &chunk_id == b"smpl" {
+            // The loop count comes after 7 fields about the sampler, and is followed by another field
+            file.seek(SeekFrom::Current(28))?;
+            let loop_count = read_u32_le(file)?;
+            if loop_count == 0 {
+                return Ok((0, None));
+            }
+
+            // Each loop starts with its id and type, and the end is the last frame of the loop
+            file.seek(SeekFrom::Current(4 + 8))?;
+            let start = read_u32_le(file)? as u64;
+            let end = read_u32_le(file)? as u64;
+            return Ok((start, Some(end + 1)));
+        }
+
+        // Chunks are padded to an even size
+        file.seek(SeekFrom::Current(
+            chunk_size as i64 + (chunk_size % 2) as i64,
+        ))?;
+    }
+}
+
+fn read_flac_comments(file: &mut impl Read) -> io::Result<Vec<String>> {
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic)?;
+
+    loop {
+        let mut header = [0u8; 4];
+        file.read_exact(&mut header)?;
+        let is_last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7f;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+
+        let mut block = vec![0u8; length];
+        file.read_exact(&mut block)?;
+
+        // Type 4 is the Vorbis comment block
+        if block_type == 4 {
+            return Ok(parse_vorbis_comments(&block).unwrap_or_default());
+        }
+
+        if is_last {
+            return Ok(Vec::new());
+        }
+    }
+}
+
+/// Only finds the comments if the comment header is within a single Ogg page, which is the case unless the file
+/// has very large comments (e.g. embedded cover art).
+fn read_ogg_comments(file: &mut impl Read) -> io::Result<Vec<String>> {
+    let mut bytes = Vec::new();
+    file.take(OGG_COMMENT_SEARCH_BYTES as u64)
+        .read_to_end(&mut bytes)?;
+
+    let header = b"\x03vorbis";
+    let comments = bytes
+        .windows(header.len())
+        .position(|window| window == header)
+        .and_then(|position| parse_vorbis_comments(&bytes[position + header.len()..]));
+
+    Ok(comments.unwrap_or_default())
+}
+
+/// Parse a Vorbis comment list (which FLAC uses too): a vendor string, then a count of `KEY=value` strings. All
+/// lengths are little-endian.
+fn parse_vorbis_comments(bytes: &[u8]) -> Option<Vec<String>> {
+    let mut reader = bytes;
+    let vendor_length = read_u32_le(&mut reader).ok()? as usize;
+    reader = reader.get(vendor_length..)?;
+
+    let count = read_u32_le(&mut reader).ok()?;
+    let mut comments = Vec::new();
+    for _ in 0..count {
+        let length = read_u32_le(&mut reader).ok()? as usize;
+        let comment = reader.get(..length)?;
+        comments.push(String::from_utf8_lossy(comment).into_owned());
+        reader = &reader[length..];
+    }
+
+    Some(comments)
+}
+
+fn loop_points_from_comments(comments: &[String]) -> (u64, Option<u64>) {
+    let value = |key: &str| {
+        comments.iter().find_map(|comment| {
+            let separator = comment.find('=')?;
+            if comment[..separator].eq_ignore_ascii_case(key) {
+                comment[separator + 1..].trim().parse::<u64>().ok()
+            } else {
+                None
+            }
+        })
+    };
+
+    let start = value("LOOPSTART").unwrap_or(0);
+    let end = value("LOOPLENGTH")
+        .map(|length| start + length)
+        .or_else(|| value("LOOPEND"));
+
+    (start, end)
+}
+
+#[derive(Debug)]
+pub enum PlaylistError {
+    Io(PathBuf, io::Error),
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for PlaylistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlaylistError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            PlaylistError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+/// Named lists of music tracks. A playlist plays its tracks in order and then starts over, and a playlist with a
+/// single track loops it.
+#[derive(Default)]
+pub struct Playlists {
+    playlists: HashMap<String, Vec<String>>,
+}
+
+impl Playlists {
+    /// Load playlists from a file. Each playlist starts with a `playlist <name>` line, followed by a `track: <music>`
+    /// line for each of its tracks, naming music from the asset manifest. Lines starting with `//` are comments.
+    pub fn load(path: &Path) -> Result<Self, PlaylistError> {
+        let source = fs::read_to_string(path).map_err(|e| PlaylistError::Io(path.to_owned(), e))?;
+
+        let mut playlists = Playlists::default();
+        let mut current: Option<String> = None;
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let parse_error = |message: String| PlaylistError::Parse {
+                path: path.to_owned(),
+                line: i + 1,
+                message,
+            };
+
+            if let Some(name) = line.strip_prefix("playlist ") {
+                let name = name.trim().to_owned();
+                if playlists.playlists.contains_key(&name) {
+                    return Err(parse_error(format!("Playlist `{}` is defined twice", name)));
+                }
+
+                playlists.playlists.insert(name.clone(), Vec::new());
+                current = Some(name);
+            } else if let Some(track) = line.strip_prefix("track:") {
+                match current.as_ref() {
+                    Some(name) => playlists
+                        .playlists
+                        .get_mut(name)
+                        .unwrap()
+                        .push(track.trim().to_owned()),
+                    None => return Err(parse_error("Expected `playlist <name>` first".to_owned())),
+                }
+            } else {
+                return Err(parse_error(format!(
+                    "Expected `playlist <name>` or `track: <music>`, found `{}`",
+                    line
+                )));
+            }
+        }
+
+        Ok(playlists)
+    }
+
+    pub fn tracks(&self, playlist: &str) -> Option<&[String]> {
+        self.playlists.get(playlist).map(|tracks| tracks.as_slice())
+    }
+}
+
+/// Which playlist is playing, and how far through it.
+#[derive(Default)]
+pub struct MusicState {
+    pub playlist: Option<String>,
+    track_index: usize,
+    voice: Option<VoiceHandle>,
+}
+
+/// Plays the playlist for the title screen or the current level, crossfading when it changes and moving on to the
+/// next track when one finishes.
+pub struct MusicSystem;
+
+impl<'a> System<'a> for MusicSystem {
+    type SystemData = (
+        Write<'a, MusicState>,
+        Read<'a, FlowState>,
+        Read<'a, LevelState>,
+        ReadExpect<'a, Playlists>,
+        ReadExpect<'a, Assets>,
+        WriteExpect<'a, AudioMixer>,
+    );
+
+    fn run(&mut self, (mut music, flow, level, playlists, assets, mut mixer): Self::SystemData) {
+        let playlist = if flow.shows_field() {
+            level.music.as_str()
+        } else {
+            TITLE_PLAYLIST
+        };
+
+        let tracks = match playlists.tracks(playlist) {
+            Some(tracks) if !tracks.is_empty() => tracks,
+            _ => {
+                if music.playlist.as_deref() != Some(playlist) {
+                    eprintln!(
+                        "Failed to play music! Playlist `{}` has no tracks!",
+                        playlist
+                    );
+                    music.playlist = Some(playlist.to_owned());
+                    mixer.stop_music(MUSIC_CROSSFADE_TICKS);
+                }
+                return;
+            }
+        };
+
+        // Crossfade into a new playlist, but go straight from one track to the next
+        let (track_index, fade_ticks) = if music.playlist.as_deref() != Some(playlist) {
+            println!("[MusicSystem] Playing playlist {}", playlist);
+            music.playlist = Some(playlist.to_owned());
+            (0, MUSIC_CROSSFADE_TICKS)
+        } else {
+            // Only move on once the current track has finished. If it never started, don't keep retrying it.
+            match music.voice {
+                Some(voice) if !mixer.is_playing(voice) => {
+                    ((music.track_index + 1) % tracks.len(), 0)
+                }
+                _ => return,
+            }
+        };
+
+        music.track_index = track_index;
+        let name = &tracks[track_index];
+        music.voice = match assets.music(name) {
+            // A lone track loops by itself, otherwise the playlist moves on when it ends
+            Some(track) => {
+                mixer.play_music(assets.music_track(track), tracks.len() == 1, fade_ticks)
+            }
+            None => {
+                eprintln!("Failed to play music! Music `{}` was not loaded!", name);
+                mixer.stop_music(MUSIC_CROSSFADE_TICKS);
+                None
+            }
+        };
+    }
+}
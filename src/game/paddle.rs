@@ -1,10 +1,7 @@
 use crate::game::{
-    ball::BallComponent,
-    level::{LevelState, LoadLevelEvent},
-    transform::TransformComponent,
-    Vector2d,
+    ball::BallComponent, level::LevelState, transform::TransformComponent, Vector2d,
 };
-use gfx::input::{InputState, VirtualKeyCode};
+use gfx::input::{InputAction, InputState};
 use specs::prelude::*;
 
 pub const PADDLE_HIT_BOX_WIDTH: f64 = 57.0;
@@ -18,6 +15,7 @@ pub struct PlayerPaddleComponent {
     pub held_ball_ent: Option<Entity>,
     pub held_ball_position: Vector2d,
     pub level_width: u32,
+    pub hit_box_width: f64,
     movement_linear_velocity: Vector2d,
 }
 
@@ -27,6 +25,7 @@ impl PlayerPaddleComponent {
             held_ball_ent: None,
             held_ball_position: Vector2d::zeros(),
             level_width,
+            hit_box_width: PADDLE_HIT_BOX_WIDTH,
             movement_linear_velocity: Vector2d::zeros(),
         }
     }
@@ -40,35 +39,29 @@ pub struct PlayerPaddleSystem;
 
 impl<'a> System<'a> for PlayerPaddleSystem {
     type SystemData = (
-        Write<'a, LevelState>,
+        Read<'a, LevelState>,
         Read<'a, InputState>,
         WriteStorage<'a, TransformComponent>,
         WriteStorage<'a, PlayerPaddleComponent>,
         WriteStorage<'a, BallComponent>,
     );
 
-    fn run(
-        &mut self,
-        (mut level, input, mut transforms, mut paddles, mut balls): Self::SystemData,
-    ) {
+    fn run(&mut self, (level, input, mut transforms, mut paddles, mut balls): Self::SystemData) {
         for (transform, paddle) in (&mut transforms, &mut paddles).join() {
             let speed = 8.0;
             paddle.movement_linear_velocity = Vector2d::zeros();
 
-            if input.is_key_held(VirtualKeyCode::A) || input.is_key_held(VirtualKeyCode::Left) {
-                paddle.movement_linear_velocity.x -= speed;
-            }
-
-            if input.is_key_held(VirtualKeyCode::D) || input.is_key_held(VirtualKeyCode::Right) {
-                paddle.movement_linear_velocity.x += speed;
-            }
+            // Keys move the paddle at full speed, while a stick moves it as fast as it's pushed
+            let movement = input.action_value(InputAction::MoveRight)
+                - input.action_value(InputAction::MoveLeft);
+            paddle.movement_linear_velocity.x = speed * movement as f64;
 
             transform.position += paddle.movement_linear_velocity;
 
             // Restrain paddle to the level
             let paddle_x_min = 2.0;
             let paddle_x_max = paddle.level_width as f64 - 2.0;
-            let paddle_half_width = PADDLE_HIT_BOX_WIDTH / 2.0;
+            let paddle_half_width = paddle.hit_box_width / 2.0;
             if (transform.position.x - paddle_half_width) < paddle_x_min {
                 transform.position.x = paddle_x_min + paddle_half_width;
             } else if (transform.position.x + paddle_half_width) > paddle_x_max {
@@ -82,17 +75,12 @@ impl<'a> System<'a> for PlayerPaddleSystem {
                         - crate::game::ball::BALL_COLLIDER_RADIUS
                         - 2.0,
                 );
-
-            // If the game is over, and the player presses 'R', begin a new game
-            if (level.lives == 0) && input.is_key_pressed(VirtualKeyCode::R) {
-                level.load_level_event = Some(LoadLevelEvent);
-            }
         }
 
         // Handle paddles that are holding a ball
         for mut paddle in (&mut paddles).join() {
             if let Some(ball_ent) = paddle.held_ball_ent {
-                if input.is_key_pressed(VirtualKeyCode::Space) {
+                if input.is_action_pressed(InputAction::Launch) {
                     paddle.held_ball_ent = None;
 
                     let ball = balls.get_mut(ball_ent).expect(
@@ -101,7 +89,7 @@ impl<'a> System<'a> for PlayerPaddleSystem {
 
                     ball.holding_paddle_ent = None;
                     ball.velocity.linear = paddle.movement_linear_velocity * 0.5;
-                    ball.velocity.linear.y = -crate::game::ball::BALL_DEFAULT_FORCE;
+                    ball.velocity.linear.y = -level.ball_speed;
                 }
             }
         }
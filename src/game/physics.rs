@@ -2,6 +2,7 @@ use crate::game::*;
 use nalgebra::{Isometry2, Vector2};
 use ncollide2d::{
     pipeline::{CollisionGroups, ContactEvent},
+    query::Proximity,
     shape::{Shape, ShapeHandle},
 };
 use nphysics2d::{
@@ -132,6 +133,7 @@ pub struct ColliderComponent {
     pub collision_groups: CollisionGroups,
     pub density: f64,
     pub ccd_enabled: bool,
+    pub is_sensor: bool,
 }
 
 impl ColliderComponent {
@@ -148,6 +150,19 @@ impl ColliderComponent {
             density,
             // CCD seems kinda buggy at the moment https://github.com/rustsim/nphysics/issues/255
             ccd_enabled: false,
+            is_sensor: false,
+        }
+    }
+
+    /// Create a sensor collider, which reports overlaps as `CollisionEvent`s without any contact response.
+    pub fn new_sensor<S: Shape<f64>>(
+        shape: S,
+        offset: Vector2<f64>,
+        collision_groups: CollisionGroups,
+    ) -> Self {
+        ColliderComponent {
+            is_sensor: true,
+            ..ColliderComponent::new(shape, offset, collision_groups, 0.0)
         }
     }
 }
@@ -371,6 +386,20 @@ impl<'a> System<'a> for ColliderSendPhysicsSystem {
             }
         }
 
+        // Handle modified colliders by removing them from the physics world, so they are rebuilt along with the inserted ones
+        for (ent, _, ent_id) in (&entities, &colliders, &self.modified_colliders).join() {
+            if let Some(collider_handle) = physics.ent_collider_handles.remove(&ent.id()) {
+                physics.colliders.remove(collider_handle);
+                self.inserted_colliders.add(ent_id);
+                println!(
+                    "[ColliderSendPhysicsSystem] Modified collider. Entity Id = {}",
+                    ent_id
+                );
+            } else {
+                eprintln!("[ColliderSendPhysicsSystem] Failed to update collider because it didn't exist! Entity Id = {}", ent_id);
+            }
+        }
+
         // Handle inserted colliders
         for (ent, transform, collider, _) in
             (&entities, &transforms, &colliders, &self.inserted_colliders).join()
@@ -397,6 +426,7 @@ impl<'a> System<'a> for ColliderSendPhysicsSystem {
                 .translation(translation)
                 .margin(0.02)
                 .ccd_enabled(collider.ccd_enabled)
+                .sensor(collider.is_sensor)
                 .collision_groups(collider.collision_groups.clone())
                 .user_data(ent)
                 .build(BodyPartHandle(parent_body_handle, 0));
@@ -411,19 +441,6 @@ impl<'a> System<'a> for ColliderSendPhysicsSystem {
             );
         }
 
-        // Handle modified colliders
-        for (ent, _, _) in (&entities, &colliders, &self.modified_colliders).join() {
-            if let Some(_) = physics.ent_collider_handles.get(&ent.id()).cloned() {
-                // TODO
-                println!(
-                    "[ColliderSendPhysicsSystem] Modified collider: {}",
-                    ent.id()
-                );
-            } else {
-                eprintln!("[ColliderSendPhysicsSystem] Failed to update collider because it didn't exist! Entity Id = {}", ent.id());
-            }
-        }
-
         // Handle modified transforms (ignoring rigidbodies, because they will update themselves)
         for (ent, transform, collider, _, _) in (
             &entities,
@@ -540,6 +557,57 @@ impl<'a> System<'a> for WorldStepPhysicsSystem {
                 collision_events.iter_write(events);
             }
         }
+
+        // Sensors don't generate contacts, so their overlaps are reported through proximity events instead
+        for event in physics.geometrical_world.proximity_events() {
+            if event.new_status != Proximity::Intersecting {
+                continue;
+            }
+
+            let (collider_a, collider_b) = match (
+                physics.colliders.get(event.collider1),
+                physics.colliders.get(event.collider2),
+            ) {
+                (Some(a), Some(b)) => (a, b),
+                _ => {
+                    eprintln!("No colliders found for proximity event!");
+                    continue;
+                }
+            };
+
+            let entity_a = collider_a
+                .user_data()
+                .unwrap()
+                .downcast_ref::<Entity>()
+                .cloned();
+            let entity_b = collider_b
+                .user_data()
+                .unwrap()
+                .downcast_ref::<Entity>()
+                .cloned();
+
+            let event_a = CollisionEvent {
+                entity_a,
+                collider_handle_a: event.collider1,
+                entity_b,
+                collider_handle_b: event.collider2,
+                normal: None,
+                collision_point: None,
+                ty: CollisionType::Started,
+            };
+
+            let event_b = CollisionEvent {
+                entity_a: entity_b,
+                collider_handle_a: event.collider2,
+                entity_b: entity_a,
+                collider_handle_b: event.collider1,
+                normal: None,
+                collision_point: None,
+                ty: CollisionType::Started,
+            };
+
+            collision_events.iter_write(vec![event_a, event_b]);
+        }
     }
 }
 
@@ -0,0 +1,600 @@
+use crate::game::{
+    ball::{BallComponent, SpawnBallEvent},
+    brick::BrickComponent,
+    paddle::{self, PlayerPaddleComponent},
+    physics::{ColliderComponent, CollisionEvent, RigidbodyComponent},
+    render::SpriteComponent,
+    transform::TransformComponent,
+    LevelState, Point2f, Vector2d, Vector2f, WORLD_UNIT_RATIO,
+};
+use gfx::{
+    atlas::TextureAtlas,
+    color::*,
+    input::{InputAction, InputState},
+    renderer::Transparency,
+};
+use nalgebra::Vector2;
+use ncollide2d::shape::{Cuboid, ShapeHandle};
+use nphysics2d::object::BodyStatus;
+use shrev::EventChannel;
+use specs::prelude::*;
+
+pub const POWERUP_DEFAULT_DROP_CHANCE: f32 = 0.15;
+pub const POWERUP_CAPSULE_WIDTH: u32 = 16;
+pub const POWERUP_CAPSULE_HEIGHT: u32 = 8;
+pub const POWERUP_CAPSULE_FALL_SPEED: f64 = 2.5;
+pub const POWERUP_WIDE_PADDLE_SCALE: f64 = 1.5;
+pub const POWERUP_SLOW_BALL_SCALE: f64 = 0.6;
+pub const POWERUP_MULTI_BALL_SPLIT_COUNT: usize = 3;
+pub const POWERUP_MULTI_BALL_SPREAD_RADIANS: f64 = 0.4;
+pub const LASER_BOLT_WIDTH: u32 = 4;
+pub const LASER_BOLT_HEIGHT: u32 = 8;
+pub const LASER_BOLT_SPEED: f64 = 12.0;
+pub const LASER_COOLDOWN_TICKS: u32 = 20;
+
+/// How picking up a power-up interacts with an already active power-up of the same kind.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PowerUpStacking {
+    /// The effect is applied immediately, and never becomes active.
+    Instant,
+    /// The remaining duration is reset to the full duration.
+    Refresh,
+    /// The full duration is added to the remaining duration, up to a maximum.
+    Extend { max_ticks: u32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerUpKind {
+    WidePaddle,
+    MultiBall,
+    SlowBall,
+    StickyPaddle,
+    Laser,
+    ExtraLife,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 6] = [
+        PowerUpKind::WidePaddle,
+        PowerUpKind::MultiBall,
+        PowerUpKind::SlowBall,
+        PowerUpKind::StickyPaddle,
+        PowerUpKind::Laser,
+        PowerUpKind::ExtraLife,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerUpKind::WidePaddle => "Wide",
+            PowerUpKind::MultiBall => "Multi",
+            PowerUpKind::SlowBall => "Slow",
+            PowerUpKind::StickyPaddle => "Sticky",
+            PowerUpKind::Laser => "Laser",
+            PowerUpKind::ExtraLife => "1UP",
+        }
+    }
+
+    pub fn duration_ticks(self) -> u32 {
+        match self {
+            PowerUpKind::WidePaddle => 60 * 15,
+            PowerUpKind::SlowBall => 60 * 10,
+            PowerUpKind::StickyPaddle => 60 * 15,
+            PowerUpKind::Laser => 60 * 12,
+            PowerUpKind::MultiBall | PowerUpKind::ExtraLife => 0,
+        }
+    }
+
+    pub fn stacking(self) -> PowerUpStacking {
+        match self {
+            PowerUpKind::WidePaddle | PowerUpKind::StickyPaddle | PowerUpKind::Laser => {
+                PowerUpStacking::Refresh
+            }
+            PowerUpKind::SlowBall => PowerUpStacking::Extend { max_ticks: 60 * 30 },
+            PowerUpKind::MultiBall | PowerUpKind::ExtraLife => PowerUpStacking::Instant,
+        }
+    }
+
+    /// Sticky and laser paddles both use the launch key, so only one of them can be active at a time.
+    pub fn excludes(self) -> Option<PowerUpKind> {
+        match self {
+            PowerUpKind::StickyPaddle => Some(PowerUpKind::Laser),
+            PowerUpKind::Laser => Some(PowerUpKind::StickyPaddle),
+            _ => None,
+        }
+    }
+
+    pub fn drop_weight(self) -> u32 {
+        match self {
+            PowerUpKind::ExtraLife => 1,
+            _ => 4,
+        }
+    }
+
+    pub fn sprite_name(self) -> &'static str {
+        match self {
+            PowerUpKind::WidePaddle => "powerup/wide_paddle",
+            PowerUpKind::MultiBall => "powerup/multi_ball",
+            PowerUpKind::SlowBall => "powerup/slow_ball",
+            PowerUpKind::StickyPaddle => "powerup/sticky_paddle",
+            PowerUpKind::Laser => "powerup/laser",
+            PowerUpKind::ExtraLife => "powerup/extra_life",
+        }
+    }
+
+    /// Pick a power-up kind, weighted by `drop_weight`.
+    pub fn random<R: rand::Rng>(rng: &mut R) -> PowerUpKind {
+        let total_weight: u32 = PowerUpKind::ALL.iter().map(|k| k.drop_weight()).sum();
+        let mut roll = rng.gen_range(0, total_weight);
+        for kind in PowerUpKind::ALL.iter() {
+            if roll < kind.drop_weight() {
+                return *kind;
+            }
+
+            roll -= kind.drop_weight();
+        }
+
+        unreachable!()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ActivePowerUp {
+    pub kind: PowerUpKind,
+    pub ticks_remaining: u32,
+}
+
+#[derive(Default)]
+pub struct PowerUpState {
+    pub active: Vec<ActivePowerUp>,
+    pub laser_cooldown_ticks: u32,
+}
+
+impl PowerUpState {
+    pub fn new() -> Self {
+        PowerUpState {
+            active: Vec::new(),
+            laser_cooldown_ticks: 0,
+        }
+    }
+
+    pub fn is_active(&self, kind: PowerUpKind) -> bool {
+        self.active.iter().any(|p| p.kind == kind)
+    }
+
+    /// Activate a timed power-up, following its stacking rules.
+    pub fn activate(&mut self, kind: PowerUpKind) {
+        if let Some(excluded) = kind.excludes() {
+            self.active.retain(|p| p.kind != excluded);
+        }
+
+        let duration = kind.duration_ticks();
+        let stacking = kind.stacking();
+        if stacking == PowerUpStacking::Instant {
+            return;
+        }
+
+        if let Some(active) = self.active.iter_mut().find(|p| p.kind == kind) {
+            active.ticks_remaining = match stacking {
+                PowerUpStacking::Extend { max_ticks } => {
+                    (active.ticks_remaining + duration).min(max_ticks)
+                }
+                _ => duration,
+            };
+        } else {
+            self.active.push(ActivePowerUp {
+                kind,
+                ticks_remaining: duration,
+            });
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.active.clear();
+        self.laser_cooldown_ticks = 0;
+    }
+
+    pub fn ball_speed_scale(&self) -> f64 {
+        if self.is_active(PowerUpKind::SlowBall) {
+            POWERUP_SLOW_BALL_SCALE
+        } else {
+            1.0
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SpawnPowerUpEvent {
+    pub position: Vector2d,
+    pub kind: PowerUpKind,
+}
+
+#[derive(Debug)]
+pub struct PowerUpCapsuleComponent {
+    pub kind: PowerUpKind,
+}
+
+impl Component for PowerUpCapsuleComponent {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Default, Debug)]
+pub struct LaserBoltComponent;
+
+impl Component for LaserBoltComponent {
+    type Storage = NullStorage<Self>;
+}
+
+// Capsules and laser bolts only need to overlap solid objects, never the balls or each other
+fn powerup_collision_groups(membership: usize) -> ncollide2d::pipeline::CollisionGroups {
+    ncollide2d::pipeline::CollisionGroups::new()
+        .with_membership(&[membership])
+        .with_blacklist(&[0, 2, 3])
+}
+
+#[derive(Default)]
+pub struct SpawnPowerUpSystem {
+    spawn_powerup_event_reader: Option<ReaderId<SpawnPowerUpEvent>>,
+}
+
+impl<'a> System<'a> for SpawnPowerUpSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, LazyUpdate>,
+        ReadExpect<'a, TextureAtlas>,
+        Read<'a, EventChannel<SpawnPowerUpEvent>>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.spawn_powerup_event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<SpawnPowerUpEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(&mut self, (ents, lazy_updater, atlas, spawn_powerup_events): Self::SystemData) {
+        for event in
+            spawn_powerup_events.read(&mut self.spawn_powerup_event_reader.as_mut().unwrap())
+        {
+            let ent = ents.create();
+
+            lazy_updater.insert(
+                ent,
+                TransformComponent::new(
+                    event.position,
+                    Point2f::new(
+                        POWERUP_CAPSULE_WIDTH as f32 / 2.0,
+                        POWERUP_CAPSULE_HEIGHT as f32 / 2.0,
+                    ),
+                    Vector2f::new(1.0, 1.0),
+                ),
+            );
+
+            lazy_updater.insert(
+                ent,
+                SpriteComponent {
+                    color: COLOR_WHITE,
+                    spritesheet_tex_id: atlas.tex_id(),
+                    region: atlas.sprite(event.kind.sprite_name()),
+                    layer: 2,
+                    transparency: Transparency::Opaque,
+                },
+            );
+
+            lazy_updater.insert(ent, PowerUpCapsuleComponent { kind: event.kind });
+
+            let linear_velocity = Vector2d::new(0.0, POWERUP_CAPSULE_FALL_SPEED);
+            lazy_updater.insert(
+                ent,
+                RigidbodyComponent::new(
+                    1.0,
+                    linear_velocity,
+                    POWERUP_CAPSULE_FALL_SPEED,
+                    BodyStatus::Dynamic,
+                ),
+            );
+
+            lazy_updater.insert(
+                ent,
+                ColliderComponent::new_sensor(
+                    Cuboid::new(Vector2::new(
+                        (POWERUP_CAPSULE_WIDTH as f64 / 2.0) * WORLD_UNIT_RATIO,
+                        (POWERUP_CAPSULE_HEIGHT as f64 / 2.0) * WORLD_UNIT_RATIO,
+                    )),
+                    Vector2::zeros(),
+                    powerup_collision_groups(2),
+                ),
+            );
+
+            println!("[SpawnPowerUpSystem] Spawned {:?} capsule", event.kind);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct PowerUpPickupSystem {
+    collision_event_reader: Option<ReaderId<CollisionEvent>>,
+}
+
+impl<'a> System<'a> for PowerUpPickupSystem {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, LevelState>,
+        Write<'a, PowerUpState>,
+        Read<'a, EventChannel<CollisionEvent>>,
+        Write<'a, EventChannel<SpawnBallEvent>>,
+        ReadStorage<'a, PowerUpCapsuleComponent>,
+        ReadStorage<'a, PlayerPaddleComponent>,
+        ReadStorage<'a, BallComponent>,
+        ReadStorage<'a, TransformComponent>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.collision_event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<CollisionEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(
+        &mut self,
+        (
+            ents,
+            mut level,
+            mut powerups,
+            collision_events,
+            mut spawn_ball_events,
+            capsules,
+            paddles,
+            balls,
+            transforms,
+        ): Self::SystemData,
+    ) {
+        let mut capsules_caught_this_tick: BitSet = BitSet::new();
+        for event in collision_events.read(&mut self.collision_event_reader.as_mut().unwrap()) {
+            // Get the entities involved in the event, ignoring it entirely if either of them are not an entity
+            let (entity_a, entity_b) = {
+                if event.entity_a.is_none() || event.entity_b.is_none() {
+                    continue;
+                }
+
+                (event.entity_a.unwrap(), event.entity_b.unwrap())
+            };
+
+            let capsule = match capsules.get(entity_a) {
+                Some(capsule) => capsule,
+                None => continue,
+            };
+
+            if paddles.get(entity_b).is_none() || capsules_caught_this_tick.contains(entity_a.id())
+            {
+                continue;
+            }
+
+            capsules_caught_this_tick.add(entity_a.id());
+            ents.delete(entity_a).expect("Failed to delete capsule!");
+
+            println!("Caught {:?} power-up", capsule.kind);
+            match capsule.kind {
+                PowerUpKind::ExtraLife => {
+                    level.lives += 1;
+                }
+                PowerUpKind::MultiBall => {
+                    // Every ball in play splits, fanned out around its current direction
+                    for (ball, transform) in (&balls, &transforms).join() {
+                        spawn_ball_events.iter_write(ball.split(
+                            transform.position,
+                            POWERUP_MULTI_BALL_SPLIT_COUNT,
+                            POWERUP_MULTI_BALL_SPREAD_RADIANS,
+                            level.ball_speed,
+                        ));
+                    }
+                }
+                kind => powerups.activate(kind),
+            }
+        }
+
+        // Remove capsules that fell past the paddle
+        for (ent, _, transform) in (&ents, &capsules, &transforms).join() {
+            if transform.position.y > level.level_height as f64 + POWERUP_CAPSULE_HEIGHT as f64 {
+                ents.delete(ent).expect("Failed to delete capsule!");
+            }
+        }
+    }
+}
+
+/// Counts down active power-ups, expires them, and applies their ongoing effects to the paddle.
+pub struct PowerUpEffectSystem;
+
+impl<'a> System<'a> for PowerUpEffectSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, LazyUpdate>,
+        ReadExpect<'a, TextureAtlas>,
+        Read<'a, InputState>,
+        Write<'a, PowerUpState>,
+        WriteStorage<'a, PlayerPaddleComponent>,
+        WriteStorage<'a, TransformComponent>,
+        WriteStorage<'a, ColliderComponent>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            ents,
+            lazy_updater,
+            atlas,
+            input,
+            mut powerups,
+            mut paddles,
+            mut transforms,
+            mut colliders,
+        ): Self::SystemData,
+    ) {
+        for active in powerups.active.iter_mut() {
+            active.ticks_remaining = active.ticks_remaining.saturating_sub(1);
+            if active.ticks_remaining == 0 {
+                println!("{:?} power-up expired", active.kind);
+            }
+        }
+
+        powerups.active.retain(|p| p.ticks_remaining > 0);
+        powerups.laser_cooldown_ticks = powerups.laser_cooldown_ticks.saturating_sub(1);
+
+        let is_wide = powerups.is_active(PowerUpKind::WidePaddle);
+        let is_laser = powerups.is_active(PowerUpKind::Laser);
+        for (ent, paddle, transform) in (&ents, &mut paddles, &mut transforms).join() {
+            // Resize the paddle if its width is out of date. The collider is only touched when needed, since modifying it rebuilds it.
+            let width_scale = if is_wide {
+                POWERUP_WIDE_PADDLE_SCALE
+            } else {
+                1.0
+            };
+            let hit_box_width = paddle::PADDLE_HIT_BOX_WIDTH * width_scale;
+            if (paddle.hit_box_width - hit_box_width).abs() > std::f64::EPSILON {
+                paddle.hit_box_width = hit_box_width;
+                transform.scale.x = paddle::PADDLE_SCALE_X * width_scale as f32;
+
+                if let Some(collider) = colliders.get_mut(ent) {
+                    collider.shape = ShapeHandle::new(Cuboid::new(Vector2::new(
+                        (hit_box_width / 2.0) * WORLD_UNIT_RATIO,
+                        (paddle::PADDLE_HIT_BOX_HEIGHT / 2.0) * WORLD_UNIT_RATIO,
+                    )));
+                }
+            }
+
+            // Fire a pair of laser bolts from the edges of the paddle
+            if is_laser
+                && paddle.held_ball_ent.is_none()
+                && powerups.laser_cooldown_ticks == 0
+                && input.is_action_pressed(InputAction::Launch)
+            {
+                powerups.laser_cooldown_ticks = LASER_COOLDOWN_TICKS;
+
+                let half_width = paddle.hit_box_width / 2.0 - LASER_BOLT_WIDTH as f64;
+                for offset_x in [-half_width, half_width].iter() {
+                    spawn_laser_bolt(
+                        &ents,
+                        &lazy_updater,
+                        &atlas,
+                        transform.position
+                            + Vector2d::new(*offset_x, -paddle::PADDLE_HIT_BOX_HEIGHT),
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn spawn_laser_bolt(
+    ents: &Entities,
+    lazy_updater: &LazyUpdate,
+    atlas: &TextureAtlas,
+    position: Vector2d,
+) {
+    let ent = ents.create();
+
+    lazy_updater.insert(
+        ent,
+        TransformComponent::new(
+            position,
+            Point2f::new(
+                LASER_BOLT_WIDTH as f32 / 2.0,
+                LASER_BOLT_HEIGHT as f32 / 2.0,
+            ),
+            Vector2f::new(1.0, 1.0),
+        ),
+    );
+
+    lazy_updater.insert(
+        ent,
+        SpriteComponent {
+            color: COLOR_WHITE,
+            spritesheet_tex_id: atlas.tex_id(),
+            region: atlas.sprite("laser_bolt"),
+            layer: 2,
+            transparency: Transparency::Opaque,
+        },
+    );
+
+    lazy_updater.insert(ent, LaserBoltComponent);
+
+    lazy_updater.insert(
+        ent,
+        RigidbodyComponent::new(
+            1.0,
+            Vector2d::new(0.0, -LASER_BOLT_SPEED),
+            LASER_BOLT_SPEED,
+            BodyStatus::Dynamic,
+        ),
+    );
+
+    lazy_updater.insert(
+        ent,
+        ColliderComponent::new_sensor(
+            Cuboid::new(Vector2::new(
+                (LASER_BOLT_WIDTH as f64 / 2.0) * WORLD_UNIT_RATIO,
+                (LASER_BOLT_HEIGHT as f64 / 2.0) * WORLD_UNIT_RATIO,
+            )),
+            Vector2::zeros(),
+            powerup_collision_groups(3),
+        ),
+    );
+}
+
+/// Removes laser bolts once they hit a brick or leave the top of the level. The damage itself is handled by `BrickSystem`.
+#[derive(Default)]
+pub struct LaserBoltSystem {
+    collision_event_reader: Option<ReaderId<CollisionEvent>>,
+}
+
+impl<'a> System<'a> for LaserBoltSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, EventChannel<CollisionEvent>>,
+        ReadStorage<'a, LaserBoltComponent>,
+        ReadStorage<'a, BrickComponent>,
+        ReadStorage<'a, TransformComponent>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.collision_event_reader = Some(
+            world
+                .fetch_mut::<EventChannel<CollisionEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(&mut self, (ents, collision_events, bolts, bricks, transforms): Self::SystemData) {
+        let mut bolts_hit_this_tick: BitSet = BitSet::new();
+        for event in collision_events.read(&mut self.collision_event_reader.as_mut().unwrap()) {
+            let (entity_a, entity_b) = {
+                if event.entity_a.is_none() || event.entity_b.is_none() {
+                    continue;
+                }
+
+                (event.entity_a.unwrap(), event.entity_b.unwrap())
+            };
+
+            if bolts.get(entity_a).is_some()
+                && bricks.get(entity_b).is_some()
+                && !bolts_hit_this_tick.contains(entity_a.id())
+            {
+                bolts_hit_this_tick.add(entity_a.id());
+                ents.delete(entity_a).expect("Failed to delete laser bolt!");
+            }
+        }
+
+        for (ent, _, transform) in (&ents, &bolts, &transforms).join() {
+            if transform.position.y < -(LASER_BOLT_HEIGHT as f64)
+                && !bolts_hit_this_tick.contains(ent.id())
+            {
+                ents.delete(ent).expect("Failed to delete laser bolt!");
+            }
+        }
+    }
+}
@@ -1,8 +1,16 @@
 use crate::game::{
+    assets::Assets,
+    flow::{self, FlowState},
+    font::{BitmapFont, TextAlign},
+    level::LevelState,
     physics::{PhysicsState, RigidbodyComponent},
+    powerup::PowerUpState,
+    settings::Settings,
     transform::TransformComponent,
+    ui::{Ui, UiFocus, UiInput, UiPainter},
 };
 use gfx::{
+    atlas::TextureAtlas,
     color::*,
     renderer::{Renderable, TextureId, Transparency},
     sprite::*,
@@ -10,6 +18,16 @@ use gfx::{
 };
 use specs::prelude::*;
 
+// Names of assets in the asset manifest
+pub const SPRITES_TEXTURE: &str = "sprites";
+pub const HUD_FONT: &str = "default";
+
+pub const SPRITE_ATLAS_PATH: &str = "res/textures/sprites.atlas";
+
+// How far the field shakes (in pixels) and for how many ticks, when a brick breaks and when a ball is lost
+pub const SCREEN_SHAKE_BRICK_BREAK: (f32, u32) = (1.0, 6);
+pub const SCREEN_SHAKE_BALL_DEATH: (f32, u32) = (4.0, 20);
+
 #[derive(Default)]
 pub struct RenderState {
     commands: Vec<gfx::renderer::RenderCommand>,
@@ -66,29 +84,34 @@ impl RenderState {
         });
     }
 
-    pub fn text(&mut self, x: f32, y: f32, w: u32, h: u32, scale: f32, text: &str) {
-        let cols: u32 = 16;
-        for (i, c) in text.chars().enumerate() {
-            let ascii: u8 = c as u8;
-            let sprite_col: u32 = ascii as u32 % cols;
-            let sprite_row: u32 = ascii as u32 / cols;
+    /// Draw text starting at a position. The font's texture is used rather than the bound one.
+    pub fn text(&mut self, font: &BitmapFont, x: f32, y: f32, scale: f32, text: &str) {
+        self.text_aligned(font, x, y, scale, TextAlign::Left, text);
+    }
+
+    /// Draw text aligned to a position, see `BitmapFont::layout`.
+    pub fn text_aligned(
+        &mut self,
+        font: &BitmapFont,
+        x: f32,
+        y: f32,
+        scale: f32,
+        align: TextAlign,
+        text: &str,
+    ) {
+        for glyph in font.layout(text, x, y, scale, align) {
             self.commands.push(gfx::renderer::RenderCommand {
                 transparency: self.bound_transparency,
                 shader_program_id: 1,
-                tex_id: self.bound_texture_id,
+                tex_id: font.tex_id(),
                 layer: self.bound_layer,
                 data: Renderable::Sprite {
-                    x: x + (i as f32 * (w as f32 * scale)),
-                    y: y,
+                    x: glyph.x,
+                    y: glyph.y,
                     origin: Point2f::origin(),
                     scale: Vector2f::new(scale, scale),
                     color: self.bound_color,
-                    region: SpriteRegion {
-                        x: sprite_col * w,
-                        y: sprite_row * h,
-                        w,
-                        h,
-                    },
+                    region: glyph.region,
                 },
             });
         }
@@ -129,6 +152,52 @@ impl RenderState {
     }
 }
 
+/// Shakes the field's sprites, fading out over the length of the shake. Only drawn when the screen shake setting
+/// is on.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ScreenShake {
+    strength: f32,
+    ticks: u32,
+    ticks_remaining: u32,
+}
+
+impl ScreenShake {
+    /// Start shaking with a strength and length, unless a stronger shake is already going.
+    pub fn shake(&mut self, (strength, ticks): (f32, u32)) {
+        if strength >= self.current_strength() {
+            self.strength = strength;
+            self.ticks = ticks;
+            self.ticks_remaining = ticks;
+        }
+    }
+
+    /// How far the field is moved. Follows a fixed pattern rather than the rng, so shaking doesn't change runs.
+    pub fn offset(&self) -> Vector2f {
+        let strength = self.current_strength();
+        let t = self.ticks_remaining as f32;
+        Vector2f::new((t * 2.3).sin() * strength, (t * 3.7).cos() * strength)
+    }
+
+    fn current_strength(&self) -> f32 {
+        if self.ticks == 0 {
+            return 0.0;
+        }
+
+        self.strength * self.ticks_remaining as f32 / self.ticks as f32
+    }
+}
+
+/// Counts down the screen shake, once the systems that start shakes have run.
+pub struct ScreenShakeSystem;
+
+impl<'a> System<'a> for ScreenShakeSystem {
+    type SystemData = Write<'a, ScreenShake>;
+
+    fn run(&mut self, mut shake: Self::SystemData) {
+        shake.ticks_remaining = shake.ticks_remaining.saturating_sub(1);
+    }
+}
+
 #[derive(Debug)]
 pub struct SpriteComponent {
     pub color: Color,
@@ -148,13 +217,24 @@ pub struct SpriteRenderSystem;
 impl<'a> System<'a> for SpriteRenderSystem {
     type SystemData = (
         ReadExpect<'a, PhysicsState>,
+        Read<'a, ScreenShake>,
+        Read<'a, Settings>,
         Write<'a, RenderState>,
         ReadStorage<'a, TransformComponent>,
         ReadStorage<'a, SpriteComponent>,
         ReadStorage<'a, RigidbodyComponent>,
     );
 
-    fn run(&mut self, (physics, mut render, transforms, sprites, rigidbodies): Self::SystemData) {
+    fn run(
+        &mut self,
+        (physics, shake, settings, mut render, transforms, sprites, rigidbodies): Self::SystemData,
+    ) {
+        let offset = if settings.screen_shake {
+            shake.offset()
+        } else {
+            Vector2f::zeros()
+        };
+
         for (transform, sprite, rigidbody) in (&transforms, &sprites, (&rigidbodies).maybe()).join()
         {
             let (x, y) = if let Some(_) = rigidbody {
@@ -172,8 +252,8 @@ impl<'a> System<'a> for SpriteRenderSystem {
             render.bind_color(sprite.color);
             render.bind_layer(sprite.layer);
             render.sprite(
-                x as f32,
-                y as f32,
+                x as f32 + offset.x,
+                y as f32 + offset.y,
                 transform.origin,
                 transform.scale,
                 sprite.region,
@@ -181,3 +261,68 @@ impl<'a> System<'a> for SpriteRenderSystem {
         }
     }
 }
+
+/// Draw everything besides the entity sprites: the HUD, the UI for the current flow state and the background.
+/// The FPS counter is only drawn when `fps` is given.
+pub fn draw_hud(world: &World, fps: Option<u32>, screen_width: u32, screen_height: u32) {
+    let flow = *world.read_resource::<FlowState>();
+    let level = world.read_resource::<LevelState>();
+    let assets = world.read_resource::<Assets>();
+    let atlas = world.read_resource::<TextureAtlas>();
+    let mut render = world.write_resource::<RenderState>();
+
+    // A missing font draws nothing
+    let missing_font = BitmapFont::default();
+    let font = assets
+        .font(HUD_FONT)
+        .map_or(&missing_font, |font| assets.bitmap_font(font));
+
+    // The HUD has nothing to focus, so it doesn't keep its focus around
+    let mut hud_focus = UiFocus::default();
+    let painter = UiPainter {
+        render: &mut render,
+        font,
+        atlas: &atlas,
+    };
+    let mut hud = Ui::new(&mut hud_focus, UiInput::default(), Some(painter));
+    let right_edge = screen_width as f32 - 2.0;
+
+    // FPS text
+    if let Some(fps) = fps {
+        hud.list(0.0, 2.0, right_edge, TextAlign::Right);
+        hud.label(&format!("FPS: {}", fps));
+    }
+
+    if flow.shows_field() {
+        // Score and balls text
+        hud.list(2.0, 2.0, 0.0, TextAlign::Left);
+        hud.label(&format!("Score: {}", level.score));
+        hud.label(&format!("Balls: {}", level.lives));
+
+        // Active power-ups text, under the FPS counter
+        hud.list(0.0, 10.0, right_edge, TextAlign::Right);
+        let powerups = world.read_resource::<PowerUpState>();
+        for active in powerups.active.iter() {
+            hud.label(&format!(
+                "{} {}s",
+                active.kind.name(),
+                (active.ticks_remaining + 59) / 60
+            ));
+        }
+    }
+
+    // UI for the current flow state (title screen, pause menu, etc.)
+    let painter = UiPainter {
+        render: &mut render,
+        font,
+        atlas: &atlas,
+    };
+    flow::draw_ui(world, painter, screen_width, screen_height);
+
+    // Background
+    render.bind_color(COLOR_WHITE);
+    render.bind_layer(0);
+    render.bind_transparency(Transparency::Opaque);
+    render.bind_texture(assets.tex_id(&level.background));
+    render.textured_quad((0.0, 400.0), (400.0, 400.0), (0.0, 0.0), (400.0, 0.0));
+}
@@ -0,0 +1,398 @@
+use crate::game::{high_score::HighScoreTable, settings::Settings, GameState};
+use gfx::input::{
+    self, GamepadAxis, GamepadButton, InputBindings, InputState, VirtualGamepad, VirtualKeyCode,
+};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+pub const REPLAY_FAST_FORWARD_TICKS: u32 = 8;
+
+// The first line of a replay file, so future format changes can be detected
+const REPLAY_FILE_HEADER: &str = "brickbonker-replay 1";
+const REPLAY_NO_KEYS: &str = "-";
+
+/// Everything besides input that needs to match for a replay to play back the same way it was recorded.
+#[derive(Clone, Debug, Default)]
+pub struct ReplayHeader {
+    pub seed: u64,
+    pub start_level_id: Option<String>,
+    pub high_scores: Vec<u32>,
+
+    // Recorded keys only mean the same thing with the same bindings
+    pub bindings: InputBindings,
+}
+
+impl ReplayHeader {
+    /// Set up a newly created game (made with the same seed) to match the game this replay was recorded from.
+    pub fn apply(&self, game: &mut GameState) {
+        if let Some(level_id) = &self.start_level_id {
+            game.start_game(level_id);
+        }
+
+        // Replays can't change the real high score table, but need the same scores to qualify the same way
+        *game.world.write_resource::<HighScoreTable>() =
+            HighScoreTable::from_scores(&self.high_scores);
+
+        // Nor the real settings, which would otherwise be saved with the replay's bindings
+        let mut settings = game.world.write_resource::<Settings>();
+        settings.key_bindings = self.bindings.clone();
+        settings.path = None;
+    }
+}
+
+/// The input for a tick where at least one key or gamepad button was pressed or released, or a stick moved.
+/// Ticks without a frame hold the same keys, and leave the gamepad the same, as the frame before them.
+#[derive(Clone, Debug)]
+pub struct ReplayFrame {
+    pub tick: u64,
+    pub held: Vec<VirtualKeyCode>,
+    pub pressed: Vec<VirtualKeyCode>,
+    pub released: Vec<VirtualKeyCode>,
+
+    // The gamepad as it was read, with the axes before the deadzone is applied
+    pub gamepad_buttons: Vec<GamepadButton>,
+    pub gamepad_axes: Vec<(GamepadAxis, f32)>,
+}
+
+#[derive(Debug)]
+pub struct ReplayParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ReplayParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayLoadError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, ReplayParseError),
+}
+
+impl fmt::Display for ReplayLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayLoadError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            ReplayLoadError::Parse(path, e) => write!(f, "{}:{}", path.display(), e),
+        }
+    }
+}
+
+/// Writes the input of every tick to a replay file as the game runs.
+pub struct ReplayRecorder {
+    file: File,
+    tick: u64,
+
+    // The gamepad as last written, so a line is only written when it changes
+    gamepad_buttons: String,
+    gamepad_axes: String,
+}
+
+impl ReplayRecorder {
+    pub fn create(path: &Path, header: &ReplayHeader) -> io::Result<Self> {
+        let mut file = File::create(path)?;
+        writeln!(file, "{}", REPLAY_FILE_HEADER)?;
+        writeln!(file, "seed: {}", header.seed)?;
+        if let Some(level_id) = &header.start_level_id {
+            writeln!(file, "level: {}", level_id)?;
+        }
+
+        let high_scores: Vec<String> = header.high_scores.iter().map(|s| s.to_string()).collect();
+        writeln!(file, "high_scores: {}", high_scores.join(" "))?;
+        for binding in header.bindings.to_bindings() {
+            writeln!(file, "bind: {}", binding)?;
+        }
+        for binding in header.bindings.to_gamepad_bindings() {
+            writeln!(file, "bind_gamepad: {}", binding)?;
+        }
+        writeln!(file, "inputs:")?;
+
+        Ok(ReplayRecorder {
+            file,
+            tick: 0,
+            gamepad_buttons: REPLAY_NO_KEYS.to_owned(),
+            gamepad_axes: REPLAY_NO_KEYS.to_owned(),
+        })
+    }
+
+    /// Record the input for the next tick. Lines are written as they happen, so the replay survives the game
+    /// being closed at any point.
+    pub fn record(&mut self, input: &InputState) -> io::Result<()> {
+        let tick = self.tick;
+        self.tick += 1;
+
+        let pressed = key_list(input.pressed_keys());
+        let released = key_list(input.released_keys());
+        let gamepad_buttons = name_list(input.gamepad_buttons().map(GamepadButton::name));
+        let gamepad_axes = name_list(
+            input
+                .gamepad_axes()
+                .map(|(axis, value)| format!("{}={}", axis.name(), value)),
+        );
+        let gamepad_changed =
+            gamepad_buttons != self.gamepad_buttons || gamepad_axes != self.gamepad_axes;
+        if pressed == REPLAY_NO_KEYS && released == REPLAY_NO_KEYS && !gamepad_changed {
+            return Ok(());
+        }
+
+        writeln!(
+            self.file,
+            "{} {} {} {} {} {}",
+            tick,
+            key_list(input.held_keys()),
+            pressed,
+            released,
+            gamepad_buttons,
+            gamepad_axes
+        )?;
+        self.gamepad_buttons = gamepad_buttons;
+        self.gamepad_axes = gamepad_axes;
+        Ok(())
+    }
+}
+
+fn key_list(keys: impl Iterator<Item = VirtualKeyCode>) -> String {
+    name_list(keys.filter_map(input::key_name))
+}
+
+fn name_list<T: AsRef<str> + Ord>(names: impl Iterator<Item = T>) -> String {
+    let mut names: Vec<T> = names.collect();
+    if names.is_empty() {
+        return REPLAY_NO_KEYS.to_owned();
+    }
+
+    // Sorted, so the same input always writes the same line
+    names.sort();
+    let names: Vec<&str> = names.iter().map(|name| name.as_ref()).collect();
+    names.join(",")
+}
+
+pub struct Replay {
+    pub header: ReplayHeader,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self, ReplayLoadError> {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => return Err(ReplayLoadError::Io(path.to_owned(), e)),
+        };
+
+        Replay::parse(&source).map_err(|e| ReplayLoadError::Parse(path.to_owned(), e))
+    }
+
+    pub fn parse(source: &str) -> Result<Self, ReplayParseError> {
+        let mut lines = source
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()));
+
+        match lines.next() {
+            Some((_, REPLAY_FILE_HEADER)) => {}
+            _ => {
+                return Err(parse_error(
+                    1,
+                    "Not a replay file, or an unsupported version",
+                ))
+            }
+        }
+
+        // Header section
+        let mut header = ReplayHeader::default();
+        let mut found_seed = false;
+        let mut found_inputs = false;
+        for (line_num, line) in lines.by_ref() {
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = match line.find(':') {
+                Some(separator) => (line[..separator].trim(), line[separator + 1..].trim()),
+                None => return Err(parse_error(line_num, "Expected `key: value`")),
+            };
+
+            match key {
+                "inputs" => {
+                    found_inputs = true;
+                    break;
+                }
+                "seed" => {
+                    header.seed = value
+                        .parse()
+                        .map_err(|_| parse_error(line_num, "Expected a number for `seed`"))?;
+                    found_seed = true;
+                }
+                "level" => header.start_level_id = Some(value.to_owned()),
+                "high_scores" => {
+                    header.high_scores = value
+                        .split_whitespace()
+                        .map(|score| score.parse())
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| parse_error(line_num, "Expected a list of scores"))?;
+                }
+                "bind" => header
+                    .bindings
+                    .parse_binding(value)
+                    .map_err(|message| parse_error(line_num, &message))?,
+                "bind_gamepad" => header
+                    .bindings
+                    .parse_gamepad_binding(value)
+                    .map_err(|message| parse_error(line_num, &message))?,
+                _ => return Err(parse_error(line_num, &format!("Unknown key `{}`", key))),
+            }
+        }
+
+        if !found_seed || !found_inputs {
+            return Err(parse_error(
+                source.lines().count(),
+                "Missing `seed` or `inputs:` section",
+            ));
+        }
+
+        // Input frames, one per line: tick, held keys, pressed keys, released keys, and then the gamepad's held
+        // buttons and axes
+        let mut frames: Vec<ReplayFrame> = Vec::new();
+        for (line_num, line) in lines {
+            if line.is_empty() {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 6 {
+                return Err(parse_error(
+                    line_num,
+                    "Expected `tick held pressed released buttons axes`",
+                ));
+            }
+
+            let tick: u64 = fields[0]
+                .parse()
+                .map_err(|_| parse_error(line_num, "Expected a tick number"))?;
+            if frames.last().map_or(false, |last| tick <= last.tick) {
+                return Err(parse_error(line_num, "Ticks must be in increasing order"));
+            }
+
+            frames.push(ReplayFrame {
+                tick,
+                held: parse_key_list(fields[1], line_num)?,
+                pressed: parse_key_list(fields[2], line_num)?,
+                released: parse_key_list(fields[3], line_num)?,
+                gamepad_buttons: parse_button_list(fields[4], line_num)?,
+                gamepad_axes: parse_axis_list(fields[5], line_num)?,
+            });
+        }
+
+        Ok(Replay { header, frames })
+    }
+}
+
+fn parse_key_list(list: &str, line: usize) -> Result<Vec<VirtualKeyCode>, ReplayParseError> {
+    if list == REPLAY_NO_KEYS {
+        return Ok(Vec::new());
+    }
+
+    list.split(',')
+        .map(|name| {
+            input::key_from_name(name)
+                .ok_or_else(|| parse_error(line, &format!("Unknown key `{}`", name)))
+        })
+        .collect()
+}
+
+fn parse_button_list(list: &str, line: usize) -> Result<Vec<GamepadButton>, ReplayParseError> {
+    if list == REPLAY_NO_KEYS {
+        return Ok(Vec::new());
+    }
+
+    list.split(',')
+        .map(|name| {
+            GamepadButton::from_name(name)
+                .ok_or_else(|| parse_error(line, &format!("Unknown gamepad button `{}`", name)))
+        })
+        .collect()
+}
+
+/// Parse a list of axes in the form `LeftStickX=0.5,LeftStickY=-1`.
+fn parse_axis_list(list: &str, line: usize) -> Result<Vec<(GamepadAxis, f32)>, ReplayParseError> {
+    if list == REPLAY_NO_KEYS {
+        return Ok(Vec::new());
+    }
+
+    list.split(',')
+        .map(|entry| {
+            let separator = entry.find('=').ok_or_else(|| {
+                parse_error(line, &format!("Expected `axis=value`, found `{}`", entry))
+            })?;
+            let (name, value) = (&entry[..separator], &entry[separator + 1..]);
+            let axis = GamepadAxis::from_name(name)
+                .ok_or_else(|| parse_error(line, &format!("Unknown gamepad axis `{}`", name)))?;
+            let value = value
+                .parse()
+                .map_err(|_| parse_error(line, &format!("Invalid value for `{}`", name)))?;
+            Ok((axis, value))
+        })
+        .collect()
+}
+
+fn parse_error(line: usize, message: &str) -> ReplayParseError {
+    ReplayParseError {
+        line,
+        message: message.to_owned(),
+    }
+}
+
+/// Produces the recorded input for each tick of a replay, in order.
+pub struct ReplayPlayer {
+    frames: Vec<ReplayFrame>,
+    next_frame: usize,
+    tick: u64,
+    input: InputState,
+    gamepad: VirtualGamepad,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayer {
+            frames: replay.frames,
+            next_frame: 0,
+            tick: 0,
+            input: InputState::new(),
+            gamepad: VirtualGamepad::new(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_frame >= self.frames.len()
+    }
+
+    /// Get the input for the next tick, or `None` once every recorded frame has been played.
+    pub fn next_input(&mut self) -> Option<&InputState> {
+        if self.is_finished() {
+            return None;
+        }
+
+        // The gamepad is read every tick as it was while recording, so its buttons are pressed and released the
+        // same way
+        self.input.clear_pressed_and_released();
+        let frame = &self.frames[self.next_frame];
+        if frame.tick == self.tick {
+            self.input
+                .set_keys(&frame.held, &frame.pressed, &frame.released);
+            self.gamepad
+                .set_state(&frame.gamepad_buttons, &frame.gamepad_axes);
+            self.next_frame += 1;
+        }
+        self.input.update_gamepad(&self.gamepad);
+
+        self.tick += 1;
+        Some(&self.input)
+    }
+}
@@ -0,0 +1,48 @@
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+/// The random number generator all gameplay systems draw from, so a run can be reproduced from its seed.
+///
+/// Systems that use it must have a fixed order in the dispatcher, otherwise the order they draw numbers in
+/// (and so the numbers they get) can change between runs.
+pub struct RngState {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl RngState {
+    pub fn new(seed: u64) -> Self {
+        RngState {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+// Used when no seed is given, for when reproducing the run doesn't matter
+impl Default for RngState {
+    fn default() -> Self {
+        RngState::new(rand::random())
+    }
+}
+
+impl RngCore for RngState {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
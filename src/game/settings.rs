@@ -0,0 +1,271 @@
+use crate::game::{
+    audio::{AudioBus, AudioMixer},
+    high_score::HIGH_SCORE_DATA_DIRECTORY,
+};
+use gfx::{input::InputBindings, window::WindowSettings};
+use specs::prelude::*;
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+pub const SETTINGS_FILE_NAME: &str = "settings.txt";
+
+// Range of the render scale on the options screen, and how far each press moves it
+pub const SETTINGS_RENDER_SCALE_MIN: f32 = 1.0;
+pub const SETTINGS_RENDER_SCALE_MAX: f32 = 4.0;
+pub const SETTINGS_RENDER_SCALE_STEP: f32 = 0.5;
+
+pub const SETTINGS_VOLUME_STEP: f32 = 0.1;
+
+// The first line of a settings file
+const SETTINGS_FILE_HEADER: &str = "// Brickbonker settings, see the options screen";
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(PathBuf, io::Error),
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            SettingsError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+/// The player's settings, changed from the options screen. Saved to `path` when the options screen is closed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    // How many times larger than the screen the window is
+    pub render_scale: f32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+
+    // Volumes from 0 to 1. The sound effects volume covers UI sounds too.
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+
+    pub key_bindings: InputBindings,
+    pub show_fps: bool,
+    pub screen_shake: bool,
+    pub path: Option<PathBuf>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            render_scale: 2.0,
+            fullscreen: false,
+            vsync: true,
+            master_volume: 1.0,
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+            key_bindings: InputBindings::default(),
+            show_fps: true,
+            screen_shake: true,
+            path: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Load the settings from the default location in the user's data directory.
+    pub fn load_default() -> Self {
+        let path = match default_settings_path() {
+            Some(path) => path,
+            None => {
+                eprintln!("Failed to find a data directory! Settings won't be saved.");
+                return Settings::default();
+            }
+        };
+
+        // Saving over a file that couldn't be read could destroy settings that are still in it
+        Settings::load(&path).unwrap_or_else(|e| {
+            eprintln!("Failed to load settings! Settings won't be saved. {}", e);
+            Settings::default()
+        })
+    }
+
+    /// Load settings from a file. A missing file has the default settings, and lines that can't be read are
+    /// skipped, so a damaged file loses as few settings as possible.
+    pub fn load(path: &Path) -> Result<Self, SettingsError> {
+        let settings = match fs::read_to_string(path) {
+            Ok(source) => {
+                let (settings, errors) = Settings::parse(path, &source);
+                for e in errors.iter() {
+                    eprintln!("Skipping invalid setting! {}", e);
+                }
+
+                settings
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Settings::default(),
+            Err(e) => return Err(SettingsError::Io(path.to_owned(), e)),
+        };
+
+        Ok(Settings {
+            path: Some(path.to_owned()),
+            ..settings
+        })
+    }
+
+    /// Parse a settings file, which is a list of `key: value` lines. Each `bind: <action> <keys>` line replaces the
+    /// keys bound to an action, see `InputBindings::parse_binding`, and `bind_gamepad` lines do the same for gamepad
+    /// inputs. Settings that aren't in the file keep their defaults, and lines starting with `//` are comments.
+    ///
+    /// Lines that can't be read are left out, and returned as errors along with the rest of the settings.
+    pub fn parse(path: &Path, source: &str) -> (Self, Vec<SettingsError>) {
+        let mut settings = Settings::default();
+        let mut errors = Vec::new();
+
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            if let Err(message) = settings.parse_line(line) {
+                errors.push(SettingsError::Parse {
+                    path: path.to_owned(),
+                    line: i + 1,
+                    message,
+                });
+            }
+        }
+
+        (settings, errors)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let separator = line
+            .find(':')
+            .ok_or_else(|| format!("Expected `key: value`, found `{}`", line))?;
+        let key = line[..separator].trim();
+        let value = line[separator + 1..].trim();
+
+        match key {
+            "render_scale" => {
+                let scale: f32 = parse_value(key, value)?;
+                self.render_scale = scale
+                    .max(SETTINGS_RENDER_SCALE_MIN)
+                    .min(SETTINGS_RENDER_SCALE_MAX);
+            }
+            "fullscreen" => self.fullscreen = parse_value(key, value)?,
+            "vsync" => self.vsync = parse_value(key, value)?,
+            "master_volume" => self.master_volume = parse_volume(key, value)?,
+            "music_volume" => self.music_volume = parse_volume(key, value)?,
+            "sfx_volume" => self.sfx_volume = parse_volume(key, value)?,
+            "show_fps" => self.show_fps = parse_value(key, value)?,
+            "screen_shake" => self.screen_shake = parse_value(key, value)?,
+            "bind" => self.key_bindings.parse_binding(value)?,
+            "bind_gamepad" => self.key_bindings.parse_gamepad_binding(value)?,
+            _ => return Err(format!("Unknown setting `{}`", key)),
+        }
+
+        Ok(())
+    }
+
+    /// The settings in the format read by `parse`.
+    pub fn to_source(&self) -> String {
+        let mut lines = vec![
+            SETTINGS_FILE_HEADER.to_owned(),
+            format!("render_scale: {}", self.render_scale),
+            format!("fullscreen: {}", self.fullscreen),
+            format!("vsync: {}", self.vsync),
+            format!("master_volume: {:.2}", self.master_volume),
+            format!("music_volume: {:.2}", self.music_volume),
+            format!("sfx_volume: {:.2}", self.sfx_volume),
+            format!("show_fps: {}", self.show_fps),
+            format!("screen_shake: {}", self.screen_shake),
+        ];
+
+        for binding in self.key_bindings.to_bindings() {
+            lines.push(format!("bind: {}", binding));
+        }
+        for binding in self.key_bindings.to_gamepad_bindings() {
+            lines.push(format!("bind_gamepad: {}", binding));
+        }
+
+        lines.join("\n") + "\n"
+    }
+
+    /// Write the settings to a temporary file first, so a crash mid-save can't destroy the old settings.
+    pub fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, self.to_source())?;
+        fs::rename(&temp_path, path)
+    }
+
+    pub fn window_settings(&self) -> WindowSettings {
+        WindowSettings {
+            render_scale: self.render_scale,
+            fullscreen: self.fullscreen,
+            vsync: self.vsync,
+        }
+    }
+
+    /// Set the mixer's volumes to the settings' volumes.
+    pub fn apply_volumes(&self, mixer: &mut AudioMixer) {
+        if mixer.master_volume() != self.master_volume {
+            mixer.set_master_volume(self.master_volume);
+        }
+
+        let bus_volumes = [
+            (AudioBus::Music, self.music_volume),
+            (AudioBus::Sfx, self.sfx_volume),
+            (AudioBus::Ui, self.sfx_volume),
+        ];
+        for (bus, volume) in bus_volumes.iter() {
+            if mixer.bus_volume(*bus) != *volume {
+                mixer.set_bus_volume(*bus, *volume);
+            }
+        }
+    }
+}
+
+pub fn default_settings_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(HIGH_SCORE_DATA_DIRECTORY).join(SETTINGS_FILE_NAME))
+}
+
+fn parse_value<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("Invalid `{}`: `{}`", key, value))
+}
+
+fn parse_volume(key: &str, value: &str) -> Result<f32, String> {
+    let volume: f32 = parse_value(key, value)?;
+    Ok(volume.max(0.0).min(1.0))
+}
+
+/// Keeps the mixer's volumes in line with the settings, so changes on the options screen are heard right away.
+pub struct SettingsSystem;
+
+impl<'a> System<'a> for SettingsSystem {
+    type SystemData = (Read<'a, Settings>, WriteExpect<'a, AudioMixer>);
+
+    fn run(&mut self, (settings, mut mixer): Self::SystemData) {
+        settings.apply_volumes(&mut mixer);
+    }
+}
@@ -0,0 +1,383 @@
+use crate::game::{
+    assets::Assets,
+    audio::{AudioBus, AudioMixer, VoiceHandle},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+pub const SOUND_CUES_PATH: &str = "res/audio/cues.txt";
+
+// Mixed into the game seed, so cues get their own reproducible numbers without drawing from the gameplay RngState
+const SOUND_CUE_SEED_SALT: u64 = 0x5eed_c0e5;
+
+#[derive(Debug)]
+pub enum SoundCueError {
+    Io(PathBuf, io::Error),
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for SoundCueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SoundCueError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            SoundCueError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CueClip {
+    // Name of a sound in the asset manifest
+    pub sound: String,
+    pub weight: f32,
+}
+
+/// A named group of clips, one of which is picked (with some variation) each time the cue is triggered.
+#[derive(Clone, Debug)]
+pub struct SoundCue {
+    pub name: String,
+    pub clips: Vec<CueClip>,
+    pub bus: AudioBus,
+    pub volume: (f32, f32),
+    pub pitch: (f32, f32),
+
+    // Never pick the same clip twice in a row
+    pub no_repeat: bool,
+
+    // Triggers within this many ticks of the last time the cue played are ignored
+    pub cooldown_ticks: u64,
+}
+
+impl SoundCue {
+    fn new(name: &str) -> Self {
+        SoundCue {
+            name: name.to_owned(),
+            clips: Vec::new(),
+            bus: AudioBus::Sfx,
+            volume: (1.0, 1.0),
+            pitch: (1.0, 1.0),
+            no_repeat: false,
+            cooldown_ticks: 0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct CueState {
+    last_clip: Option<usize>,
+    last_tick: Option<u64>,
+}
+
+/// Every sound cue, loaded from a cue file. Systems trigger cues by name rather than picking clips themselves.
+pub struct SoundCues {
+    cues: HashMap<String, SoundCue>,
+    states: HashMap<String, CueState>,
+    rng: StdRng,
+}
+
+impl SoundCues {
+    pub fn new(cues: Vec<SoundCue>, seed: u64) -> Self {
+        SoundCues {
+            cues: cues
+                .into_iter()
+                .map(|cue| (cue.name.clone(), cue))
+                .collect(),
+            states: HashMap::new(),
+            rng: StdRng::seed_from_u64(seed ^ SOUND_CUE_SEED_SALT),
+        }
+    }
+
+    pub fn load(path: &Path, seed: u64) -> Result<Self, SoundCueError> {
+        Ok(SoundCues::new(load_cue_file(path)?, seed))
+    }
+
+    /// Replace the cues with the ones in a cue file, e.g. after it was edited.
+    pub fn reload(&mut self, path: &Path) -> Result<(), SoundCueError> {
+        self.cues = load_cue_file(path)?
+            .into_iter()
+            .map(|cue| (cue.name.clone(), cue))
+            .collect();
+
+        Ok(())
+    }
+
+    pub fn cue(&self, name: &str) -> Option<&SoundCue> {
+        self.cues.get(name)
+    }
+
+    /// Play one of a cue's clips. Returns `None` if the cue is cooling down or nothing could be played.
+    pub fn trigger(
+        &mut self,
+        name: &str,
+        assets: &Assets,
+        mixer: &mut AudioMixer,
+    ) -> Option<VoiceHandle> {
+        let cue = match self.cues.get(name) {
+            Some(cue) => cue,
+            None => {
+                eprintln!("Failed to play sound cue! Cue `{}` does not exist!", name);
+                return None;
+            }
+        };
+
+        let state = self.states.entry(name.to_owned()).or_default();
+        if let Some(last_tick) = state.last_tick {
+            if mixer.tick() < last_tick + cue.cooldown_ticks {
+                return None;
+            }
+        }
+
+        let clip_index = pick_clip(cue, state.last_clip, &mut self.rng)?;
+        let volume = roll_range(cue.volume, &mut self.rng);
+        let pitch = roll_range(cue.pitch, &mut self.rng);
+        state.last_clip = Some(clip_index);
+        state.last_tick = Some(mixer.tick());
+
+        let clip = &cue.clips[clip_index];
+        match assets.sound(&clip.sound) {
+            Some(sound) => mixer.play_with(
+                &clip.sound,
+                assets.sound_data(sound),
+                cue.bus,
+                false,
+                volume,
+                pitch,
+            ),
+            None => {
+                eprintln!(
+                    "Failed to play sound cue `{}`! Sound `{}` was not loaded!",
+                    name, clip.sound
+                );
+                None
+            }
+        }
+    }
+}
+
+/// Pick a clip by weight, leaving out the last one picked if the cue doesn't allow repeats.
+fn pick_clip(cue: &SoundCue, last_clip: Option<usize>, rng: &mut StdRng) -> Option<usize> {
+    let is_allowed =
+        |index: usize| !(cue.no_repeat && cue.clips.len() > 1 && last_clip == Some(index));
+
+    let total_weight: f32 = (0..cue.clips.len())
+        .filter(|index| is_allowed(*index))
+        .map(|index| cue.clips[index].weight)
+        .sum();
+    if total_weight <= 0.0 {
+        return None;
+    }
+
+    let mut roll = rng.gen_range(0.0, total_weight);
+    let mut picked = None;
+    for (index, clip) in cue.clips.iter().enumerate() {
+        if !is_allowed(index) {
+            continue;
+        }
+
+        picked = Some(index);
+        if roll < clip.weight {
+            break;
+        }
+        roll -= clip.weight;
+    }
+
+    picked
+}
+
+fn roll_range((min, max): (f32, f32), rng: &mut StdRng) -> f32 {
+    if max > min {
+        rng.gen_range(min, max)
+    } else {
+        min
+    }
+}
+
+fn load_cue_file(path: &Path) -> Result<Vec<SoundCue>, SoundCueError> {
+    let source = fs::read_to_string(path).map_err(|e| SoundCueError::Io(path.to_owned(), e))?;
+    parse_cues(path, &source)
+}
+
+/// Parse the contents of a cue file.
+///
+/// Each cue starts with a `cue <name>` line, followed by `key: value` lines for its clips and properties.
+/// Lines starting with `//` are comments.
+pub fn parse_cues(path: &Path, source: &str) -> Result<Vec<SoundCue>, SoundCueError> {
+    let parse_error = |line: usize, message: String| SoundCueError::Parse {
+        path: path.to_owned(),
+        line,
+        message,
+    };
+
+    // Cues along with the line they start on
+    let mut cues: Vec<(SoundCue, usize)> = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let line_num = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("cue ") {
+            let name = name.trim();
+            if cues.iter().any(|(cue, _)| cue.name == name) {
+                return Err(parse_error(
+                    line_num,
+                    format!("Cue `{}` is defined twice", name),
+                ));
+            }
+
+            cues.push((SoundCue::new(name), line_num));
+            continue;
+        }
+
+        let cue = match cues.last_mut() {
+            Some((cue, _)) => cue,
+            None => {
+                return Err(parse_error(
+                    line_num,
+                    "Expected `cue <name>` first".to_owned(),
+                ))
+            }
+        };
+
+        let separator = match line.find(':') {
+            Some(separator) => separator,
+            None => {
+                return Err(parse_error(
+                    line_num,
+                    format!("Expected `key: value`, found `{}`", line),
+                ))
+            }
+        };
+        let key = line[..separator].trim();
+        let value = line[separator + 1..].trim();
+        let fields: Vec<&str> = value.split_whitespace().collect();
+
+        match key {
+            "clip" => {
+                let weight = match fields.get(1).map(|weight| weight.parse::<f32>()) {
+                    None => Some(1.0),
+                    Some(Ok(weight)) if weight >= 0.0 => Some(weight),
+                    _ => None,
+                };
+
+                match (fields.first(), weight) {
+                    (Some(sound), Some(weight)) if fields.len() <= 2 => cue.clips.push(CueClip {
+                        sound: (*sound).to_owned(),
+                        weight,
+                    }),
+                    _ => {
+                        return Err(parse_error(
+                            line_num,
+                            "Expected `clip: <sound> [weight]`".to_owned(),
+                        ))
+                    }
+                }
+            }
+            "bus" => {
+                cue.bus = match value {
+                    "music" => AudioBus::Music,
+                    "sfx" => AudioBus::Sfx,
+                    "ui" => AudioBus::Ui,
+                    _ => {
+                        return Err(parse_error(
+                            line_num,
+                            format!(
+                                "Expected `music`, `sfx` or `ui` for `bus`, found `{}`",
+                                value
+                            ),
+                        ))
+                    }
+                };
+            }
+            "volume" | "pitch" => {
+                let range = match parse_range(&fields) {
+                    Some(range) if key == "volume" || range.0 > 0.0 => range,
+                    _ => {
+                        return Err(parse_error(
+                            line_num,
+                            format!("Expected `{}: <min> [max]`, found `{}`", key, value),
+                        ))
+                    }
+                };
+
+                if key == "volume" {
+                    cue.volume = range;
+                } else {
+                    cue.pitch = range;
+                }
+            }
+            "no_repeat" => {
+                cue.no_repeat = match value {
+                    "true" => true,
+                    "false" => false,
+                    _ => {
+                        return Err(parse_error(
+                            line_num,
+                            format!(
+                                "Expected `true` or `false` for `no_repeat`, found `{}`",
+                                value
+                            ),
+                        ))
+                    }
+                };
+            }
+            "cooldown" => {
+                cue.cooldown_ticks = match value.parse::<u64>() {
+                    Ok(ticks) => ticks,
+                    Err(_) => {
+                        return Err(parse_error(
+                            line_num,
+                            format!(
+                                "Expected a number of ticks for `cooldown`, found `{}`",
+                                value
+                            ),
+                        ))
+                    }
+                };
+            }
+            _ => {
+                return Err(parse_error(
+                    line_num,
+                    format!("Unknown cue property `{}`", key),
+                ))
+            }
+        }
+    }
+
+    if let Some((cue, line_num)) = cues.iter().find(|(cue, _)| cue.clips.is_empty()) {
+        return Err(parse_error(
+            *line_num,
+            format!("Cue `{}` has no clips", cue.name),
+        ));
+    }
+
+    Ok(cues.into_iter().map(|(cue, _)| cue).collect())
+}
+
+/// Parse `<min> [max]`, where a single number means no variation.
+fn parse_range(fields: &[&str]) -> Option<(f32, f32)> {
+    let numbers = fields
+        .iter()
+        .map(|field| field.parse::<f32>())
+        .collect::<Result<Vec<f32>, _>>()
+        .ok()?;
+
+    match numbers.as_slice() {
+        [value] if *value >= 0.0 => Some((*value, *value)),
+        [min, max] if *min >= 0.0 && max >= min => Some((*min, *max)),
+        _ => None,
+    }
+}
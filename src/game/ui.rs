@@ -0,0 +1,381 @@
+use crate::game::{
+    font::{BitmapFont, TextAlign},
+    render::RenderState,
+};
+use gfx::{
+    atlas::TextureAtlas,
+    color::*,
+    input::{InputAction, InputState},
+    renderer::Transparency,
+    sprite::SpriteRegion,
+    Point2f, Vector2f,
+};
+
+// Widgets are drawn over the field, with their text on top of them
+pub const UI_LAYER: u8 = 3;
+pub const UI_TEXT_LAYER: u8 = 4;
+
+pub const UI_TEXT_SCALE: f32 = 0.5;
+
+// Sprites in the sprite atlas
+const UI_PANEL_SPRITE: &str = "ui/panel";
+const UI_BUTTON_SPRITE: &str = "ui/button";
+const UI_BUTTON_FOCUSED_SPRITE: &str = "ui/button/focused";
+const UI_CHECKBOX_SPRITE: &str = "ui/checkbox";
+const UI_CHECKBOX_CHECKED_SPRITE: &str = "ui/checkbox/checked";
+const UI_SLIDER_TRACK_SPRITE: &str = "ui/slider/track";
+const UI_SLIDER_KNOB_SPRITE: &str = "ui/slider/knob";
+
+// Size of the corners of nine-slice sprites, which aren't stretched
+const UI_PANEL_BORDER: u32 = 4;
+const UI_SLIDER_TRACK_BORDER: u32 = 1;
+
+// Space between the edge of a panel and the rows inside it
+const UI_PANEL_PADDING: f32 = 6.0;
+
+// Space above and below the text of a focusable row
+const UI_ROW_PADDING: f32 = 2.0;
+
+/// The navigation pressed this tick. Menus are moved around with the menu up and down actions and the move
+/// actions, and confirmed with the confirm action.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UiInput {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+    pub confirm: bool,
+}
+
+impl UiInput {
+    pub fn from_input(input: &InputState) -> Self {
+        UiInput {
+            up: input.is_action_pressed(InputAction::MenuUp),
+            down: input.is_action_pressed(InputAction::MenuDown),
+            left: input.is_action_pressed(InputAction::MoveLeft),
+            right: input.is_action_pressed(InputAction::MoveRight),
+            confirm: input.is_action_pressed(InputAction::Confirm),
+        }
+    }
+}
+
+/// Which row of a menu has focus. Kept between ticks, and reset whenever a different menu is shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UiFocus {
+    pub index: usize,
+}
+
+/// What widgets are drawn with.
+pub struct UiPainter<'a> {
+    pub render: &'a mut RenderState,
+    pub font: &'a BitmapFont,
+    pub atlas: &'a TextureAtlas,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UiRect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+/// An immediate-mode UI. Widgets are declared in order every time the menu is handled or drawn, and are stacked
+/// into vertical lists. Focusable widgets (buttons, checkboxes and sliders) are numbered in the order they're
+/// declared, and up and down move the focus between them.
+///
+/// A menu is built twice: during the tick with the tick's input and no painter, which is when widgets report
+/// being used, and when drawing with a painter and no input. Building both from the same function keeps what's
+/// drawn in line with what's handled.
+pub struct Ui<'a> {
+    focus: &'a mut UiFocus,
+    input: UiInput,
+    painter: Option<UiPainter<'a>>,
+    focusable_count: usize,
+
+    // The list rows are being added to
+    list_x: f32,
+    list_y: f32,
+    list_width: f32,
+    list_align: TextAlign,
+}
+
+impl<'a> Ui<'a> {
+    pub fn new(focus: &'a mut UiFocus, input: UiInput, painter: Option<UiPainter<'a>>) -> Self {
+        Ui {
+            focus,
+            input,
+            painter,
+            focusable_count: 0,
+            list_x: 0.0,
+            list_y: 0.0,
+            list_width: 0.0,
+            list_align: TextAlign::Left,
+        }
+    }
+
+    /// Start a list of rows at a position. Labels are aligned within the list's width.
+    pub fn list(&mut self, x: f32, y: f32, width: f32, align: TextAlign) {
+        self.list_x = x;
+        self.list_y = y;
+        self.list_width = width;
+        self.list_align = align;
+    }
+
+    /// Draw a panel, and start a list of rows inside it.
+    pub fn panel(&mut self, rect: UiRect) {
+        if let Some(painter) = self.painter.as_mut() {
+            nine_slice(painter, UI_PANEL_SPRITE, rect, UI_PANEL_BORDER);
+        }
+
+        self.list(
+            rect.x + UI_PANEL_PADDING,
+            rect.y + UI_PANEL_PADDING,
+            rect.w - UI_PANEL_PADDING * 2.0,
+            TextAlign::Center,
+        );
+    }
+
+    /// A row of text.
+    pub fn label(&mut self, text: &str) {
+        self.label_colored(text, COLOR_WHITE);
+    }
+
+    pub fn label_colored(&mut self, text: &str, color: Color) {
+        let row = self.next_row(0.0);
+        let x = match self.list_align {
+            TextAlign::Left => row.x,
+            TextAlign::Center => row.x + row.w / 2.0,
+            TextAlign::Right => row.x + row.w,
+        };
+        let align = self.list_align;
+        if let Some(painter) = self.painter.as_mut() {
+            draw_text(painter, x, row.y, align, color, text);
+        }
+    }
+
+    /// Leave some space before the next row.
+    pub fn space(&mut self, height: f32) {
+        self.list_y += height;
+    }
+
+    /// Returns true when the button is pressed.
+    pub fn button(&mut self, text: &str) -> bool {
+        let (row, is_focused) = self.next_focusable_row();
+        if let Some(painter) = self.painter.as_mut() {
+            let sprite = if is_focused {
+                UI_BUTTON_FOCUSED_SPRITE
+            } else {
+                UI_BUTTON_SPRITE
+            };
+            nine_slice(painter, sprite, row, UI_PANEL_BORDER);
+
+            let text_x = row.x + row.w / 2.0;
+            let text_y = row.y + UI_ROW_PADDING;
+            draw_text(
+                painter,
+                text_x,
+                text_y,
+                TextAlign::Center,
+                COLOR_WHITE,
+                text,
+            );
+        }
+
+        is_focused && self.input.confirm
+    }
+
+    /// Returns true when the checkbox is toggled.
+    pub fn checkbox(&mut self, text: &str, value: &mut bool) -> bool {
+        let (row, is_focused) = self.next_focusable_row();
+        let toggled = is_focused && self.input.confirm;
+        if toggled {
+            *value = !*value;
+        }
+
+        if let Some(painter) = self.painter.as_mut() {
+            draw_focus(painter, row, is_focused);
+
+            let sprite = if *value {
+                UI_CHECKBOX_CHECKED_SPRITE
+            } else {
+                UI_CHECKBOX_SPRITE
+            };
+            let region = painter.atlas.sprite(sprite);
+            let box_y = row.y + (row.h - region.h as f32) / 2.0;
+            draw_region(
+                painter,
+                region,
+                row.x + UI_ROW_PADDING,
+                box_y,
+                Vector2f::new(1.0, 1.0),
+            );
+
+            let text_x = row.x + UI_ROW_PADDING * 2.0 + region.w as f32;
+            let text_y = row.y + UI_ROW_PADDING;
+            draw_text(painter, text_x, text_y, TextAlign::Left, COLOR_WHITE, text);
+        }
+
+        toggled
+    }
+
+    /// A value between `min` and `max`, moved by `step` with left and right. Returns true when it changes.
+    pub fn slider(&mut self, text: &str, value: &mut f32, min: f32, max: f32, step: f32) -> bool {
+        let (row, is_focused) = self.next_focusable_row();
+        let previous = *value;
+        if is_focused && self.input.left {
+            *value -= step;
+        }
+        if is_focused && self.input.right {
+            *value += step;
+        }
+        *value = value.max(min).min(max);
+
+        if let Some(painter) = self.painter.as_mut() {
+            draw_focus(painter, row, is_focused);
+
+            let text_x = row.x + UI_ROW_PADDING;
+            let text_y = row.y + UI_ROW_PADDING;
+            draw_text(painter, text_x, text_y, TextAlign::Left, COLOR_WHITE, text);
+
+            // The track fills the right half of the row, with the knob along it
+            let track_region = painter.atlas.sprite(UI_SLIDER_TRACK_SPRITE);
+            let knob_region = painter.atlas.sprite(UI_SLIDER_KNOB_SPRITE);
+            let track = UiRect {
+                x: row.x + row.w / 2.0,
+                y: row.y + (row.h - track_region.h as f32) / 2.0,
+                w: row.w / 2.0 - UI_ROW_PADDING,
+                h: track_region.h as f32,
+            };
+            nine_slice(
+                painter,
+                UI_SLIDER_TRACK_SPRITE,
+                track,
+                UI_SLIDER_TRACK_BORDER,
+            );
+
+            let t = if max > min {
+                (*value - min) / (max - min)
+            } else {
+                0.0
+            };
+            let knob_x = track.x + t * (track.w - knob_region.w as f32);
+            let knob_y = row.y + (row.h - knob_region.h as f32) / 2.0;
+            draw_region(
+                painter,
+                knob_region,
+                knob_x,
+                knob_y,
+                Vector2f::new(1.0, 1.0),
+            );
+        }
+
+        *value != previous
+    }
+
+    /// Move the focus for the up and down pressed this tick, wrapping around at either end. Call this once every
+    /// widget has been declared.
+    pub fn finish(self) {
+        if self.focusable_count == 0 {
+            self.focus.index = 0;
+            return;
+        }
+
+        let count = self.focusable_count;
+        let mut index = self.focus.index.min(count - 1);
+        if self.input.up {
+            index = (index + count - 1) % count;
+        }
+        if self.input.down {
+            index = (index + 1) % count;
+        }
+        self.focus.index = index;
+    }
+
+    /// Take up the next row of the list. Rows are as tall as a line of text, plus some padding.
+    fn next_row(&mut self, padding: f32) -> UiRect {
+        let line_height = self
+            .painter
+            .as_ref()
+            .map_or(0, |painter| painter.font.line_height());
+        let row = UiRect {
+            x: self.list_x,
+            y: self.list_y,
+            w: self.list_width,
+            h: line_height as f32 * UI_TEXT_SCALE + padding * 2.0,
+        };
+
+        self.list_y += row.h;
+        row
+    }
+
+    fn next_focusable_row(&mut self) -> (UiRect, bool) {
+        let index = self.focusable_count;
+        self.focusable_count += 1;
+
+        (self.next_row(UI_ROW_PADDING), index == self.focus.index)
+    }
+}
+
+fn draw_text(painter: &mut UiPainter, x: f32, y: f32, align: TextAlign, color: Color, text: &str) {
+    painter.render.bind_color(color);
+    painter.render.bind_layer(UI_TEXT_LAYER);
+    painter.render.bind_transparency(Transparency::Transparent);
+    painter
+        .render
+        .text_aligned(painter.font, x, y, UI_TEXT_SCALE, align, text);
+}
+
+/// Highlight the focused row of a checkbox or slider.
+fn draw_focus(painter: &mut UiPainter, row: UiRect, is_focused: bool) {
+    if is_focused {
+        nine_slice(painter, UI_BUTTON_FOCUSED_SPRITE, row, UI_PANEL_BORDER);
+    }
+}
+
+fn draw_region(painter: &mut UiPainter, region: SpriteRegion, x: f32, y: f32, scale: Vector2f) {
+    painter.render.bind_color(COLOR_WHITE);
+    painter.render.bind_layer(UI_LAYER);
+    painter.render.bind_transparency(Transparency::Transparent);
+    painter.render.bind_texture(painter.atlas.tex_id());
+    painter
+        .render
+        .sprite(x, y, Point2f::origin(), scale, region);
+}
+
+/// Draw a sprite stretched over a rectangle, keeping its corners the same size and stretching its edges along
+/// one axis only, so borders don't get blurry or thick.
+fn nine_slice(painter: &mut UiPainter, sprite: &str, rect: UiRect, border: u32) {
+    let region = painter.atlas.sprite(sprite);
+    let border = border.min(region.w / 2).min(region.h / 2);
+    let edge = border as f32;
+
+    // Source offset and size, and destination offset and size, of each column and row
+    let columns = [
+        (0, border, 0.0, edge),
+        (border, region.w - border * 2, edge, rect.w - edge * 2.0),
+        (region.w - border, border, rect.w - edge, edge),
+    ];
+    let rows = [
+        (0, border, 0.0, edge),
+        (border, region.h - border * 2, edge, rect.h - edge * 2.0),
+        (region.h - border, border, rect.h - edge, edge),
+    ];
+
+    for (src_y, src_h, dst_y, dst_h) in rows.iter() {
+        for (src_x, src_w, dst_x, dst_w) in columns.iter() {
+            if *src_w == 0 || *src_h == 0 || *dst_w <= 0.0 || *dst_h <= 0.0 {
+                continue;
+            }
+
+            let slice = SpriteRegion {
+                x: region.x + src_x,
+                y: region.y + src_y,
+                w: *src_w,
+                h: *src_h,
+            };
+            let scale = Vector2f::new(dst_w / *src_w as f32, dst_h / *src_h as f32);
+            draw_region(painter, slice, rect.x + dst_x, rect.y + dst_y, scale);
+        }
+    }
+}
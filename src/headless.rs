@@ -0,0 +1,146 @@
+use crate::game::{
+    assets::Assets,
+    audio::{AudioMixer, AudioOutput},
+    audio_backend::CapturedSound,
+    flow::FlowState,
+    high_score::HighScoreTable,
+    physics::PhysicsState,
+    render::{self, RenderState},
+    replay::{Replay, ReplayPlayer},
+    settings::Settings,
+    GameState,
+};
+use gfx::{image::RgbaImage, input::InputState, software::SoftwareRenderer, window::DeltaTime};
+use specs::World;
+
+pub const HEADLESS_TICK_DT: DeltaTime = 1.0 / 60.0;
+
+// Matches the default render scale, so captured frames look like screenshots of the game
+pub const HEADLESS_CAPTURE_SCALE: f32 = 2.0;
+
+/// Runs the game without a window, GPU or audio device, ticking it at the same fixed step as `window::run`.
+/// Rendering is skipped unless `game.rendering_enabled` is turned back on.
+pub struct HeadlessRunner<'a, 'b> {
+    pub game: GameState<'a, 'b>,
+    width: u32,
+    height: u32,
+    ticks: u64,
+    capture_renderer: Option<SoftwareRenderer>,
+}
+
+impl<'a, 'b> HeadlessRunner<'a, 'b> {
+    pub fn new(width: u32, height: u32, seed: u64) -> Self {
+        // Headless runs never touch the player's real high scores or settings
+        let mut game = GameState::new(
+            width,
+            height,
+            seed,
+            HighScoreTable::default(),
+            Settings::default(),
+            AudioOutput::Null,
+        );
+        game.rendering_enabled = false;
+
+        HeadlessRunner {
+            game,
+            width,
+            height,
+            ticks: 0,
+            capture_renderer: None,
+        }
+    }
+
+    /// Create a runner set up to play back a replay, along with the player that feeds it the recorded input.
+    pub fn from_replay(width: u32, height: u32, replay: Replay) -> (Self, ReplayPlayer) {
+        let mut runner = HeadlessRunner::new(width, height, replay.header.seed);
+        replay.header.apply(&mut runner.game);
+
+        (runner, ReplayPlayer::new(replay))
+    }
+
+    /// The number of ticks run so far.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Record every sound played instead of discarding them, see `AudioMixer::captured_sounds`. Call this before
+    /// running any ticks, so the captured tick numbers line up with `ticks`.
+    pub fn capture_audio(&mut self) {
+        self.game
+            .world
+            .insert(AudioMixer::new(AudioOutput::Capture));
+    }
+
+    pub fn captured_sounds(&self) -> Vec<CapturedSound> {
+        self.game
+            .world
+            .read_resource::<AudioMixer>()
+            .captured_sounds()
+            .to_vec()
+    }
+
+    pub fn tick(&mut self, input: &InputState) {
+        self.game.world.insert::<InputState>(input.clone());
+        self.game.world.insert::<DeltaTime>(HEADLESS_TICK_DT);
+        self.game.tick();
+        self.ticks += 1;
+    }
+
+    /// Run for a number of ticks, asking `script` for the input of each one (by tick number, starting at 0).
+    pub fn run_script(&mut self, ticks: u64, mut script: impl FnMut(u64) -> InputState) {
+        for _ in 0..ticks {
+            let input = script(self.ticks);
+            self.tick(&input);
+        }
+    }
+
+    /// Run for a number of ticks without any input.
+    pub fn run_idle(&mut self, ticks: u64) {
+        let input = InputState::new();
+        for _ in 0..ticks {
+            self.tick(&input);
+        }
+    }
+
+    /// Run until every recorded frame of a replay has been played.
+    pub fn run_replay(&mut self, player: &mut ReplayPlayer) {
+        while let Some(input) = player.next_input() {
+            let input = input.clone();
+            self.tick(&input);
+        }
+    }
+
+    /// Draw the current frame with the software renderer, e.g. for screenshots and golden image tests.
+    /// Textures are loaded the first time a frame is captured.
+    pub fn capture_frame(&mut self) -> RgbaImage {
+        let (width, height) = (self.width, self.height);
+        let world = &self.game.world;
+        let renderer = self
+            .capture_renderer
+            .get_or_insert_with(|| create_capture_renderer(width, height, world));
+
+        // Draw where everything is after the last tick, without interpolating
+        self.game.world.write_resource::<PhysicsState>().lerp = 1.0;
+        self.game
+            .world
+            .write_resource::<RenderState>()
+            .clear_commands();
+        if self.game.world.read_resource::<FlowState>().shows_field() {
+            self.game.render_dispatcher.dispatch(&mut self.game.world);
+        }
+
+        render::draw_hud(&self.game.world, None, width, height);
+
+        renderer.render(self.game.world.write_resource::<RenderState>().commands());
+        renderer.to_image()
+    }
+}
+
+fn create_capture_renderer(width: u32, height: u32, world: &World) -> SoftwareRenderer {
+    let mut renderer = SoftwareRenderer::new(width, height, HEADLESS_CAPTURE_SCALE);
+    world
+        .read_resource::<Assets>()
+        .create_textures(&mut renderer);
+
+    renderer
+}
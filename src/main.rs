@@ -1,153 +1,294 @@
-mod game;
-
-use game::{
-    audio::{self, AudioAssetId, AudioAssetDb},
-    level::{self, LevelState},
+use brickbonker::game::{
+    assets::Assets,
+    audio::AudioOutput,
+    flow::FlowState,
+    high_score::HighScoreTable,
+    hot_reload::{self, FileWatcher, HOT_RELOAD_ROOT},
+    level::LevelState,
     physics::PhysicsState,
-    render::RenderState,
+    render::{self, RenderState},
+    replay::{Replay, ReplayHeader, ReplayPlayer, ReplayRecorder, REPLAY_FAST_FORWARD_TICKS},
+    rng::RngState,
+    settings::Settings,
     GameState,
 };
+use brickbonker::headless::HeadlessRunner;
 
 use gfx::{
-    color::*,
-    image::*,
-    input::InputState,
+    input::{InputState, VirtualKeyCode},
+    render_backend::RenderBackend,
     renderer::*,
-    texture::*,
     window::{self, *},
 };
 use specs::prelude::*;
+use std::path::Path;
 
 fn main() {
     let window_title: &str = "Brickbonker";
     let window_width: u32 = 320;
     let window_height: u32 = 240;
-    let render_scale: f32 = 2.0;
-    let state = GameState::new(window_width, window_height);
+    let args: Vec<String> = std::env::args().collect();
+
+    // Play back a recorded session, which decides the seed and start level for us
+    let replay =
+        arg_value(&args, "--replay").and_then(|path| match Replay::load(Path::new(path)) {
+            Ok(replay) => Some(replay),
+            Err(e) => {
+                eprintln!("Failed to load replay! {}", e);
+                None
+            }
+        });
+
+    // Allow choosing the rng seed, so a run can be reproduced
+    let seed: u64 = match &replay {
+        Some(replay) => replay.header.seed,
+        None => match arg_value(&args, "--seed").map(|seed| seed.parse()) {
+            Some(Ok(seed)) => seed,
+            Some(Err(_)) => {
+                eprintln!("Expected a number after --seed!");
+                rand::random()
+            }
+            None => rand::random(),
+        },
+    };
+
+    // Allow starting on a specific level, so level files can be tested without recompiling
+    let start_level_id: Option<String> = match &replay {
+        Some(replay) => replay.header.start_level_id.clone(),
+        None => arg_value(&args, "--level").map(|level_id| level_id.to_owned()),
+    };
+
+    // Run the simulation without a window, GPU or audio device, e.g. for benchmarks and CI
+    if args.iter().any(|arg| arg == "--headless") {
+        let ticks: Option<u64> = arg_value(&args, "--ticks").and_then(|ticks| ticks.parse().ok());
+        let screenshot_path: Option<&str> = arg_value(&args, "--screenshot");
+        run_headless(
+            window_width,
+            window_height,
+            seed,
+            start_level_id,
+            replay,
+            ticks,
+            screenshot_path,
+        );
+        return;
+    }
+
+    let mut state = GameState::new(
+        window_width,
+        window_height,
+        seed,
+        HighScoreTable::load_default(),
+        Settings::load_default(),
+        AudioOutput::Device,
+    );
+    println!(
+        "Using rng seed {}",
+        state.world.read_resource::<RngState>().seed()
+    );
+
+    if let Some(level_id) = &start_level_id {
+        state.start_game(level_id);
+    }
 
+    let mut replay_player = replay.map(|replay| {
+        replay.header.apply(&mut state);
+
+        println!("Playing replay, hold 'Tab' to fast-forward.");
+        ReplayPlayer::new(replay)
+    });
+
+    // Record the session, so it can be played back with --replay
+    let mut replay_recorder = match arg_value(&args, "--record") {
+        Some(path) if replay_player.is_none() => {
+            let header = ReplayHeader {
+                seed,
+                start_level_id: start_level_id.clone(),
+                high_scores: state
+                    .world
+                    .read_resource::<HighScoreTable>()
+                    .entries
+                    .iter()
+                    .map(|entry| entry.score)
+                    .collect(),
+                bindings: state.world.read_resource::<Settings>().key_bindings.clone(),
+            };
+
+            match ReplayRecorder::create(Path::new(path), &header) {
+                Ok(recorder) => Some(recorder),
+                Err(e) => {
+                    eprintln!("Failed to create replay {}! {}", path, e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    // Development mode reloads textures, sounds and levels as the files in res/ change
+    let mut file_watcher = if args.iter().any(|arg| arg == "--dev") {
+        println!("Watching {} for changes.", HOT_RELOAD_ROOT);
+        Some(FileWatcher::new(Path::new(HOT_RELOAD_ROOT)))
+    } else {
+        None
+    };
+
+    // The window starts with the saved settings, and follows them as they're changed on the options screen
+    let window_settings = state.world.read_resource::<Settings>().window_settings();
     window::run(
         window_title,
         window_width,
         window_height,
-        render_scale,
+        window_settings,
+        Renderer::new,
         state,
         move |game, renderer| {
-            import_texture(1, "res/textures/costanza.png", renderer);
-            import_texture(2, "res/textures/sprites.png", renderer);
-            import_texture(3, "res/textures/font.png", renderer);
-            import_texture(4, "res/textures/bg.png", renderer);
-
-            // Import audio assets (music and sound effects)
-            {
-                let mut audio_db = game.world.write_resource::<AudioAssetDb>();
-                audio_db.import(AudioAssetId::MusicBackground, "res/audio/tha-bounce-life.wav").unwrap();
-                audio_db.import(AudioAssetId::SfxBallBounce0, "res/audio/ball-bounce-0.wav").unwrap();
-                audio_db.import(AudioAssetId::SfxBallBounce1, "res/audio/ball-bounce-1.wav").unwrap();
-                audio_db.import(AudioAssetId::SfxBallWallHit0, "res/audio/ball-wall-hit-0.wav").unwrap();
-                audio_db.import(AudioAssetId::SfxBallWallHit1, "res/audio/ball-wall-hit-1.wav").unwrap();
-                audio_db.import(AudioAssetId::SfxBrickBreak0, "res/audio/brick-break-0.wav").unwrap();
-                audio_db.import(AudioAssetId::SfxBrickBreak1, "res/audio/brick-break-1.wav").unwrap();
-                audio_db.import(AudioAssetId::SfxBallDeath0, "res/audio/ball-death-0.wav").unwrap();
-
-                // Start playing the bg music right away
-                audio::play(AudioAssetId::MusicBackground, &audio_db, true);
-            }
-
+            game.world
+                .read_resource::<Assets>()
+                .create_textures(renderer);
         },
-        move |game, _window, input, dt| {
-            game.world.insert::<InputState>(input.clone());
+        move |game, window, input, dt| {
             game.world.insert::<DeltaTime>(dt);
+            window.settings = game.world.read_resource::<Settings>().window_settings();
+
+            if let Some(player) = replay_player.as_mut() {
+                // Holding 'Tab' fast-forwards through the replay
+                let ticks = if input.is_key_held(VirtualKeyCode::Tab) {
+                    REPLAY_FAST_FORWARD_TICKS
+                } else {
+                    1
+                };
 
-            // Handle any level loads
-            let load_level_pending = game
-                .world
-                .read_resource::<LevelState>()
-                .load_level_event
-                .is_some();
-            if load_level_pending {
-                level::load_level(&mut game.world);
+                for _ in 0..ticks {
+                    match player.next_input() {
+                        Some(replay_input) => {
+                            game.world.insert::<InputState>(replay_input.clone());
+                            game.tick();
+                        }
+                        None => break,
+                    }
+                }
+
+                // Once the replay runs out, the player takes over from wherever it left off
+                if player.is_finished() {
+                    println!("Replay finished.");
+                    replay_player = None;
+                }
+
+                return;
             }
 
-            game.world.write_resource::<RenderState>().clear_commands();
-            game.tick_dispatcher.dispatch(&mut game.world);
-            game.physics_dispatcher.dispatch(&mut game.world);
+            if let Some(recorder) = replay_recorder.as_mut() {
+                if let Err(e) = recorder.record(input) {
+                    eprintln!("Failed to record replay! {}", e);
+                    replay_recorder = None;
+                }
+            }
 
-            game.world.maintain();
+            game.world.insert::<InputState>(input.clone());
+            game.tick();
         },
         move |game, _ticks, lerp, window, renderer| {
-            game.world.write_resource::<PhysicsState>().lerp = lerp;
-
-            let mut render = game.world.write_resource::<RenderState>();
-
-            // FPS text
-            let msg = format!("FPS: {}", window.fps);
-            render.bind_color(COLOR_WHITE);
-            render.bind_layer(0);
-            render.bind_transparency(Transparency::Transparent);
-            render.bind_texture(3);
-            let fps_text_x = window_width as f32 - (msg.len() as f32 * 4.0) - 2.0;
-            render.text(fps_text_x, 2.0, 8, 16, 0.5, &msg);
-
-            let (score, balls, is_game_over) = {
-                let level = game.world.read_resource::<LevelState>();
-                (level.score, level.lives, level.lives == 0)
-            };
+            let flow = *game.world.read_resource::<FlowState>();
 
-            // Score text
-            let msg = format!("Score: {}", score);
-            render.bind_color(if is_game_over {
-                COLOR_GREEN
-            } else {
-                COLOR_WHITE
-            });
-            render.text(2.0, 2.0, 8, 16, 0.5, &msg);
-
-            // Balls text
-            let msg = format!("Balls: {}", balls);
-            render.bind_color(COLOR_WHITE);
-            render.text(2.0, 10.0, 8, 16, 0.5, &msg);
-            if is_game_over {
-                // Game Over text
-                let game_over_text_y = window_height as f32 - 22.0;
-                render.bind_color(COLOR_RED);
-                render.text(2.0, game_over_text_y, 8, 16, 0.75, &format!("Game Over!"));
-
-                // Restart text
-                let restart_text_y = window_height as f32 - 10.0;
-                render.bind_color(COLOR_WHITE);
-                render.text(
-                    2.0,
-                    restart_text_y,
-                    8,
-                    16,
-                    0.5,
-                    &format!("Press 'R' to start a new game."),
-                );
+            // Nothing moves while the game isn't simulating, so don't interpolate between stale positions
+            game.world.write_resource::<PhysicsState>().lerp =
+                if flow.is_simulating() { lerp } else { 1.0 };
+
+            if let Some(watcher) = file_watcher.as_mut() {
+                let changed = watcher.poll();
+                for tex_id in hot_reload::reload_changed_files(&game.world, &changed) {
+                    game.world
+                        .read_resource::<Assets>()
+                        .create_texture(tex_id, renderer);
+                }
             }
 
-            // Background
-            render.bind_color(COLOR_WHITE);
-            render.bind_layer(0);
-            render.bind_transparency(Transparency::Opaque);
-            render.bind_texture(4);
-            render.textured_quad((0.0, 400.0), (400.0, 400.0), (0.0, 0.0), (400.0, 0.0));
+            let fps = if game.world.read_resource::<Settings>().show_fps {
+                Some(window.fps)
+            } else {
+                None
+            };
+            render::draw_hud(&game.world, fps, window_width, window_height);
 
-            // Process commands into batches and send to the renderer
-            let batches = renderer.process_commands(render.commands());
-            renderer.render(window.dpi_scale_factor, batches);
+            // Send the commands to the renderer
+            let commands = game.world.write_resource::<RenderState>().commands();
+            renderer.submit(commands);
+            renderer.present(window.dpi_scale_factor);
         },
     );
 }
 
-fn import_texture(id: u16, path: &str, renderer: &mut Renderer) -> Texture {
-    let image: RgbaImage = gfx::image::open(path)
-        .expect(&format!("Failed to open image {}!", path))
-        .to_rgba();
+fn run_headless(
+    width: u32,
+    height: u32,
+    seed: u64,
+    start_level_id: Option<String>,
+    replay: Option<Replay>,
+    ticks: Option<u64>,
+    screenshot_path: Option<&str>,
+) {
+    let (mut runner, replay_player) = match replay {
+        Some(replay) => {
+            let (runner, player) = HeadlessRunner::from_replay(width, height, replay);
+            (runner, Some(player))
+        }
+        None => {
+            let mut runner = HeadlessRunner::new(width, height, seed);
+            if let Some(level_id) = &start_level_id {
+                runner.game.start_game(level_id);
+            }
+
+            (runner, None)
+        }
+    };
+
+    let start_time = std::time::Instant::now();
+    if let Some(mut player) = replay_player {
+        runner.run_replay(&mut player);
+    }
+
+    // Keep going without input for any extra ticks asked for (an hour of game time, when not playing a replay)
+    let extra_ticks = match ticks {
+        Some(ticks) => ticks,
+        None if runner.ticks() == 0 => 60 * 60 * 60,
+        None => 0,
+    };
+    runner.run_idle(extra_ticks);
+
+    let elapsed = start_time.elapsed().as_secs_f64();
+    let level = runner.game.world.read_resource::<LevelState>();
+    println!(
+        "[Headless] Ran {} ticks in {:.2}s ({:.0} ticks/s)",
+        runner.ticks(),
+        elapsed,
+        runner.ticks() as f64 / elapsed.max(std::f64::EPSILON)
+    );
+    println!(
+        "[Headless] Level: {}, score: {}, lives: {}, state: {:?}",
+        level.level_id,
+        level.score,
+        level.lives,
+        *runner.game.world.read_resource::<FlowState>()
+    );
+    drop(level);
+
+    // Save the last frame, drawn with the software renderer
+    if let Some(path) = screenshot_path {
+        match runner.capture_frame().save(path) {
+            Ok(_) => println!("[Headless] Saved screenshot {}", path),
+            Err(e) => eprintln!("Failed to save screenshot {}! {}", path, e),
+        }
+    }
+}
 
-    let width: u32 = image.width();
-    let height: u32 = image.height();
-    let pixels: Vec<u8> = image.into_raw();
-    renderer.create_gpu_texture(id, width, height, &pixels);
+/// Get the value following a command line flag, e.g. `--level level-02`.
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let i = args.iter().position(|arg| arg == flag)?;
+    let value = args.get(i + 1).map(|value| value.as_str());
+    if value.is_none() {
+        eprintln!("Expected a value after {}!", flag);
+    }
 
-    Texture::new(id, width, height, pixels)
+    value
 }
@@ -0,0 +1,74 @@
+//! Checks which sounds the game plays, using the capture audio backend.
+
+use brickbonker::{
+    game::{audio::AudioBus, audio_backend::CapturedSound},
+    headless::HeadlessRunner,
+};
+use gfx::input::{InputState, VirtualKeyCode};
+
+const SCREEN_WIDTH: u32 = 320;
+const SCREEN_HEIGHT: u32 = 240;
+const SEED: u64 = 1;
+const FIRST_LEVEL_ID: &str = "level-01";
+const PLAY_TICKS: u64 = 1200;
+
+// A few ticks in, once the level is loaded and the ball is resting on the paddle
+const LAUNCH_TICK: u64 = 5;
+
+/// Start the first level, launch the ball and let it bounce around.
+fn play_first_level() -> HeadlessRunner<'static, 'static> {
+    let mut runner = HeadlessRunner::new(SCREEN_WIDTH, SCREEN_HEIGHT, SEED);
+    runner.capture_audio();
+    runner.game.start_game(FIRST_LEVEL_ID);
+    runner.run_script(PLAY_TICKS, |tick| {
+        if tick == LAUNCH_TICK {
+            InputState::from_keys(&[VirtualKeyCode::Space], &[VirtualKeyCode::Space], &[])
+        } else {
+            InputState::new()
+        }
+    });
+
+    runner
+}
+
+/// Everything that was captured except music, which plays whatever the game is doing.
+fn sound_effects(runner: &HeadlessRunner) -> Vec<CapturedSound> {
+    runner
+        .captured_sounds()
+        .into_iter()
+        .filter(|sound| sound.bus != AudioBus::Music)
+        .collect()
+}
+
+#[test]
+fn title_screen_is_silent() {
+    let mut runner = HeadlessRunner::new(SCREEN_WIDTH, SCREEN_HEIGHT, SEED);
+    runner.capture_audio();
+    runner.run_idle(120);
+
+    assert!(sound_effects(&runner).is_empty());
+}
+
+#[test]
+fn ball_sounds_are_captured_in_order() {
+    let sounds = sound_effects(&play_first_level());
+
+    assert!(
+        sounds.iter().any(|sound| sound.name.starts_with("ball-")),
+        "No ball sounds were played: {:?}",
+        sounds
+    );
+    for sound in sounds.iter() {
+        assert_eq!(sound.bus, AudioBus::Sfx);
+        assert!(sound.tick < PLAY_TICKS);
+    }
+    assert!(sounds.windows(2).all(|pair| pair[0].tick <= pair[1].tick));
+}
+
+#[test]
+fn captured_sounds_are_reproducible() {
+    assert_eq!(
+        sound_effects(&play_first_level()),
+        sound_effects(&play_first_level())
+    );
+}
@@ -0,0 +1,66 @@
+//! Checks how `BallComponent::split` fans new balls out around the ball being split.
+
+use brickbonker::game::{ball::BallComponent, Vector2d};
+use specs::prelude::*;
+
+const SPREAD: f64 = 0.25;
+const LAUNCH_SPEED: f64 = 6.0;
+
+/// The angle between two directions, positive when `to` is counter-clockwise of `from`.
+fn angle_between(from: Vector2d, to: Vector2d) -> f64 {
+    (from.x * to.y - from.y * to.x).atan2(from.dot(&to))
+}
+
+#[test]
+fn split_fans_balls_out_either_side() {
+    let velocity = Vector2d::new(3.0, -4.0);
+    let position = Vector2d::new(100.0, 50.0);
+    let ball = BallComponent::new(velocity, None);
+
+    let events = ball.split(position, 5, SPREAD, LAUNCH_SPEED);
+    assert_eq!(events.len(), 4);
+
+    let angles: Vec<f64> = events
+        .iter()
+        .map(|event| angle_between(velocity, event.linear_velocity))
+        .collect();
+    let expected = [SPREAD, -SPREAD, 2.0 * SPREAD, -2.0 * SPREAD];
+    for (angle, expected) in angles.iter().zip(expected.iter()) {
+        assert!((angle - expected).abs() < 1e-9, "{:?}", angles);
+    }
+
+    for event in events.iter() {
+        assert_eq!(event.position, position);
+        assert!((event.linear_velocity.magnitude() - velocity.magnitude()).abs() < 1e-9);
+        assert_eq!(event.owning_paddle_ent, None);
+    }
+}
+
+#[test]
+fn split_into_one_ball_spawns_nothing() {
+    let ball = BallComponent::new(Vector2d::new(0.0, -5.0), None);
+
+    assert!(ball
+        .split(Vector2d::zeros(), 1, SPREAD, LAUNCH_SPEED)
+        .is_empty());
+    assert!(ball
+        .split(Vector2d::zeros(), 0, SPREAD, LAUNCH_SPEED)
+        .is_empty());
+}
+
+#[test]
+fn held_ball_splits_as_if_launched() {
+    let mut world = World::new();
+    let paddle_ent = world.create_entity().build();
+    let ball = BallComponent::new(Vector2d::zeros(), Some(paddle_ent));
+
+    let events = ball.split(Vector2d::zeros(), 3, SPREAD, LAUNCH_SPEED);
+    assert_eq!(events.len(), 2);
+
+    let launch_velocity = Vector2d::new(0.0, -LAUNCH_SPEED);
+    for event in events.iter() {
+        let angle = angle_between(launch_velocity, event.linear_velocity);
+        assert!((angle.abs() - SPREAD).abs() < 1e-9);
+        assert!((event.linear_velocity.magnitude() - LAUNCH_SPEED).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,95 @@
+//! Checks that the campaign copes with a level in its playlist that fails to load.
+
+use brickbonker::{
+    game::{
+        brick::BrickComponent,
+        campaign::CampaignState,
+        flow::FlowState,
+        level::{LevelState, LoadLevelEvent},
+    },
+    headless::HeadlessRunner,
+};
+use gfx::input::{InputState, VirtualKeyCode};
+use specs::prelude::*;
+
+const SCREEN_WIDTH: u32 = 320;
+const SCREEN_HEIGHT: u32 = 240;
+const SEED: u64 = 1;
+const FIRST_LEVEL_ID: &str = "level-01";
+
+// Level ids are paths relative to the levels directory, which lets the playlist reach a fixture outside it
+const BROKEN_LEVEL_ID: &str = "../../tests/levels/broken";
+
+const LOAD_TICKS: u64 = 5;
+
+fn runner_with_playlist(playlist: &[&str]) -> HeadlessRunner<'static, 'static> {
+    let mut runner = HeadlessRunner::new(SCREEN_WIDTH, SCREEN_HEIGHT, SEED);
+    runner.game.world.write_resource::<LevelState>().campaign =
+        CampaignState::new(playlist.iter().map(|id| (*id).to_owned()).collect());
+    runner
+}
+
+fn flow(runner: &HeadlessRunner) -> FlowState {
+    *runner.game.world.read_resource::<FlowState>()
+}
+
+#[test]
+fn broken_first_level_goes_back_to_the_title() {
+    let mut runner = runner_with_playlist(&[BROKEN_LEVEL_ID, FIRST_LEVEL_ID]);
+    runner.game.start_game(BROKEN_LEVEL_ID);
+    runner.run_idle(LOAD_TICKS);
+
+    assert_eq!(flow(&runner), FlowState::Title);
+    let level = runner.game.world.read_resource::<LevelState>();
+    assert!(level.load_level_event.is_none());
+    assert_eq!(level.campaign.current_level_index, None);
+}
+
+#[test]
+fn broken_next_level_goes_back_to_the_title() {
+    let mut runner = runner_with_playlist(&[FIRST_LEVEL_ID, BROKEN_LEVEL_ID]);
+    runner.game.start_game(FIRST_LEVEL_ID);
+    runner.run_idle(LOAD_TICKS);
+
+    // Clear the level by hand
+    {
+        let ents = runner.game.world.entities();
+        let bricks = runner.game.world.read_storage::<BrickComponent>();
+        for (ent, _) in (&ents, &bricks).join() {
+            ents.delete(ent).expect("Failed to delete brick!");
+        }
+    }
+    runner.game.world.maintain();
+    runner.run_idle(1);
+    assert!(matches!(flow(&runner), FlowState::LevelComplete { .. }));
+
+    let enter = InputState::from_keys(&[VirtualKeyCode::Return], &[VirtualKeyCode::Return], &[]);
+    runner.tick(&enter);
+    runner.run_idle(LOAD_TICKS);
+
+    // The first level stays loaded rather than the broken one being "completed" over and over
+    assert_eq!(flow(&runner), FlowState::Title);
+    let level = runner.game.world.read_resource::<LevelState>();
+    assert_eq!(level.level_id, FIRST_LEVEL_ID);
+    assert_eq!(level.campaign.current_level_index, Some(0));
+}
+
+#[test]
+fn broken_reload_keeps_the_level_running() {
+    let mut runner = runner_with_playlist(&[FIRST_LEVEL_ID]);
+    runner.game.start_game(FIRST_LEVEL_ID);
+    runner.run_idle(LOAD_TICKS);
+
+    runner
+        .game
+        .world
+        .write_resource::<LevelState>()
+        .load_level_event = Some(LoadLevelEvent::reload(BROKEN_LEVEL_ID));
+    runner.run_idle(LOAD_TICKS);
+
+    assert_eq!(flow(&runner), FlowState::Playing);
+    assert_eq!(
+        runner.game.world.read_resource::<LevelState>().level_id,
+        FIRST_LEVEL_ID
+    );
+}
@@ -0,0 +1,68 @@
+//! Checks the bitmap font's text layout against a small hand-written descriptor.
+
+use brickbonker::game::font::{BitmapFont, TextAlign};
+use std::path::Path;
+
+// Three glyphs: `A` is 6 pixels wide, `B` is 4 and the fallback `?` is 5, with `A` pulled in after `B`
+const FONT_SOURCE: &str = r#"info face="test" size=8
+common lineHeight=10 base=8 scaleW=32 scaleH=32 pages=1
+page id=0 file="test.png"
+chars count=4
+char id=32 x=0  y=0 width=0 height=0 xoffset=0 yoffset=0 xadvance=3 page=0 chnl=15
+char id=63 x=0  y=0 width=5 height=8 xoffset=0 yoffset=0 xadvance=5 page=0 chnl=15
+char id=65 x=5  y=0 width=6 height=8 xoffset=0 yoffset=1 xadvance=6 page=0 chnl=15
+char id=66 x=11 y=0 width=4 height=8 xoffset=1 yoffset=0 xadvance=4 page=0 chnl=15
+kernings count=1
+kerning first=66 second=65 amount=-1
+"#;
+
+fn test_font() -> BitmapFont {
+    BitmapFont::parse(Path::new("res/fonts/test.fnt"), FONT_SOURCE).unwrap()
+}
+
+#[test]
+fn page_is_relative_to_descriptor() {
+    assert_eq!(test_font().page_path(), Path::new("res/fonts/test.png"));
+}
+
+#[test]
+fn measure_applies_kerning_and_fallback() {
+    let font = test_font();
+
+    assert_eq!(font.measure("AB", 1.0), 10.0);
+    assert_eq!(font.measure("BA", 1.0), 9.0);
+    assert_eq!(font.measure("A\u{e9}", 2.0), 22.0);
+    assert_eq!(font.measure("AA\nB", 1.0), 12.0);
+}
+
+#[test]
+fn layout_aligns_each_line() {
+    let font = test_font();
+
+    let left = font.layout("BA", 10.0, 20.0, 1.0, TextAlign::Left);
+    assert_eq!((left[0].x, left[0].y), (11.0, 20.0));
+    assert_eq!((left[1].x, left[1].y), (13.0, 21.0));
+
+    let centered = font.layout("AB\nA", 50.0, 0.0, 1.0, TextAlign::Center);
+    assert_eq!(centered[0].x, 45.0);
+    assert_eq!((centered[2].x, centered[2].y), (47.0, 11.0));
+
+    let right = font.layout("A", 50.0, 0.0, 1.0, TextAlign::Right);
+    assert_eq!(right[0].x, 44.0);
+}
+
+#[test]
+fn wrap_breaks_between_words() {
+    let font = test_font();
+
+    assert_eq!(font.wrap("AB AB AB", 25.0, 1.0), vec!["AB AB", "AB"]);
+    assert_eq!(font.wrap("AAAA", 13.0, 1.0), vec!["AA", "AA"]);
+    assert_eq!(font.wrap("A\nB", 100.0, 1.0), vec!["A", "B"]);
+}
+
+#[test]
+fn game_font_loads() {
+    let font = BitmapFont::load(Path::new("res/textures/font.fnt")).unwrap();
+
+    assert_eq!(font.measure("Score: 100", 0.5), 40.0);
+}
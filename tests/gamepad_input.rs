@@ -0,0 +1,155 @@
+//! Checks gamepad input through a virtual gamepad: deadzones, buttons and sticks driving actions, the paddle
+//! following an analog stick, and gamepad input in replays.
+
+use brickbonker::{
+    game::{
+        paddle::PlayerPaddleComponent,
+        replay::{Replay, ReplayPlayer},
+        transform::TransformComponent,
+    },
+    headless::HeadlessRunner,
+};
+use gfx::input::{
+    GamepadAxis, GamepadButton, GamepadInput, InputAction, InputState, VirtualGamepad,
+    VirtualKeyCode,
+};
+use specs::prelude::*;
+
+const SCREEN_WIDTH: u32 = 320;
+const SCREEN_HEIGHT: u32 = 240;
+const SEED: u64 = 1;
+const FIRST_LEVEL_ID: &str = "level-01";
+
+// Enough ticks for the level to load, and for the paddle to move without reaching a wall
+const LOAD_TICKS: u64 = 5;
+const MOVE_TICKS: u64 = 10;
+
+/// Read a gamepad into the input for the next tick.
+fn read(input: &mut InputState, gamepad: &VirtualGamepad) {
+    input.clear_pressed_and_released();
+    input.update_gamepad(gamepad);
+}
+
+fn paddle_x(runner: &HeadlessRunner) -> f64 {
+    let transforms = runner.game.world.read_storage::<TransformComponent>();
+    let paddles = runner.game.world.read_storage::<PlayerPaddleComponent>();
+    let (transform, _) = (&transforms, &paddles).join().next().unwrap();
+    transform.position.x
+}
+
+/// How far the paddle moves while the input is held.
+fn paddle_movement(input: &InputState) -> f64 {
+    let mut runner = HeadlessRunner::new(SCREEN_WIDTH, SCREEN_HEIGHT, SEED);
+    runner.game.start_game(FIRST_LEVEL_ID);
+    runner.run_idle(LOAD_TICKS);
+
+    let start_x = paddle_x(&runner);
+    for _ in 0..MOVE_TICKS {
+        runner.tick(input);
+    }
+
+    paddle_x(&runner) - start_x
+}
+
+#[test]
+fn sticks_have_a_deadzone() {
+    let mut input = InputState::new();
+    let mut gamepad = VirtualGamepad::new();
+
+    gamepad.set_axis(GamepadAxis::LeftStickX, 0.15);
+    gamepad.set_axis(GamepadAxis::LeftStickY, -0.1);
+    read(&mut input, &gamepad);
+    assert_eq!(input.gamepad_axis(GamepadAxis::LeftStickX), 0.0);
+    assert_eq!(input.action_value(InputAction::MoveRight), 0.0);
+
+    // Past the deadzone, the rest of the range is stretched to start at 0
+    gamepad.set_state(&[], &[(GamepadAxis::LeftStickX, 0.6)]);
+    read(&mut input, &gamepad);
+    assert!((input.gamepad_axis(GamepadAxis::LeftStickX) - 0.5).abs() < 1e-6);
+    assert!((input.action_value(InputAction::MoveRight) - 0.5).abs() < 1e-6);
+    assert_eq!(input.action_value(InputAction::MoveLeft), 0.0);
+
+    input.set_gamepad_deadzone(0.0);
+    assert!((input.gamepad_axis(GamepadAxis::LeftStickX) - 0.6).abs() < 1e-6);
+}
+
+#[test]
+fn buttons_are_pressed_held_and_released() {
+    let mut input = InputState::new();
+    let mut gamepad = VirtualGamepad::new();
+
+    gamepad.press_button(GamepadButton::South);
+    read(&mut input, &gamepad);
+    assert!(input.is_gamepad_input_pressed(GamepadInput::Button(GamepadButton::South)));
+    assert!(input.is_action_pressed(InputAction::Launch));
+    assert!(input.is_action_pressed(InputAction::Confirm));
+
+    read(&mut input, &gamepad);
+    assert!(!input.is_action_pressed(InputAction::Launch));
+    assert!(input.is_action_held(InputAction::Launch));
+    assert_eq!(input.action_value(InputAction::Launch), 1.0);
+
+    gamepad.release_button(GamepadButton::South);
+    read(&mut input, &gamepad);
+    assert!(!input.is_action_held(InputAction::Launch));
+    assert!(input.is_action_released(InputAction::Launch));
+}
+
+#[test]
+fn sticks_press_actions_past_the_threshold() {
+    let mut input = InputState::new();
+    let mut gamepad = VirtualGamepad::new();
+
+    gamepad.set_axis(GamepadAxis::LeftStickX, -0.4);
+    read(&mut input, &gamepad);
+    assert!(!input.is_action_held(InputAction::MoveLeft));
+    assert!(input.action_value(InputAction::MoveLeft) > 0.0);
+
+    gamepad.set_axis(GamepadAxis::LeftStickX, -0.9);
+    read(&mut input, &gamepad);
+    assert!(input.is_action_pressed(InputAction::MoveLeft));
+
+    gamepad.set_axis(GamepadAxis::LeftStickX, 0.0);
+    read(&mut input, &gamepad);
+    assert!(input.is_action_released(InputAction::MoveLeft));
+}
+
+#[test]
+fn paddle_follows_the_stick() {
+    let keys = InputState::from_keys(&[VirtualKeyCode::Right], &[], &[]);
+    let full_speed = paddle_movement(&keys);
+    assert!(full_speed > 0.0);
+
+    let mut half_stick = InputState::new();
+    let mut gamepad = VirtualGamepad::new();
+    gamepad.set_axis(GamepadAxis::LeftStickX, 0.6);
+    half_stick.update_gamepad(&gamepad);
+    let half_speed = paddle_movement(&half_stick);
+    assert!((half_speed - full_speed / 2.0).abs() < 1e-3);
+
+    let mut d_pad = InputState::new();
+    gamepad.set_state(&[GamepadButton::DPadLeft], &[]);
+    d_pad.update_gamepad(&gamepad);
+    assert_eq!(paddle_movement(&d_pad), -full_speed);
+}
+
+#[test]
+fn replays_play_back_the_gamepad() {
+    let source = "brickbonker-replay 1\n\
+                  seed: 1\n\
+                  high_scores:\n\
+                  inputs:\n\
+                  3 - - - South LeftStickX=-0.6\n\
+                  5 - - - - -\n";
+    let mut player = ReplayPlayer::new(Replay::parse(source).unwrap());
+
+    let inputs: Vec<InputState> = std::iter::from_fn(|| player.next_input().cloned()).collect();
+    assert_eq!(inputs.len(), 6);
+    assert!(!inputs[2].is_action_held(InputAction::Launch));
+    assert!(inputs[3].is_action_pressed(InputAction::Launch));
+    assert!((inputs[3].action_value(InputAction::MoveLeft) - 0.5).abs() < 1e-6);
+    assert!(!inputs[4].is_action_pressed(InputAction::Launch));
+    assert!(inputs[4].is_action_held(InputAction::Launch));
+    assert!(inputs[5].is_action_released(InputAction::Launch));
+    assert_eq!(inputs[5].action_value(InputAction::MoveLeft), 0.0);
+}
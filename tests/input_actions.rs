@@ -0,0 +1,172 @@
+//! Checks that actions follow the keys bound to them, and that the controls screen rebinds them.
+
+use brickbonker::{
+    game::{flow::FlowState, settings::Settings},
+    headless::HeadlessRunner,
+};
+use gfx::input::{
+    GamepadButton, GamepadInput, InputAction, InputBindings, InputState, VirtualGamepad,
+    VirtualKeyCode,
+};
+
+const SCREEN_WIDTH: u32 = 320;
+const SCREEN_HEIGHT: u32 = 240;
+const SEED: u64 = 1;
+
+fn press(key: VirtualKeyCode) -> InputState {
+    InputState::from_keys(&[key], &[key], &[])
+}
+
+#[test]
+fn actions_follow_their_bindings() {
+    let mut input = press(VirtualKeyCode::W);
+    assert!(!input.is_action_pressed(InputAction::Launch));
+
+    let mut bindings = InputBindings::default();
+    bindings.set_keys(InputAction::Launch, vec![VirtualKeyCode::W]);
+    input.set_bindings(bindings);
+    assert!(input.is_action_pressed(InputAction::Launch));
+    assert!(input.is_action_held(InputAction::Launch));
+    assert!(!input.is_action_pressed(InputAction::MoveLeft));
+
+    let space = InputState::from_keys(&[], &[], &[VirtualKeyCode::Space]);
+    assert!(space.is_action_released(InputAction::Launch));
+    assert!(space.is_action_released(InputAction::Confirm));
+}
+
+#[test]
+fn bindings_survive_saving() {
+    let mut bindings = InputBindings::default();
+    bindings.set_keys(
+        InputAction::Pause,
+        vec![VirtualKeyCode::Tab, VirtualKeyCode::Back],
+    );
+
+    let mut parsed = InputBindings::default();
+    for binding in bindings.to_bindings() {
+        parsed.parse_binding(&binding).unwrap();
+    }
+    assert_eq!(parsed, bindings);
+
+    assert!(parsed.parse_binding("jump Space").is_err());
+}
+
+#[test]
+fn controls_screen_rebinds_launch() {
+    let mut runner = HeadlessRunner::new(SCREEN_WIDTH, SCREEN_HEIGHT, SEED);
+
+    // Open the options screen from the title menu, then the controls screen below the options, then pick the launch
+    // action
+    runner.run_script(14, |tick| match tick {
+        0 => press(VirtualKeyCode::Down),
+        1 => press(VirtualKeyCode::Return),
+        2..=9 => press(VirtualKeyCode::Down),
+        10 => press(VirtualKeyCode::Return),
+        11..=12 => press(VirtualKeyCode::Down),
+        _ => press(VirtualKeyCode::Return),
+    });
+    assert_eq!(
+        *runner.game.world.read_resource::<FlowState>(),
+        FlowState::Controls {
+            paused: false,
+            waiting: Some(InputAction::Launch),
+        }
+    );
+
+    // The key is added next to the keys already bound
+    runner.tick(&press(VirtualKeyCode::W));
+    let settings = runner.game.world.read_resource::<Settings>().clone();
+    assert_eq!(
+        settings.key_bindings.keys(InputAction::Launch),
+        &[VirtualKeyCode::Space, VirtualKeyCode::W]
+    );
+    assert_eq!(
+        settings.key_bindings.keys(InputAction::MoveLeft),
+        InputAction::MoveLeft.default_keys()
+    );
+
+    // Gamepad buttons can be bound too, and binding something that's already bound removes it
+    let mut gamepad = VirtualGamepad::new();
+    gamepad.press_button(GamepadButton::North);
+    let mut north = InputState::new();
+    north.update_gamepad(&gamepad);
+    runner.tick(&press(VirtualKeyCode::Return));
+    runner.tick(&north);
+    runner.tick(&press(VirtualKeyCode::Return));
+    runner.tick(&press(VirtualKeyCode::Space));
+    let settings = runner.game.world.read_resource::<Settings>().clone();
+    assert_eq!(
+        settings.key_bindings.gamepad_inputs(InputAction::Launch),
+        &[
+            GamepadInput::Button(GamepadButton::South),
+            GamepadInput::Button(GamepadButton::North)
+        ]
+    );
+    assert_eq!(
+        settings.key_bindings.keys(InputAction::Launch),
+        &[VirtualKeyCode::W]
+    );
+
+    // Escape cancels rather than being bound
+    runner.tick(&press(VirtualKeyCode::Return));
+    runner.tick(&press(VirtualKeyCode::Escape));
+    assert_eq!(
+        *runner.game.world.read_resource::<FlowState>(),
+        FlowState::Controls {
+            paused: false,
+            waiting: None,
+        }
+    );
+    assert_eq!(
+        runner
+            .game
+            .world
+            .read_resource::<Settings>()
+            .key_bindings
+            .keys(InputAction::Launch),
+        &[VirtualKeyCode::W]
+    );
+
+    runner.tick(&press(VirtualKeyCode::Escape));
+    runner.tick(&press(VirtualKeyCode::Escape));
+    assert_eq!(
+        *runner.game.world.read_resource::<FlowState>(),
+        FlowState::Title
+    );
+}
+
+#[test]
+fn initials_are_entered_with_actions() {
+    let mut runner = HeadlessRunner::new(SCREEN_WIDTH, SCREEN_HEIGHT, SEED);
+    *runner.game.world.write_resource::<FlowState>() = FlowState::EnterInitials {
+        initials: *b"AAA",
+        cursor: 0,
+    };
+
+    // Up and down change the letter, left and right move between letters, and back goes back a letter
+    runner.run_script(4, |tick| match tick {
+        0 => press(VirtualKeyCode::Up),
+        1 => press(VirtualKeyCode::Right),
+        2 => press(VirtualKeyCode::Down),
+        _ => press(VirtualKeyCode::Escape),
+    });
+    assert_eq!(
+        *runner.game.world.read_resource::<FlowState>(),
+        FlowState::EnterInitials {
+            initials: *b"BZA",
+            cursor: 0,
+        }
+    );
+
+    let mut bindings = InputBindings::default();
+    bindings.set_keys(InputAction::MenuUp, vec![VirtualKeyCode::Tab]);
+    runner.game.world.write_resource::<Settings>().key_bindings = bindings;
+    runner.tick(&press(VirtualKeyCode::Tab));
+    assert_eq!(
+        *runner.game.world.read_resource::<FlowState>(),
+        FlowState::EnterInitials {
+            initials: *b"CZA",
+            cursor: 0,
+        }
+    );
+}
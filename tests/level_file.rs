@@ -0,0 +1,113 @@
+//! Checks that level files parse, and that mistakes in them are reported at the right line and column.
+
+use brickbonker::game::{
+    brick::BrickKind,
+    level_file::{self, LevelBrick, LevelParseError},
+};
+
+const CAMPAIGN_LEVEL_IDS: [&str; 3] = ["level-01", "level-02", "level-03"];
+
+fn parse_error(source: &str) -> LevelParseError {
+    match level_file::parse_level("test", source) {
+        Err(e) => e,
+        Ok(_) => panic!("Expected a parse error for:\n{}", source),
+    }
+}
+
+#[test]
+fn level_parses() {
+    let source =
+        "// A comment\nname: \"Test\"\nball_speed: 4.5\nmusic: calm\n\nbricks:\nY.#\n\n S\n";
+    let level = level_file::parse_level("test", source).unwrap();
+
+    assert_eq!(level.name, "Test");
+    assert_eq!(level.ball_speed, 4.5);
+    assert_eq!(level.music, "calm");
+    assert_eq!(
+        level.bricks,
+        vec![
+            LevelBrick {
+                x: 0,
+                y: 0,
+                kind: BrickKind::Weak,
+            },
+            LevelBrick {
+                x: 2,
+                y: 0,
+                kind: BrickKind::Standard,
+            },
+            LevelBrick {
+                x: 1,
+                y: 2,
+                kind: BrickKind::Steel,
+            },
+        ]
+    );
+}
+
+#[test]
+fn bad_metadata_value_is_reported_at_the_value() {
+    let e = parse_error("name: \"Test\"\nball_speed: fast\nbricks:\n##\n");
+    assert_eq!((e.line, e.column), (2, 13));
+
+    let e = parse_error("  powerup_chance:   2\nbricks:\n##\n");
+    assert_eq!((e.line, e.column), (1, 21));
+
+    let e = parse_error("name: Test\nbricks:\n");
+    assert_eq!((e.line, e.column), (1, 7));
+}
+
+#[test]
+fn metadata_columns_count_characters() {
+    let e = parse_error("\u{a0}\u{a0}ball_speed: fast\nbricks:\n##\n");
+    assert_eq!((e.line, e.column), (1, 15));
+
+    let e = parse_error("ball_speed:\u{a0}\u{a0}fast\nbricks:\n##\n");
+    assert_eq!((e.line, e.column), (1, 14));
+
+    let e = parse_error("\u{a0}gravité: 2\nbricks:\n");
+    assert_eq!((e.line, e.column), (1, 2));
+}
+
+#[test]
+fn unknown_property_is_reported_at_the_key() {
+    let e = parse_error("\n  gravity: 2\nbricks:\n");
+    assert_eq!((e.line, e.column), (2, 3));
+}
+
+#[test]
+fn unknown_brick_code_is_reported_at_the_cell() {
+    let e = parse_error("bricks:\n##\n#.Z#\n");
+    assert_eq!((e.line, e.column), (3, 3));
+    assert!(e.message.contains('Z'), "{}", e.message);
+}
+
+#[test]
+fn ragged_row_is_reported_where_it_gets_too_wide() {
+    let e = parse_error("bricks:\n##########\n###########\n");
+    assert_eq!((e.line, e.column), (3, 11));
+}
+
+#[test]
+fn missing_brick_grid_is_reported() {
+    let e = parse_error("name: \"Test\"\n");
+    assert_eq!(e.line, 2);
+}
+
+#[test]
+fn level_without_breakable_bricks_is_reported() {
+    let e = parse_error("name: \"Test\"\n\nbricks:\nS.S\n");
+    assert_eq!((e.line, e.column), (3, 1));
+
+    let e = parse_error("bricks:\n\n");
+    assert_eq!(e.line, 1);
+}
+
+#[test]
+fn campaign_levels_load() {
+    for level_id in CAMPAIGN_LEVEL_IDS.iter() {
+        if let Err(e) = level_file::load_level_file(level_id) {
+            panic!("Failed to load {}! {}", level_id, e);
+        }
+    }
+}
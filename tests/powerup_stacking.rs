@@ -0,0 +1,87 @@
+//! Checks how picking up a power-up that's already active changes how long it has left.
+
+use brickbonker::game::powerup::{PowerUpKind, PowerUpStacking, PowerUpState};
+
+/// How long a power-up has left, or `None` if it isn't active.
+fn ticks_remaining(powerups: &PowerUpState, kind: PowerUpKind) -> Option<u32> {
+    powerups
+        .active
+        .iter()
+        .find(|p| p.kind == kind)
+        .map(|p| p.ticks_remaining)
+}
+
+/// Pretend the power-up has been active for a while.
+fn set_ticks_remaining(powerups: &mut PowerUpState, kind: PowerUpKind, ticks: u32) {
+    for active in powerups.active.iter_mut().filter(|p| p.kind == kind) {
+        active.ticks_remaining = ticks;
+    }
+}
+
+#[test]
+fn instant_powerups_never_become_active() {
+    let kind = PowerUpKind::MultiBall;
+    assert_eq!(kind.stacking(), PowerUpStacking::Instant);
+
+    let mut powerups = PowerUpState::new();
+    powerups.activate(kind);
+    powerups.activate(kind);
+
+    assert!(!powerups.is_active(kind));
+    assert!(powerups.active.is_empty());
+}
+
+#[test]
+fn refresh_resets_the_remaining_duration() {
+    let kind = PowerUpKind::WidePaddle;
+    assert_eq!(kind.stacking(), PowerUpStacking::Refresh);
+
+    let mut powerups = PowerUpState::new();
+    powerups.activate(kind);
+    assert_eq!(
+        ticks_remaining(&powerups, kind),
+        Some(kind.duration_ticks())
+    );
+
+    set_ticks_remaining(&mut powerups, kind, 100);
+    powerups.activate(kind);
+    assert_eq!(
+        ticks_remaining(&powerups, kind),
+        Some(kind.duration_ticks())
+    );
+    assert_eq!(powerups.active.len(), 1);
+}
+
+#[test]
+fn extend_adds_to_the_remaining_duration_up_to_the_maximum() {
+    let kind = PowerUpKind::SlowBall;
+    let max_ticks = match kind.stacking() {
+        PowerUpStacking::Extend { max_ticks } => max_ticks,
+        stacking => panic!("Expected SlowBall to extend, found {:?}", stacking),
+    };
+
+    let mut powerups = PowerUpState::new();
+    powerups.activate(kind);
+    set_ticks_remaining(&mut powerups, kind, 100);
+    powerups.activate(kind);
+    assert_eq!(
+        ticks_remaining(&powerups, kind),
+        Some(100 + kind.duration_ticks())
+    );
+
+    for _ in 0..5 {
+        powerups.activate(kind);
+    }
+    assert_eq!(ticks_remaining(&powerups, kind), Some(max_ticks));
+    assert_eq!(powerups.active.len(), 1);
+}
+
+#[test]
+fn exclusive_powerups_replace_each_other() {
+    let mut powerups = PowerUpState::new();
+    powerups.activate(PowerUpKind::StickyPaddle);
+    powerups.activate(PowerUpKind::Laser);
+
+    assert!(powerups.is_active(PowerUpKind::Laser));
+    assert!(!powerups.is_active(PowerUpKind::StickyPaddle));
+}
@@ -0,0 +1,143 @@
+//! Golden image tests for the render pipeline. Each test draws a known scene with the software renderer and compares
+//! it against `tests/goldens/<name>.png`.
+//!
+//! Goldens are only ever written by running the tests with `BLESS=1`, after an intended change to how the game looks
+//! (or when adding a test), and the new images then need checking in. A missing golden fails the test. On a mismatch
+//! the actual frame and a diff image (with the mismatched pixels in red) are saved to `target/golden-failures/`.
+
+use brickbonker::{
+    game::{
+        flow::FlowState,
+        level::LevelState,
+        powerup::{ActivePowerUp, PowerUpKind, PowerUpState},
+    },
+    headless::HeadlessRunner,
+};
+use gfx::{image::RgbaImage, screenshot};
+use std::path::{Path, PathBuf};
+
+const SCREEN_WIDTH: u32 = 320;
+const SCREEN_HEIGHT: u32 = 240;
+const SEED: u64 = 1;
+const FIRST_LEVEL_ID: &str = "level-01";
+
+// How far any channel of a pixel can be from the golden before it counts as a mismatch
+const GOLDEN_TOLERANCE: u8 = 2;
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("goldens")
+        .join(format!("{}.png", name))
+}
+
+fn failure_path(name: &str, suffix: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("golden-failures")
+        .join(format!("{}.{}.png", name, suffix))
+}
+
+fn save(image: &RgbaImage, path: &Path) {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).expect("Failed to create directory!");
+    }
+
+    if let Err(e) = image.save(path) {
+        panic!("Failed to save image {}! {}", path.display(), e);
+    }
+}
+
+fn assert_matches_golden(name: &str, actual: &RgbaImage) {
+    let path = golden_path(name);
+    if std::env::var_os("BLESS").is_some() {
+        save(actual, &path);
+        println!("Wrote golden image {}", path.display());
+        return;
+    }
+
+    if !path.exists() {
+        panic!(
+            "Missing golden image {}! Run the tests with `BLESS=1` to write it, then check it in.",
+            path.display()
+        );
+    }
+
+    let expected = match gfx::image::open(&path) {
+        Ok(image) => image.to_rgba(),
+        Err(e) => panic!("Failed to open golden image {}! {}", path.display(), e),
+    };
+
+    let diff = screenshot::diff_images(&expected, actual, GOLDEN_TOLERANCE);
+    if !diff.matches() {
+        let actual_path = failure_path(name, "actual");
+        let diff_path = failure_path(name, "diff");
+        save(actual, &actual_path);
+        save(&diff.image, &diff_path);
+
+        panic!(
+            "Frame doesn't match golden image {}: {} pixels differ (by up to {}). Saved the frame to {} and a diff to {}",
+            path.display(),
+            diff.mismatched_pixels,
+            diff.max_difference,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+/// Start a new game on the first level, and run a tick so it gets loaded.
+fn start_first_level() -> HeadlessRunner<'static, 'static> {
+    let mut runner = HeadlessRunner::new(SCREEN_WIDTH, SCREEN_HEIGHT, SEED);
+    runner.game.start_game(FIRST_LEVEL_ID);
+    runner.run_idle(1);
+    runner
+}
+
+#[test]
+fn title_screen() {
+    let mut runner = HeadlessRunner::new(SCREEN_WIDTH, SCREEN_HEIGHT, SEED);
+    runner.run_idle(1);
+
+    assert_matches_golden("title_screen", &runner.capture_frame());
+}
+
+#[test]
+fn initial_level() {
+    let mut runner = start_first_level();
+
+    assert_matches_golden("initial_level", &runner.capture_frame());
+}
+
+#[test]
+fn hud_text() {
+    let mut runner = start_first_level();
+    {
+        let mut level = runner.game.world.write_resource::<LevelState>();
+        level.score = 12345;
+        level.lives = 2;
+
+        let mut powerups = runner.game.world.write_resource::<PowerUpState>();
+        powerups.active.push(ActivePowerUp {
+            kind: PowerUpKind::WidePaddle,
+            ticks_remaining: 600,
+        });
+        powerups.active.push(ActivePowerUp {
+            kind: PowerUpKind::Laser,
+            ticks_remaining: 90,
+        });
+    }
+
+    assert_matches_golden("hud_text", &runner.capture_frame());
+}
+
+#[test]
+fn game_over_overlay() {
+    let mut runner = start_first_level();
+    runner.game.world.write_resource::<LevelState>().score = 500;
+    *runner.game.world.write_resource::<FlowState>() = FlowState::GameOver {
+        campaign_complete: false,
+    };
+
+    assert_matches_golden("game_over_overlay", &runner.capture_frame());
+}
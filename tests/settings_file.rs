@@ -0,0 +1,115 @@
+//! Checks reading and writing the settings file, and that the options screen changes settings as it's used.
+
+use brickbonker::{
+    game::{
+        audio::{AudioBus, AudioMixer},
+        flow::FlowState,
+        settings::{Settings, SettingsError},
+    },
+    headless::HeadlessRunner,
+};
+use gfx::input::{InputAction, InputState, VirtualKeyCode};
+use std::path::Path;
+
+const SCREEN_WIDTH: u32 = 320;
+const SCREEN_HEIGHT: u32 = 240;
+const SEED: u64 = 1;
+
+fn parse(source: &str) -> (Settings, Vec<SettingsError>) {
+    Settings::parse(Path::new("settings.txt"), source)
+}
+
+/// Parse a file that has nothing wrong with it.
+fn parse_valid(source: &str) -> Settings {
+    let (settings, errors) = parse(source);
+    assert!(errors.is_empty(), "{:?}", errors);
+    settings
+}
+
+fn press(key: VirtualKeyCode) -> InputState {
+    InputState::from_keys(&[key], &[key], &[])
+}
+
+#[test]
+fn settings_survive_saving() {
+    let mut settings = Settings::default();
+    settings.render_scale = 3.5;
+    settings.fullscreen = true;
+    settings.music_volume = 0.25;
+    settings.screen_shake = false;
+    settings.key_bindings.set_keys(
+        InputAction::Launch,
+        vec![VirtualKeyCode::Up, VirtualKeyCode::W],
+    );
+
+    assert_eq!(parse_valid(&settings.to_source()), settings);
+}
+
+#[test]
+fn missing_settings_keep_their_defaults() {
+    let settings = parse_valid("// Quieter music\nmusic_volume: 0.5\nbind: launch Return\n");
+
+    assert_eq!(settings.music_volume, 0.5);
+    assert_eq!(settings.master_volume, 1.0);
+    assert_eq!(
+        settings.key_bindings.keys(InputAction::Launch),
+        &[VirtualKeyCode::Return]
+    );
+    assert_eq!(
+        settings.key_bindings.keys(InputAction::MoveLeft),
+        &[VirtualKeyCode::A, VirtualKeyCode::Left]
+    );
+}
+
+#[test]
+fn invalid_lines_are_reported() {
+    let line_of = |source: &str| match parse(source).1.as_slice() {
+        [SettingsError::Parse { line, .. }] => *line,
+        errors => panic!("Expected a parse error, got {:?}", errors),
+    };
+
+    assert_eq!(line_of("vsync: false\nfullscreen yes"), 2);
+    assert_eq!(line_of("vsync: sometimes"), 1);
+    assert_eq!(line_of("bind: jump Space"), 1);
+    assert_eq!(line_of("bind: launch Spacebar"), 1);
+    assert_eq!(line_of("\nbrightness: 2"), 2);
+}
+
+#[test]
+fn invalid_lines_are_skipped() {
+    let (settings, errors) =
+        parse("music_volume: 0.5\nvsync: sometimes\nbrightness: 2\nfullscreen: true\n");
+
+    assert_eq!(errors.len(), 2);
+    assert_eq!(settings.music_volume, 0.5);
+    assert!(settings.fullscreen);
+    assert_eq!(settings.vsync, Settings::default().vsync);
+}
+
+#[test]
+fn options_screen_changes_volume() {
+    let mut runner = HeadlessRunner::new(SCREEN_WIDTH, SCREEN_HEIGHT, SEED);
+
+    // Open the options screen from the title menu, move down to the music volume and turn it down a step
+    runner.run_script(7, |tick| match tick {
+        0 => press(VirtualKeyCode::Down),
+        1 => press(VirtualKeyCode::Return),
+        2..=5 => press(VirtualKeyCode::Down),
+        _ => press(VirtualKeyCode::Left),
+    });
+
+    let music_volume = runner.game.world.read_resource::<Settings>().music_volume;
+    assert!((music_volume - 0.9).abs() < 1e-6);
+    let mixer_volume = runner
+        .game
+        .world
+        .read_resource::<AudioMixer>()
+        .bus_volume(AudioBus::Music);
+    assert_eq!(mixer_volume, music_volume);
+
+    runner.tick(&press(VirtualKeyCode::Escape));
+    assert_eq!(
+        *runner.game.world.read_resource::<FlowState>(),
+        FlowState::Title
+    );
+}
@@ -0,0 +1,127 @@
+//! Checks that sound cues parse, avoid repeating clips when asked to, and ignore triggers while cooling down.
+
+use brickbonker::game::{
+    assets::{Assets, ASSET_MANIFEST_PATH},
+    audio::{AudioBus, AudioMixer, AudioOutput},
+    sound_cue::{self, SoundCues, SOUND_CUES_PATH},
+};
+use std::path::Path;
+
+const SEED: u64 = 1;
+const TRIGGER_COUNT: u64 = 50;
+
+fn cues(source: &str) -> SoundCues {
+    SoundCues::new(
+        sound_cue::parse_cues(Path::new("test"), source).unwrap(),
+        SEED,
+    )
+}
+
+fn error_line(source: &str) -> usize {
+    match sound_cue::parse_cues(Path::new("test"), source) {
+        Err(sound_cue::SoundCueError::Parse { line, .. }) => line,
+        result => panic!("Expected a parse error, found {:?}", result.map(|_| ())),
+    }
+}
+
+fn load_assets() -> Assets {
+    let (assets, result) = Assets::load(Path::new(ASSET_MANIFEST_PATH));
+    result.unwrap();
+    assets
+}
+
+#[test]
+fn cues_parse() {
+    let source = "// A comment\ncue bounce\nclip: ball-bounce-0\nclip: ball-bounce-1 3\nbus: ui\n\
+                  volume: 0.5 1\npitch: 0.9\nno_repeat: true\ncooldown: 4\n";
+    let parsed = sound_cue::parse_cues(Path::new("test"), source).unwrap();
+    assert_eq!(parsed.len(), 1);
+
+    let cue = &parsed[0];
+    assert_eq!(cue.name, "bounce");
+    assert_eq!(cue.clips.len(), 2);
+    assert_eq!(cue.clips[1].sound, "ball-bounce-1");
+    assert_eq!(cue.clips[1].weight, 3.0);
+    assert_eq!(cue.bus, AudioBus::Ui);
+    assert_eq!(cue.volume, (0.5, 1.0));
+    assert_eq!(cue.pitch, (0.9, 0.9));
+    assert!(cue.no_repeat);
+    assert_eq!(cue.cooldown_ticks, 4);
+}
+
+#[test]
+fn mistakes_are_reported_at_their_line() {
+    assert_eq!(error_line("clip: ball-bounce-0\n"), 1);
+    assert_eq!(
+        error_line("cue bounce\nclip: ball-bounce-0\nbus: loud\n"),
+        3
+    );
+    assert_eq!(
+        error_line("cue bounce\nclip: ball-bounce-0\n\nreverb: 2\n"),
+        4
+    );
+    assert_eq!(error_line("cue bounce\nclip: ball-bounce-0 -1\n"), 2);
+    assert_eq!(
+        error_line("cue bounce\nclip: ball-bounce-0\ncue bounce\n"),
+        3
+    );
+    assert_eq!(
+        error_line("cue bounce\n\ncue hit\nclip: ball-wall-hit-0\n"),
+        1
+    );
+}
+
+#[test]
+fn no_repeat_never_picks_the_same_clip_twice_in_a_row() {
+    let assets = load_assets();
+    let mut mixer = AudioMixer::new(AudioOutput::Capture);
+    let mut cues =
+        cues("cue bounce\nclip: ball-bounce-0\nclip: ball-wall-hit-0 5\nno_repeat: true\n");
+
+    for _ in 0..TRIGGER_COUNT {
+        assert!(cues.trigger("bounce", &assets, &mut mixer).is_some());
+        mixer.update();
+    }
+
+    let sounds = mixer.captured_sounds();
+    assert_eq!(sounds.len(), TRIGGER_COUNT as usize);
+    assert!(sounds.windows(2).all(|pair| pair[0].name != pair[1].name));
+}
+
+#[test]
+fn triggers_are_ignored_while_cooling_down() {
+    let assets = load_assets();
+    let mut mixer = AudioMixer::new(AudioOutput::Capture);
+    let mut cues = cues("cue hit\nclip: ball-wall-hit-0\ncooldown: 3\n");
+
+    let played: Vec<bool> = (0..7)
+        .map(|_| {
+            let played = cues.trigger("hit", &assets, &mut mixer).is_some();
+            mixer.update();
+            played
+        })
+        .collect();
+    assert_eq!(played, [true, false, false, true, false, false, true]);
+
+    let ticks: Vec<u64> = mixer.captured_sounds().iter().map(|s| s.tick).collect();
+    assert_eq!(ticks, [0, 3, 6]);
+}
+
+#[test]
+fn cue_file_only_uses_loaded_sounds() {
+    let assets = load_assets();
+    let cues = SoundCues::load(Path::new(SOUND_CUES_PATH), SEED).unwrap();
+
+    let source = std::fs::read_to_string(SOUND_CUES_PATH).unwrap();
+    for cue in sound_cue::parse_cues(Path::new(SOUND_CUES_PATH), &source).unwrap() {
+        assert!(cues.cue(&cue.name).is_some());
+        for clip in cue.clips.iter() {
+            assert!(
+                assets.sound(&clip.sound).is_some(),
+                "Cue `{}` plays `{}`, which isn't in the asset manifest",
+                cue.name,
+                clip.sound
+            );
+        }
+    }
+}